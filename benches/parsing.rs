@@ -0,0 +1,163 @@
+// criterion does not build for WASM, so the benchmarks only run natively
+#[cfg(not(target_arch = "wasm32"))]
+mod parsing {
+    use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+    use serde::{Deserialize, Serialize};
+    use signalr_client::{HubMessage, HubPayload, HubProtocol, JsonHubProtocol};
+
+    const ITEMS: usize = 100_000;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestEntity {
+        text: String,
+        number: i32,
+        tags: Vec<String>,
+        values: Vec<f64>,
+    }
+
+    /// The messages of a stream of `ITEMS` entities.
+    fn stream_items() -> Vec<HubMessage> {
+        (0..ITEMS).map(|i| HubMessage::StreamItem {
+            invocation_id: "1".to_string(),
+            item: HubPayload::from(serde_json::json!({
+                "text": format!("item {}", i),
+                "number": i,
+                "tags": ["first", "second", "third", "fourth"],
+                "values": [0.5, 1.25, 2.125, 3.0625, 4.5, 5.75, 6.875, 7.0],
+            })),
+        }).collect()
+    }
+
+    fn write_frame(protocol: &dyn HubProtocol, messages: &[HubMessage]) -> Vec<u8> {
+        messages.iter().flat_map(|message| protocol.write_message(message).unwrap()).collect()
+    }
+
+    /// Parses the frame once into messages, then deserializes the items from their slices.
+    fn parse_once(protocol: &dyn HubProtocol, frame: &[u8]) -> usize {
+        protocol.parse_messages(frame).into_iter()
+            .filter_map(|message| match message {
+                Ok(HubMessage::StreamItem { item, .. }) => item.to_type::<TestEntity>().ok(),
+                _ => None,
+            })
+            .count()
+    }
+
+    #[derive(Deserialize)]
+    struct Ping {
+        #[allow(dead_code)]
+        r#type: u8,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PossibleInvocation {
+        #[allow(dead_code)]
+        invocation_id: Option<String>,
+        #[allow(dead_code)]
+        target: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct StreamItem<T> {
+        item: T,
+    }
+
+    /// Copies every message, and parses it three times, for its type, for its invocation id and for its item, as the client did before.
+    fn parse_three_times(frame: &[u8]) -> usize {
+        std::str::from_utf8(frame).unwrap()
+            .split('\u{1e}')
+            .filter(|message| !message.is_empty())
+            .map(|message| message.to_string())
+            .filter(|message| serde_json::from_str::<Ping>(message).is_ok())
+            .filter(|message| serde_json::from_str::<PossibleInvocation>(message).is_ok())
+            .filter_map(|message| serde_json::from_str::<StreamItem<TestEntity>>(&message).ok().map(|m| m.item))
+            .count()
+    }
+
+    /// Decodes every message to MessagePack values twice, and encodes the item again to deserialize it, as the client did before.
+    #[cfg(feature = "messagepack")]
+    fn decode_three_times(frame: &[u8]) -> usize {
+        let mut count = 0;
+        let mut rest = frame;
+
+        while !rest.is_empty() {
+            let (length, read) = read_length(rest);
+            let message = rest[read..read + length].to_vec();
+            rest = &rest[read + length..];
+
+            let _type = rmpv::decode::read_value(&mut message.as_slice()).unwrap();
+            let _routing = rmpv::decode::read_value(&mut message.as_slice()).unwrap();
+            let items = rmpv::decode::read_value(&mut message.as_slice()).unwrap();
+
+            let mut item = Vec::new();
+            rmpv::encode::write_value(&mut item, &items.as_array().unwrap()[3]).unwrap();
+
+            if rmp_serde::from_slice::<TestEntity>(&item).is_ok() {
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    /// Reads the variable length prefix of a MessagePack frame.
+    #[cfg(feature = "messagepack")]
+    fn read_length(data: &[u8]) -> (usize, usize) {
+        let mut length = 0;
+
+        for (i, byte) in data.iter().enumerate() {
+            length |= ((byte & 0x7f) as usize) << (7 * i);
+
+            if byte & 0x80 == 0 {
+                return (length, i + 1);
+            }
+        }
+
+        panic!("The frame ended in its length prefix")
+    }
+
+    fn json_stream(c: &mut Criterion) {
+        let frame = write_frame(&JsonHubProtocol, &stream_items());
+        let mut group = c.benchmark_group("json_stream");
+        group.throughput(Throughput::Elements(ITEMS as u64));
+        group.sample_size(20);
+
+        group.bench_with_input(BenchmarkId::new("parse_three_times", ITEMS), &frame, |b, frame| {
+            b.iter(|| assert_eq!(parse_three_times(black_box(frame)), ITEMS))
+        });
+        group.bench_with_input(BenchmarkId::new("parse_once", ITEMS), &frame, |b, frame| {
+            b.iter(|| assert_eq!(parse_once(&JsonHubProtocol, black_box(frame)), ITEMS))
+        });
+
+        group.finish();
+    }
+
+    #[cfg(feature = "messagepack")]
+    fn msgpack_stream(c: &mut Criterion) {
+        let protocol = signalr_client::MessagePackHubProtocol;
+        let frame = write_frame(&protocol, &stream_items());
+        let mut group = c.benchmark_group("msgpack_stream");
+        group.throughput(Throughput::Elements(ITEMS as u64));
+        group.sample_size(20);
+
+        group.bench_with_input(BenchmarkId::new("decode_three_times", ITEMS), &frame, |b, frame| {
+            b.iter(|| assert_eq!(decode_three_times(black_box(frame)), ITEMS))
+        });
+        group.bench_with_input(BenchmarkId::new("parse_once", ITEMS), &frame, |b, frame| {
+            b.iter(|| assert_eq!(parse_once(&protocol, black_box(frame)), ITEMS))
+        });
+
+        group.finish();
+    }
+
+    #[cfg(not(feature = "messagepack"))]
+    fn msgpack_stream(_: &mut Criterion) {}
+
+    criterion_group!(benches, json_stream, msgpack_stream);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+criterion::criterion_main!(parsing::benches);
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
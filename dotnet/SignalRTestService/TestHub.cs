@@ -1,10 +1,15 @@
 ﻿using Microsoft.AspNetCore.SignalR;
+using System.Collections.Concurrent;
 using System.Diagnostics;
+using System.Runtime.CompilerServices;
 
 namespace SignalRTestService
 {
     public class TestHub : Hub
     {
+        private static readonly ConcurrentDictionary<string, bool> CancelledStreams = new();
+        private static readonly ConcurrentDictionary<string, bool> DisconnectedClients = new();
+
         public async Task<TestEntity> SingleEntity()
         {
             await Task.CompletedTask;
@@ -49,6 +54,46 @@ namespace SignalRTestService
             }
         }
 
+        public async IAsyncEnumerable<TestEntity> EndlessEntities([EnumeratorCancellation] CancellationToken cancellationToken)
+        {
+            var i = 0;
+            CancelledStreams[Context.ConnectionId] = false;
+
+            try
+            {
+                while (!cancellationToken.IsCancellationRequested)
+                {
+                    i++;
+
+                    yield return new TestEntity
+                    {
+                        Number = i,
+                        Text = $"test {i}"
+                    };
+
+                    await Task.Delay(1, CancellationToken.None);
+                }
+            }
+            finally
+            {
+                CancelledStreams[Context.ConnectionId] = cancellationToken.IsCancellationRequested;
+            }
+        }
+
+        public async Task<bool> IsStreamCancelled()
+        {
+            await Task.CompletedTask;
+
+            return CancelledStreams.TryGetValue(Context.ConnectionId, out var cancelled) && cancelled;
+        }
+
+        public async Task<bool> IsDisconnected(string connectionId)
+        {
+            await Task.CompletedTask;
+
+            return DisconnectedClients.ContainsKey(connectionId);
+        }
+
         public async Task<bool> PushEntity(TestEntity entity)
         {
             await Task.CompletedTask;
@@ -67,6 +112,20 @@ namespace SignalRTestService
             };
         }
 
+        public async Task<string> GetConnectionId()
+        {
+            await Task.CompletedTask;
+
+            return Context.ConnectionId;
+        }
+
+        public async Task Abort()
+        {
+            await Task.CompletedTask;
+
+            Context.Abort();
+        }
+
         public async Task TriggerCallback(string callback)
         {
             await Clients.Caller.SendAsync(callback);
@@ -102,6 +161,7 @@ namespace SignalRTestService
         public override Task OnDisconnectedAsync(Exception? exception)
         {
             Debug.WriteLine($"Client is disconnected: {Context.ConnectionId}");
+            DisconnectedClients[Context.ConnectionId] = true;
 
             return base.OnDisconnectedAsync(exception);
         }
@@ -0,0 +1,164 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{spanned::Spanned, FnArg, ImplItem, ImplItemFn, ItemImpl, Result, ReturnType, Type};
+
+use crate::naming::NamingOptions;
+
+/// The name of the generated method that registers the handlers on a `SignalRClient`.
+const REGISTER_METHOD: &str = "register_handlers";
+
+struct HandlerMethod {
+    method: syn::Ident,
+    hub_name: String,
+    arguments: Vec<Type>,
+    is_async: bool,
+    has_result: bool,
+}
+
+pub(crate) fn expand(options: NamingOptions, mut item: ItemImpl) -> Result<TokenStream> {
+    if item.trait_.is_some() {
+        return Err(syn::Error::new(item.span(), "hub_client must be placed on an inherent impl block"));
+    }
+
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new(item.generics.span(), "hub client handlers cannot be generic"));
+    }
+
+    let mut handlers = Vec::new();
+
+    for impl_item in item.items.iter_mut() {
+        if let ImplItem::Fn(method) = impl_item {
+            handlers.push(parse_method(&options, method)?);
+        }
+    }
+
+    let self_ty = &item.self_ty;
+    let register_method = format_ident!("{}", REGISTER_METHOD);
+    let registrations = handlers.iter().map(register_handler);
+
+    Ok(quote! {
+        #item
+
+        impl #self_ty {
+            /// Registers every method of this handler as a callback on the given client.
+            ///
+            /// The returned registrations unregister all the callbacks when dropped.
+            pub fn #register_method(self, client: &mut ::signalr_client::SignalRClient) -> ::signalr_client::CallbackRegistrations {
+                let handlers = ::std::sync::Arc::new(self);
+                let mut registrations = ::signalr_client::CallbackRegistrations::new();
+
+                #(#registrations)*
+
+                registrations
+            }
+        }
+    })
+}
+
+fn parse_method(options: &NamingOptions, method: &mut ImplItemFn) -> Result<HandlerMethod> {
+    if !method.sig.generics.params.is_empty() {
+        return Err(syn::Error::new(method.sig.generics.span(), "hub client handlers cannot be generic"));
+    }
+
+    let hub_name = options.take_hub_name(&mut method.attrs, &method.sig.ident.to_string())?;
+    let mut arguments = Vec::new();
+    let mut has_receiver = false;
+
+    for input in method.sig.inputs.iter() {
+        match input {
+            FnArg::Receiver(receiver) => {
+                if receiver.reference.is_none() || receiver.mutability.is_some() {
+                    return Err(syn::Error::new(receiver.span(), "hub client handlers must take `&self`"));
+                }
+
+                has_receiver = true;
+            },
+            FnArg::Typed(typed) => {
+                if let Type::Reference(reference) = typed.ty.as_ref() {
+                    return Err(syn::Error::new(reference.span(), "hub client handler arguments must be owned types"));
+                }
+
+                arguments.push(typed.ty.as_ref().clone());
+            },
+        }
+    }
+
+    if !has_receiver {
+        return Err(syn::Error::new(method.sig.span(), "hub client handlers must take `&self`"));
+    }
+
+    let has_result = match &method.sig.output {
+        ReturnType::Default => false,
+        ReturnType::Type(_, ty) => !matches!(ty.as_ref(), Type::Tuple(tuple) if tuple.elems.is_empty()),
+    };
+
+    Ok(HandlerMethod {
+        method: method.sig.ident.clone(),
+        hub_name,
+        arguments,
+        is_async: method.sig.asyncness.is_some(),
+        has_result,
+    })
+}
+
+fn register_handler(handler: &HandlerMethod) -> TokenStream {
+    let method = &handler.method;
+    let hub_name = &handler.hub_name;
+    let names: Vec<_> = (0..handler.arguments.len()).map(|i| format_ident!("arg{}", i)).collect();
+    let bindings = handler.arguments.iter().zip(names.iter()).enumerate().map(|(index, (ty, name))| {
+        quote! {
+            let #name = match ctx.argument::<#ty>(#index) {
+                ::core::result::Result::Ok(value) => value,
+                ::core::result::Result::Err(e) => {
+                    ::signalr_client::__private::reject_invocation(ctx, e);
+                    return;
+                },
+            };
+        }
+    });
+
+    let call = if handler.is_async {
+        quote! { handlers.#method(#(#names),*).await }
+    } else {
+        quote! { handlers.#method(#(#names),*) }
+    };
+
+    let respond = if handler.has_result {
+        quote! {
+            let result = #call;
+            ::signalr_client::__private::complete_invocation(ctx, result).await;
+        }
+    } else {
+        quote! { #call; }
+    };
+
+    let dispatch = if handler.is_async || handler.has_result {
+        quote! {
+            let handlers = ::std::sync::Arc::clone(&handlers);
+
+            ::signalr_client::InvocationContext::spawn(async move {
+                #respond
+            });
+        }
+    } else {
+        quote! { #call; }
+    };
+
+    // the context is needed only to bind arguments and to send back the result
+    let context = if handler.arguments.is_empty() && !handler.has_result {
+        format_ident!("_ctx")
+    } else {
+        format_ident!("ctx")
+    };
+
+    quote! {
+        {
+            let handlers = ::std::sync::Arc::clone(&handlers);
+
+            registrations.add(client.register(#hub_name.to_string(), move |#context: ::signalr_client::InvocationContext| {
+                #(#bindings)*
+                #dispatch
+            }));
+        }
+    }
+}
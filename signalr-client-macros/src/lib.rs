@@ -0,0 +1,108 @@
+//! Procedural macros for the `signalr-client` crate.
+//!
+//! These macros are re-exported by `signalr-client` when its `macros` feature is enabled,
+//! use them through that crate instead of depending on this one directly.
+
+mod client;
+mod naming;
+mod proxy;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, ItemImpl, ItemTrait};
+
+/// Generates a strongly-typed proxy for a SignalR hub and implements it for `SignalRClient`.
+///
+/// Every method of the trait is mapped to a hub method:
+///
+/// * `async fn x(&self, ..) -> T` invokes the hub method and waits for the result. The generated
+///   method returns `Result<T, String>`.
+/// * `async fn x(&self, ..)` sends the hub method without waiting for a completion. The generated
+///   method returns `Result<(), String>`.
+/// * `fn x(&self, ..) -> impl Stream<Item = T>` calls a streaming (`IAsyncEnumerable`) hub method.
+///
+/// The method names are converted to PascalCase by default. Use `#[hub_proxy(rename_all = "camelCase")]`
+/// or `#[hub_proxy(rename_all = "none")]` to change the rule for the whole trait, or
+/// `#[hub_method(name = "...")]` to name a single method explicitly.
+///
+/// # Examples
+///
+/// ```ignore
+/// #[hub_proxy]
+/// trait TestHub {
+///     async fn single_entity(&self) -> TestEntity;
+///     async fn push_two_entities(&self, a: TestEntity, b: TestEntity) -> TestEntity;
+///     #[hub_method(name = "TriggerEntityCallback")]
+///     async fn trigger(&self, callback: String);
+///     fn hundred_entities(&self) -> impl Stream<Item = TestEntity>;
+/// }
+///
+/// let client = SignalRClient::connect("localhost", "test").await.unwrap();
+/// let merged = client.push_two_entities(entity1, entity2).await?;
+/// let mut stream = client.hundred_entities();
+/// while let Some(entity) = stream.next().await {
+///     info!("Entity {}, {}", entity.text, entity.number);
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn hub_proxy(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = naming::NamingOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta, "hub_proxy"));
+    parse_macro_input!(args with parser);
+
+    let item = parse_macro_input!(input as ItemTrait);
+
+    proxy::expand(options, item)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Registers every method of an impl block as a client-side handler that the SignalR hub can call.
+///
+/// The macro generates a `register_handlers(self, client: &mut SignalRClient)` method, which registers
+/// each method through `SignalRClient::register` and returns a single `CallbackRegistrations` guard.
+/// Dropping the guard (or calling its `unregister` method) unregisters all the handlers at once.
+///
+/// * The arguments sent by the hub are bound to the method parameters in order. A parameter that
+///   cannot be deserialized is reported back to the hub as an error completion.
+/// * Methods returning a value answer client-result invocations (`Clients.Caller.InvokeAsync`)
+///   by sending the value back as the completion.
+/// * `async` methods are spawned, so they do not block the processing of incoming messages.
+///
+/// The hub method names follow the same rules as in [`macro@hub_proxy`]: PascalCase by default,
+/// configurable with `#[hub_client(rename_all = "...")]` or `#[hub_method(name = "...")]`.
+///
+/// # Examples
+///
+/// ```ignore
+/// struct ChatHandlers;
+///
+/// #[hub_client]
+/// impl ChatHandlers {
+///     fn receive_message(&self, user: String, text: String) {
+///         info!("{}: {}", user, text);
+///     }
+///
+///     async fn get_status(&self) -> Status {
+///         Status::Online
+///     }
+/// }
+///
+/// let mut client = SignalRClient::connect("localhost", "chat").await.unwrap();
+/// let registrations = ChatHandlers.register_handlers(&mut client);
+///
+/// // ...
+///
+/// registrations.unregister();
+/// ```
+#[proc_macro_attribute]
+pub fn hub_client(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = naming::NamingOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta, "hub_client"));
+    parse_macro_input!(args with parser);
+
+    let item = parse_macro_input!(input as ItemImpl);
+
+    client::expand(options, item)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
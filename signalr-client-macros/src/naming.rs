@@ -0,0 +1,129 @@
+use syn::{meta::ParseNestedMeta, Attribute, LitStr, Result};
+
+/// The attribute used to name a single hub method explicitly: `#[hub_method(name = "...")]`.
+const METHOD_ATTRIBUTE: &str = "hub_method";
+
+/// Options shared by the `#[hub_proxy(...)]` and `#[hub_client(...)]` attributes.
+#[derive(Default)]
+pub(crate) struct NamingOptions {
+    pub(crate) rename_all: RenameRule,
+}
+
+impl NamingOptions {
+    pub(crate) fn parse(&mut self, meta: ParseNestedMeta, macro_name: &str) -> Result<()> {
+        if meta.path.is_ident("rename_all") {
+            let literal: LitStr = meta.value()?.parse()?;
+            self.rename_all = RenameRule::from_literal(&literal)?;
+
+            Ok(())
+        } else {
+            Err(meta.error(format!("unsupported {} option, expected `rename_all`", macro_name)))
+        }
+    }
+
+    /// Removes the `#[hub_method]` attribute from the method and returns the hub method name,
+    /// falling back to the rename rule applied to the Rust method name.
+    pub(crate) fn take_hub_name(&self, attrs: &mut Vec<Attribute>, method_name: &str) -> Result<String> {
+        let mut hub_name = None;
+        let mut parse_error = None;
+
+        attrs.retain(|attr| {
+            if attr.path().is_ident(METHOD_ATTRIBUTE) {
+                let res = attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("name") {
+                        let literal: LitStr = meta.value()?.parse()?;
+                        hub_name = Some(literal.value());
+
+                        Ok(())
+                    } else {
+                        Err(meta.error("unsupported hub_method option, expected `name`"))
+                    }
+                });
+
+                if let Err(e) = res {
+                    parse_error = Some(e);
+                }
+
+                false
+            } else {
+                true
+            }
+        });
+
+        if let Some(e) = parse_error {
+            return Err(e);
+        }
+
+        Ok(hub_name.unwrap_or_else(|| self.rename_all.apply(method_name)))
+    }
+}
+
+/// The naming rule applied to Rust method names to get the hub method names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum RenameRule {
+    /// `push_two_entities` -> `PushTwoEntities` (default, matches .NET method naming)
+    #[default]
+    PascalCase,
+    /// `push_two_entities` -> `pushTwoEntities`
+    CamelCase,
+    /// The Rust method name is used as is
+    None,
+}
+
+impl RenameRule {
+    pub(crate) fn from_literal(literal: &LitStr) -> Result<Self> {
+        match literal.value().as_str() {
+            "PascalCase" => Ok(RenameRule::PascalCase),
+            "camelCase" => Ok(RenameRule::CamelCase),
+            "none" => Ok(RenameRule::None),
+            other => Err(syn::Error::new(
+                literal.span(),
+                format!("unknown rename rule `{}`, expected one of `PascalCase`, `camelCase` or `none`", other),
+            )),
+        }
+    }
+
+    pub(crate) fn apply(&self, name: &str) -> String {
+        match self {
+            RenameRule::PascalCase => to_pascal_case(name),
+            RenameRule::CamelCase => {
+                let pascal = to_pascal_case(name);
+                let mut chars = pascal.chars();
+
+                match chars.next() {
+                    Some(first) => first.to_lowercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            },
+            RenameRule::None => name.to_string(),
+        }
+    }
+}
+
+fn to_pascal_case(name: &str) -> String {
+    name.trim_start_matches("r#")
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_rules() {
+        assert_eq!(RenameRule::PascalCase.apply("push_two_entities"), "PushTwoEntities");
+        assert_eq!(RenameRule::PascalCase.apply("single_entity"), "SingleEntity");
+        assert_eq!(RenameRule::CamelCase.apply("push_two_entities"), "pushTwoEntities");
+        assert_eq!(RenameRule::None.apply("push_two_entities"), "push_two_entities");
+    }
+}
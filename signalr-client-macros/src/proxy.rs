@@ -0,0 +1,208 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, spanned::Spanned, FnArg, GenericArgument, Ident, ItemTrait, Pat,
+    PathArguments, Result, ReturnType, Signature, TraitItem, TraitItemFn, Type, TypeParamBound,
+};
+
+use crate::naming::NamingOptions;
+
+/// How a trait method is routed to the `SignalRClient`.
+enum MethodKind {
+    /// `async fn x(&self, ..) -> T` calls `invoke` and waits for the result.
+    Invoke(Type),
+    /// `async fn x(&self, ..)` calls `send` without waiting for a completion.
+    Send,
+    /// `fn x(&self, ..) -> impl Stream<Item = T>` calls `enumerate`.
+    Stream(Type),
+}
+
+struct ProxyMethod {
+    signature: Signature,
+    hub_name: String,
+    arguments: Vec<Ident>,
+    kind: MethodKind,
+}
+
+pub(crate) fn expand(options: NamingOptions, mut item: ItemTrait) -> Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new(item.generics.span(), "hub proxy traits cannot be generic"));
+    }
+
+    let mut methods = Vec::new();
+
+    for trait_item in item.items.iter_mut() {
+        match trait_item {
+            TraitItem::Fn(method) => {
+                let proxy = parse_method(&options, method)?;
+
+                method.sig = proxy.signature.clone();
+                methods.push(proxy);
+            },
+            other => return Err(syn::Error::new(other.span(), "hub proxy traits can contain methods only")),
+        }
+    }
+
+    let trait_ident = &item.ident;
+    let implementations = methods.iter().map(implement_method);
+
+    Ok(quote! {
+        #item
+
+        impl #trait_ident for ::signalr_client::SignalRClient {
+            #(#implementations)*
+        }
+    })
+}
+
+fn parse_method(options: &NamingOptions, method: &mut TraitItemFn) -> Result<ProxyMethod> {
+    if method.default.is_some() {
+        return Err(syn::Error::new(method.span(), "hub proxy methods cannot have a default implementation"));
+    }
+
+    if !method.sig.generics.params.is_empty() {
+        return Err(syn::Error::new(method.sig.generics.span(), "hub proxy methods cannot be generic"));
+    }
+
+    let hub_name = options.take_hub_name(&mut method.attrs, &method.sig.ident.to_string())?;
+
+    let mut signature = method.sig.clone();
+    let arguments = parse_arguments(&mut signature)?;
+
+    let kind = if signature.asyncness.is_some() {
+        let output = match &signature.output {
+            ReturnType::Default => None,
+            ReturnType::Type(_, ty) => match ty.as_ref() {
+                Type::Tuple(tuple) if tuple.elems.is_empty() => None,
+                ty => Some(ty.clone()),
+            },
+        };
+
+        signature.asyncness = None;
+
+        match output {
+            Some(ty) => {
+                signature.output = parse_quote! {
+                    -> impl ::core::future::Future<Output = ::core::result::Result<#ty, ::std::string::String>> + ::signalr_client::__private::MaybeSend
+                };
+
+                MethodKind::Invoke(ty)
+            },
+            None => {
+                signature.output = parse_quote! {
+                    -> impl ::core::future::Future<Output = ::core::result::Result<(), ::std::string::String>> + ::signalr_client::__private::MaybeSend
+                };
+
+                MethodKind::Send
+            },
+        }
+    } else if let Some(item) = stream_item(&signature.output) {
+        MethodKind::Stream(item)
+    } else {
+        return Err(syn::Error::new(
+            signature.span(),
+            "hub proxy methods must either be `async` or return `impl Stream<Item = T>`",
+        ));
+    };
+
+    Ok(ProxyMethod {
+        signature,
+        hub_name,
+        arguments,
+        kind,
+    })
+}
+
+/// Checks the receiver, strips argument patterns to plain identifiers and returns the identifiers.
+fn parse_arguments(signature: &mut Signature) -> Result<Vec<Ident>> {
+    let mut arguments = Vec::new();
+    let mut has_receiver = false;
+
+    for (index, input) in signature.inputs.iter_mut().enumerate() {
+        match input {
+            FnArg::Receiver(receiver) => {
+                if receiver.reference.is_none() || receiver.mutability.is_some() {
+                    return Err(syn::Error::new(receiver.span(), "hub proxy methods must take `&self`"));
+                }
+
+                has_receiver = true;
+            },
+            FnArg::Typed(typed) => {
+                let ident = match typed.pat.as_ref() {
+                    Pat::Ident(pat) => pat.ident.clone(),
+                    _ => format_ident!("arg{}", index),
+                };
+
+                typed.pat = parse_quote!(#ident);
+                arguments.push(ident);
+            },
+        }
+    }
+
+    if !has_receiver {
+        return Err(syn::Error::new(signature.span(), "hub proxy methods must take `&self`"));
+    }
+
+    Ok(arguments)
+}
+
+/// Returns `T` if the return type is `impl Stream<Item = T>`.
+fn stream_item(output: &ReturnType) -> Option<Type> {
+    let ReturnType::Type(_, ty) = output else { return None; };
+    let Type::ImplTrait(impl_trait) = ty.as_ref() else { return None; };
+
+    impl_trait.bounds.iter().find_map(|bound| {
+        let TypeParamBound::Trait(bound) = bound else { return None; };
+        let segment = bound.path.segments.last()?;
+
+        if segment.ident != "Stream" {
+            return None;
+        }
+
+        let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None; };
+
+        arguments.args.iter().find_map(|argument| match argument {
+            GenericArgument::AssocType(assoc) if assoc.ident == "Item" => Some(assoc.ty.clone()),
+            _ => None,
+        })
+    })
+}
+
+fn implement_method(method: &ProxyMethod) -> TokenStream {
+    let signature = &method.signature;
+    let hub_name = &method.hub_name;
+    let arguments = &method.arguments;
+    let configure = quote! {
+        |c: &mut ::signalr_client::ArgumentConfiguration| {
+            #( c.argument(&#arguments); )*
+        }
+    };
+
+    let body = match &method.kind {
+        MethodKind::Invoke(ty) => quote! {
+            async move {
+                client.invoke_with_args::<#ty, _>(#hub_name.to_string(), #configure).await
+            }
+        },
+        MethodKind::Send => quote! {
+            async move {
+                client.send_with_args(#hub_name.to_string(), #configure).await
+            }
+        },
+        MethodKind::Stream(ty) => quote! {
+            ::std::boxed::Box::pin(::signalr_client::__private::futures::StreamExt::flatten(
+                ::signalr_client::__private::futures::stream::once(async move {
+                    client.enumerate_with_args::<#ty, _>(#hub_name.to_string(), #configure).await
+                })
+            ))
+        },
+    };
+
+    quote! {
+        #signature {
+            let mut client = ::core::clone::Clone::clone(self);
+
+            #body
+        }
+    }
+}
@@ -1,19 +1,27 @@
 use std::sync::Arc;
-use futures::Stream;
-use log::info;
+use futures::{Stream, StreamExt};
+use log::{error, info, warn};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
 
-use crate::communication::{Communication, CommunicationClient, HttpClient};
-use crate::protocol::hub_protocol::HubProtocolKind;
+use crate::communication::{Communication, CommunicationClient, ConnectionStateTracker, HttpClient, HubConnectionState, NegotiationOptions};
+use crate::protocol::hub_protocol::HubMessage;
 use crate::protocol::invoke::Invocation;
-use crate::execution::{ArgumentConfiguration, CallbackHandler, Storage, StorageUnregistrationHandler, UpdatableActionStorage};
+use crate::protocol::value::HubValue;
+use crate::execution::{ArgumentConfiguration, CallbackHandler, EnumerableStream, Storage, StreamBuffer, StreamCancellation, StorageEntryGuard, StorageUnregistrationHandler, UpdatableActionStorage};
 
 use super::{ConnectionConfiguration, InvocationContext};
 
-#[cfg(not(target_arch = "wasm32"))]
 use crate::communication::ReconnectionContext;
 
+/// Turns the stream of `try_enumerate` into the stream of `enumerate`, which logs and skips the errors.
+fn successful_items<T>(stream: Result<impl Stream<Item = Result<T, String>> + Unpin, String>) -> impl Stream<Item = T> + Unpin {
+    let stream = stream.map_err(|e| error!("Cannot start the stream: {}", e));
+
+    futures::stream::iter(stream).flatten().filter_map(|item| {
+        futures::future::ready(item.map_err(|e| error!("Stream error: {}", e)).ok())
+    })
+}
+
 /// Trait for handling disconnection events.
 ///
 /// When a connection drops, the `on_disconnected` method is called with a
@@ -22,6 +30,9 @@ use crate::communication::ReconnectionContext;
 /// **Important**: When you provide a `DisconnectionHandler`, automatic reconnection
 /// is disabled. You have full control over when and if to reconnect.
 ///
+/// The client is `Reconnecting` while the `ReconnectionHandler` is held. It is closed, and the
+/// `on_closed` callbacks are called, when a reconnection fails or the handler is dropped without reconnecting.
+///
 /// # Example
 /// ```ignore
 /// struct MyHandler;
@@ -50,12 +61,10 @@ pub trait DisconnectionHandler {
 ///
 /// This is passed to your `DisconnectionHandler` when the connection is lost.
 /// Use it to manually trigger reconnection attempts.
-#[cfg(not(target_arch = "wasm32"))]
 pub struct ReconnectionHandler {
     context: ReconnectionContext,
 }
 
-#[cfg(not(target_arch = "wasm32"))]
 impl ReconnectionHandler {
     /// Attempt to reconnect once. Returns Ok(()) on success.
     pub async fn reconnect(&self) -> Result<(), String> {
@@ -77,11 +86,12 @@ impl ReconnectionHandler {
     pub fn endpoint(&self) -> String {
         self.context.endpoint().to_string()
     }
+    /// Get the cause of the lost connection, such as a close message sent by the hub
+    pub fn disconnect_cause(&self) -> &crate::DisconnectCause {
+        self.context.disconnect_cause()
+    }
 }
 
-#[cfg(target_arch = "wasm32")]
-pub struct ReconnectionHandler {}
-
 /// A client for connecting to and interacting with a SignalR hub.
 ///
 /// The `SignalRClient` can be used to invoke methods on the hub, send messages, and register callbacks.
@@ -183,12 +193,25 @@ pub struct ReconnectionHandler {}
 pub struct SignalRClient {
     _actions: UpdatableActionStorage,
     _connection: Option<CommunicationClient>,
+    _state: ConnectionStateTracker,
+    /// Shared by the clones of the user, but not by the clients kept by the callbacks and streams,
+    /// so only the clones of the user keep the connection open.
+    _handle: Option<Arc<()>>,
 }
 
 impl Drop for SignalRClient {
     fn drop(&mut self) {
+        let Some(handle) = self._handle.take() else {
+            return;
+        };
+
+        if Arc::strong_count(&handle) > 1 {
+            info!("The connection has {} more handles, not disconnecting", Arc::strong_count(&handle) - 1);
+            return;
+        }
+
         if let Some(mut conn) = self._connection.take() {
-            futures::executor::block_on(conn.disconnect());
+            conn.release();
         }
     }
 }
@@ -230,6 +253,31 @@ impl SignalRClient {
         SignalRClient::connect_internal(domain, hub, Some(options)).await
     }
 
+    /// Creates a client without a connection, which processes the messages handed to its storage.
+    #[cfg(all(test, not(target_arch = "wasm32")))]
+    pub(crate) fn from_storage(actions: UpdatableActionStorage) -> Self {
+        SignalRClient {
+            _actions: actions,
+            _connection: None,
+            _state: ConnectionStateTracker::new(),
+            _handle: None,
+        }
+    }
+
+    fn from_connection(connection: CommunicationClient) -> Result<Self, String> {
+        Ok(SignalRClient {
+            _actions: connection.get_storage()?,
+            _state: connection.get_state_tracker(),
+            _connection: Some(connection),
+            _handle: None,
+        })
+    }
+
+    /// A clone kept by the client itself, for example by a callback, which does not keep the connection open.
+    fn internal(&self) -> Self {
+        SignalRClient { _actions: self._actions.clone(), _connection: self._connection.clone(), _state: self._state.clone(), _handle: None }
+    }
+
     async fn connect_internal<F>(domain: &str, hub: &str, options: Option<F>) -> Result<Self, String>
         where F: FnMut(&mut ConnectionConfiguration)
     {
@@ -241,45 +289,57 @@ impl SignalRClient {
         }
 
         let disconnection_handler = config.get_disconnection_handler();
-        let reconnection_config = config.get_reconnection_config();
+        let connected_hooks = config.get_connected_hooks();
+        let automatic_reconnect = config.is_automatic_reconnect();
+
+        if automatic_reconnect && disconnection_handler.is_some() {
+            warn!("Automatic reconnection is enabled, the disconnection handler is ignored");
+        }
 
-        let result = HttpClient::negotiate(config).await;
+        let result = HttpClient::negotiate(&NegotiationOptions::from_configuration(&config)).await.map_err(|e| e.to_string());
 
         if result.is_ok() {
             // debug!("Negotiate response returned {:?}", result);
             let configuration = result.unwrap();
             info!("Negotiation successfull: {:?}", configuration);
-            let res = CommunicationClient::connect(&configuration).await;
 
-            if res.is_ok() {
-                let mut client  = res.unwrap();
-                let storage = client.get_storage();
-
-                if storage.is_ok() {
-                    #[cfg(not(target_arch = "wasm32"))]
-                    {
-                        client.set_reconnection_config(reconnection_config);
-                        if let Some(handler) = disconnection_handler {
-                            let handler = Arc::new(handler);
-                            let h_clone = handler.clone();
-                            client.set_disconnection_handler(move |context| {
-                                h_clone.on_disconnected(ReconnectionHandler { context });
-                            });
-                        }
-                    }
-
-                    let ret = SignalRClient {
-                        _actions: storage.unwrap(),
-                        _connection: Some(client),
-                    };    
-    
-                    Ok(ret)    
-                } else {
-                    Err(storage.err().unwrap())
+            // the reconnection settings must be in place before the connection opens,
+            // so the first disconnection already follows them
+            let res = {
+                let mut client = CommunicationClient::create(&configuration);
+                client.set_reconnection_config(config.get_reconnection_config());
+                client.set_reconnect_queue_capacity(config.get_reconnect_queue_capacity());
+                client.set_dispatch_mode(config.get_dispatch_mode());
+                client.set_protocol_options(config.get_protocol_options());
+
+                // the hooks set up the initial connection too, before the client is marked connected
+                if !connected_hooks.is_empty() {
+                    let hooks = connected_hooks;
+                    client.set_connected_hook(Arc::new(move |connection| {
+                        let hooks = hooks.clone();
+
+                        Box::pin(async move {
+                            let client = SignalRClient::from_connection(connection).map_err(crate::ConnectedHookError::Abort)?;
+
+                            hooks.run(client).await
+                        })
+                    }));
                 }
-            } else {
-                return Err(res.err().unwrap());
-            }
+
+                if let Some(handler) = disconnection_handler.filter(|_| !automatic_reconnect) {
+                    let handler = Arc::new(handler);
+                    client.set_disconnection_handler(move |context| {
+                        handler.on_disconnected(ReconnectionHandler { context });
+                    });
+                }
+
+                client.connect_internal().await.map(|_| client)
+            };
+
+            let mut client = SignalRClient::from_connection(res?)?;
+            client._handle = Some(Arc::new(()));
+
+            Ok(client)
         } else {
             Err(result.err().unwrap())
         }
@@ -311,10 +371,10 @@ impl SignalRClient {
     /// // Unregister the callback when it's no longer needed
     /// handler.unregister();
     /// ```   
-    pub fn register(&mut self, target: String, callback: impl Fn(InvocationContext) + crate::platform::MaybeSend + 'static) -> impl CallbackHandler
+    pub fn register(&mut self, target: String, callback: impl Fn(InvocationContext) + crate::platform::MaybeSendSync + 'static) -> impl CallbackHandler
     {
         // debug!("CLIENT registering invocation callback to {}", &target);
-        self._actions.add_callback(target.clone(), callback, self.clone());
+        self._actions.add_callback(target.clone(), callback, self.internal());
 
         StorageUnregistrationHandler::new(self._actions.clone(), target.clone())
     }
@@ -399,19 +459,20 @@ impl SignalRClient {
     {
         let invocation_id = self._actions.create_key(target.clone());
         let ret = self._actions.add_invocation::<T>(invocation_id.clone());
+        let _guard = StorageEntryGuard::new(self._actions.clone(), invocation_id.clone());
 
         let mut invocation = Invocation::create_single(target.clone());
         invocation.with_invocation_id(invocation_id);
 
         if configuration.is_some() {
-            let mut args = ArgumentConfiguration::new(invocation);
+            let mut args = ArgumentConfiguration::new(invocation, self._actions.protocol_options());
             configuration.unwrap()(&mut args);
 
             invocation = args.build_invocation();
         }
 
         if let Some(ref mut conn) = self._connection {
-            let res = Self::send_invocation(conn, &invocation).await;
+            let res = Self::send_invocation(conn, invocation).await;
 
             if res.is_ok() {
                 ret.await
@@ -492,23 +553,23 @@ impl SignalRClient {
         let mut invocation = Invocation::create_single(target.clone());
 
         if configuration.is_some() {
-            let mut args = ArgumentConfiguration::new(invocation);
+            let mut args = ArgumentConfiguration::new(invocation, self._actions.protocol_options());
             configuration.unwrap()(&mut args);
 
             invocation = args.build_invocation();
         }
 
         if let Some(ref mut conn) = self._connection {
-            Self::send_invocation(conn, &invocation).await
+            Self::send_invocation(conn, invocation).await
         } else {
             Err("Not connected".to_string())
         }
     }
 
-    pub(crate) async fn send_direct<T: Serialize>(&mut self, data: T) -> Result<(), String>
+    pub(crate) async fn send_message(&mut self, message: HubMessage) -> Result<(), String>
     {
         if let Some(ref mut conn) = self._connection {
-            conn.send(&data).await
+            conn.send(&message).await
         } else {
             Err("Not connected".to_string())
         }
@@ -518,13 +579,17 @@ impl SignalRClient {
     ///
     /// The target method on the hub should return an `IAsyncEnumerable` to send back data asynchronously.
     ///
+    /// The items are buffered with the default `StreamBuffer`, which cancels the stream when the reader falls behind
+    /// by `DEFAULT_STREAM_CAPACITY` items. The stream then ends, use `try_enumerate` to receive the error.
+    ///
     /// # Arguments
     ///
     /// * `target` - A `String` specifying the name of the target method to call on the hub.
     ///
     /// # Returns
     ///
-    /// * `impl Stream<Item = T>` - Returns a stream of items of type `T`.
+    /// * `impl Stream<Item = T>` - Returns a stream of items of type `T`. The errors are logged and skipped,
+    ///   use `try_enumerate` to receive them.
     ///
     /// # Type Parameters
     ///
@@ -540,13 +605,16 @@ impl SignalRClient {
     /// }
     /// ```
     pub async fn enumerate<T: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend>(&mut self, target: String) -> impl Stream<Item = T> {
-        return self.enumerate_internal(target, None::<fn(&mut ArgumentConfiguration)>).await;
+        successful_items(self.enumerate_internal(target, None::<fn(&mut ArgumentConfiguration)>).await)
     }
 
     /// Calls a specific target method on the SignalR hub with custom arguments and returns a stream for receiving data asynchronously.
     ///
     /// The target method on the hub should return an `IAsyncEnumerable` to send back data asynchronously.
     ///
+    /// The buffer of the stream can be set with `stream_buffer`. With a `Backpressure` buffer, do not await
+    /// an invocation of the same client while reading the stream, see `StreamBuffer` for the deadlock and its remedies.
+    ///
     /// # Arguments
     ///
     /// * `target` - A `String` specifying the name of the target method to call on the hub.
@@ -554,7 +622,8 @@ impl SignalRClient {
     ///
     /// # Returns
     ///
-    /// * `impl Stream<Item = T>` - Returns a stream of items of type `T`.
+    /// * `impl Stream<Item = T>` - Returns a stream of items of type `T`. The errors are logged and skipped,
+    ///   use `try_enumerate_with_args` to receive them.
     ///
     /// # Type Parameters
     ///
@@ -574,84 +643,347 @@ impl SignalRClient {
     pub async fn enumerate_with_args<T: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend, F>(&mut self, target: String, configuration: F) -> impl Stream<Item = T>
         where F : FnMut(&mut ArgumentConfiguration)
     {
-        return self.enumerate_internal(target, Some(configuration)).await;
+        successful_items(self.enumerate_internal(target, Some(configuration)).await)
+    }
+
+    /// Calls a specific target method on the SignalR hub and returns a stream of the results of its items.
+    ///
+    /// Unlike `enumerate`, no error is swallowed. An item that cannot be deserialized is an `Err` item and the stream goes on.
+    /// When the hub method throws, or the connection is lost, the stream ends with an `Err` item.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - A `String` specifying the name of the target method to call on the hub.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<impl Stream<Item = Result<T, String>>, String>` - On success, returns a stream of the item results. On failure, returns the error of sending the invocation as a `String`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `T` - The type of the items in the stream, which must implement `DeserializeOwned` and `Unpin`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut stream = client.try_enumerate::<TestEntity>("HundredEntities".to_string()).await?;
+    /// while let Some(item) = stream.next().await {
+    ///     match item {
+    ///         Ok(entity) => info!("Received entity: {}, {}", entity.text, entity.number),
+    ///         Err(e) => error!("Stream error: {}", e),
+    ///     }
+    /// }
+    /// ```
+    pub async fn try_enumerate<T: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend>(&mut self, target: String) -> Result<impl Stream<Item = Result<T, String>>, String> {
+        self.enumerate_internal(target, None::<fn(&mut ArgumentConfiguration)>).await
+    }
+
+    /// Calls a specific target method on the SignalR hub with custom arguments and returns a stream of the results of its items.
+    ///
+    /// Unlike `enumerate_with_args`, no error is swallowed. An item that cannot be deserialized is an `Err` item and the stream goes on.
+    /// When the hub method throws, or the connection is lost, the stream ends with an `Err` item.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - A `String` specifying the name of the target method to call on the hub.
+    /// * `configuration` - A mutable closure that allows the user to configure the arguments for the method call.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<impl Stream<Item = Result<T, String>>, String>` - On success, returns a stream of the item results. On failure, returns the error of sending the invocation as a `String`.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `T` - The type of the items in the stream, which must implement `DeserializeOwned` and `Unpin`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut stream = client.try_enumerate_with_args::<TestEntity, _>("HundredEntities".to_string(), |c| {
+    ///     c.argument("some_argument".to_string());
+    /// }).await?;
+    /// while let Some(item) = stream.next().await {
+    ///     let entity = item?;
+    ///     info!("Received entity: {}, {}", entity.text, entity.number);
+    /// }
+    /// ```
+    pub async fn try_enumerate_with_args<T: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend, F>(&mut self, target: String, configuration: F) -> Result<impl Stream<Item = Result<T, String>>, String>
+        where F : FnMut(&mut ArgumentConfiguration)
+    {
+        self.enumerate_internal(target, Some(configuration)).await
+    }
+
+    /// Invokes a hub method with arguments of any type, and returns its result without deserializing it to a Rust type.
+    ///
+    /// Meant for generic tooling that calls hub methods unknown at compile time. A void hub method returns `HubValue::Nil`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - A `String` specifying the name of the target method to invoke on the hub.
+    /// * `arguments` - The arguments of the hub method, in order.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<HubValue, String>` - On success, returns the result of the hub method. On failure, returns an error message as a `String`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let result = client.invoke_dynamic("TriggerEntityResponse".to_string(), vec![HubValue::from("callback2")]).await?;
+    /// assert_eq!(result, HubValue::Bool(true));
+    /// ```
+    pub async fn invoke_dynamic(&mut self, target: String, arguments: Vec<HubValue>) -> Result<HubValue, String> {
+        self.invoke_with_args::<HubValue, _>(target, |c| {
+            for argument in &arguments {
+                c.argument(argument);
+            }
+        }).await
+    }
+
+    /// Calls a streaming hub method with arguments of any type, and returns a stream of the results of its items,
+    /// without deserializing them to a Rust type.
+    ///
+    /// The stream behaves like the one of `try_enumerate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - A `String` specifying the name of the target method to call on the hub.
+    /// * `arguments` - The arguments of the hub method, in order.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<impl Stream<Item = Result<HubValue, String>>, String>` - On success, returns a stream of the item results. On failure, returns the error of sending the invocation as a `String`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut stream = client.enumerate_dynamic("HundredEntities".to_string(), Vec::new()).await?;
+    /// while let Some(item) = stream.next().await {
+    ///     info!("Item: {:?}", item?);
+    /// }
+    /// ```
+    pub async fn enumerate_dynamic(&mut self, target: String, arguments: Vec<HubValue>) -> Result<impl Stream<Item = Result<HubValue, String>>, String> {
+        self.enumerate_internal(target, Some(move |c: &mut ArgumentConfiguration| {
+            for argument in &arguments {
+                c.argument(argument);
+            }
+        })).await
     }
 
-    async fn enumerate_internal<T: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend, F>(&mut self, target: String, configuration: Option<F>) -> impl Stream<Item = T>
+    async fn enumerate_internal<T: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend, F>(&mut self, target: String, configuration: Option<F>) -> Result<EnumerableStream<T, UpdatableActionStorage>, String>
         where F : FnMut(&mut ArgumentConfiguration)
     {
         let invocation_id = self._actions.create_key(target.clone());
-        let res = self._actions.add_stream::<T>(invocation_id.clone());        
         let mut invocation = Invocation::create_multiple(target.clone());
-        invocation.with_invocation_id(invocation_id);
+        let mut buffer = StreamBuffer::default();
+        invocation.with_invocation_id(invocation_id.clone());
 
         if configuration.is_some() {
-            let mut args = ArgumentConfiguration::new(invocation);
+            let mut args = ArgumentConfiguration::new(invocation, self._actions.protocol_options());
             configuration.unwrap()(&mut args);
 
+            buffer = args.get_stream_buffer();
             invocation = args.build_invocation();
         }
 
+        let cancellation = StreamCancellation::new(invocation_id.clone(), self.internal());
+        let res = self._actions.add_stream::<T>(invocation_id.clone(), buffer, cancellation.clone());
+        let guard = StorageEntryGuard::new(self._actions.clone(), invocation_id);
+
         if let Some(ref mut conn) = self._connection {
-            let _ = Self::send_invocation(conn, &invocation).await;
+            Self::send_invocation(conn, invocation).await?;
+        } else {
+            return Err("Not connected".to_string());
         }
 
-        res
-    }
-
-    async fn send_invocation(conn: &mut CommunicationClient, invocation: &Invocation) -> Result<(), String> {
-        match conn.get_protocol_kind() {
-            HubProtocolKind::Json => {
-                conn.send(invocation).await
-            },
-            #[cfg(feature = "messagepack")]
-            HubProtocolKind::MessagePack => {
-                // Convert arguments to rmpv::Value with PascalCase keys
-                // to match .NET property naming convention.
-                let msgpack_args: Vec<rmpv::Value> = if let Some(ref raw_args) = invocation.msgpack_args {
-                    raw_args.iter()
-                        .map(|bytes| {
-                            let val = rmpv::decode::read_value(&mut std::io::Cursor::new(bytes))
-                                .unwrap_or(rmpv::Value::Nil);
-                            crate::protocol::msgpack::normalize_keys_to_pascal(&val)
-                        })
-                        .collect()
-                } else {
-                    invocation.arguments
-                        .as_ref()
-                        .map(|args| args.iter()
-                            .map(|a| {
-                                let raw = crate::protocol::msgpack::json_value_to_msgpack(a);
-                                crate::protocol::msgpack::normalize_keys_to_pascal(&raw)
-                            })
-                            .collect())
-                        .unwrap_or_default()
-                };
-
-                let payload = crate::protocol::msgpack::encode_invocation(
-                    invocation.get_message_type(),
-                    &None,
-                    &invocation.get_invocation_id(),
-                    &invocation.get_target(),
-                    &msgpack_args,
-                    &invocation.stream_ids,
-                )?;
-
-                let framed = crate::protocol::msgpack::frame_message(&payload);
-                conn.send_binary(framed).await
-            },
+        Ok(EnumerableStream::new(res, cancellation, guard))
+    }
+
+    async fn send_invocation(conn: &mut CommunicationClient, invocation: Invocation) -> Result<(), String> {
+        // The protocol of the connection writes the message, the arguments were serialized in its payload format already
+        conn.send(&invocation.into_message()).await
+    }
+
+    /// Returns the number of invocations and streams that are still waiting for the hub to respond.
+    ///
+    /// Registered callbacks are not counted. Useful for monitoring long-running services.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect("localhost", "test").await.unwrap();
+    /// info!("Pending invocations: {}", client.pending_invocations());
+    /// ```
+    pub fn pending_invocations(&self) -> usize {
+        self._actions.pending_count()
+    }
+
+    /// Returns the current state of the connection to the hub.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect("localhost", "test").await.unwrap();
+    /// assert_eq!(client.state(), HubConnectionState::Connected);
+    /// ```
+    pub fn state(&self) -> HubConnectionState {
+        self._state.state()
+    }
+
+    /// Returns a stream of the connection state transitions.
+    ///
+    /// The stream yields the current state first, then every later transition, and ends when the client and all of its clones are dropped.
+    ///
+    /// # Returns
+    ///
+    /// * `impl Stream<Item = HubConnectionState>` - Returns a stream of connection states.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut changes = client.state_changes();
+    /// spawn(async move {
+    ///     while let Some(state) = changes.next().await {
+    ///         info!("Connection state: {:?}", state);
+    ///     }
+    /// });
+    /// ```
+    pub fn state_changes(&self) -> impl Stream<Item = HubConnectionState> {
+        self._state.subscribe()
+    }
+
+    /// Registers a callback that is called when the connection is lost and the client starts reconnecting.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - A closure that receives the error that caused the connection loss, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.on_reconnecting(|error| {
+    ///     warn!("Connection lost, reconnecting: {:?}", error);
+    /// });
+    /// ```
+    pub fn on_reconnecting(&self, callback: impl Fn(Option<String>) + crate::platform::MaybeSendSync + 'static) {
+        self._state.add_on_reconnecting(Arc::new(callback));
+    }
+
+    /// Registers a callback that is called when the client reconnected to the hub.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - A closure that receives the connection id of the new connection, if known.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.on_reconnected(|connection_id| {
+    ///     info!("Reconnected with connection id {:?}", connection_id);
+    /// });
+    /// ```
+    pub fn on_reconnected(&self, callback: impl Fn(Option<String>) + crate::platform::MaybeSendSync + 'static) {
+        self._state.add_on_reconnected(Arc::new(callback));
+    }
+
+    /// Registers a callback that is called when the connection is closed and the client will not reconnect.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - A closure that receives the error that closed the connection, or `None` if the connection was closed locally.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.on_closed(|error| {
+    ///     if let Some(e) = error {
+    ///         error!("Connection closed: {}", e);
+    ///     }
+    /// });
+    /// ```
+    pub fn on_closed(&self, callback: impl Fn(Option<String>) + crate::platform::MaybeSendSync + 'static) {
+        self._state.add_on_closed(Arc::new(callback));
+    }
+
+    /// Registers a callback that is called when a registered callback cannot handle an invocation of the hub.
+    ///
+    /// This happens when the invocation cannot be parsed, or when the callback panics. The panic is caught,
+    /// so the connection keeps processing the messages, and the hub gets an error if it awaits a result.
+    /// On wasm32 the panics abort, so they cannot be caught and this callback is called only for the parse errors.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - A closure that receives the target of the failed callback and the error message.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.on_callback_error(|target, error| {
+    ///     error!("The callback of {} failed: {}", target, error);
+    /// });
+    /// ```
+    pub fn on_callback_error(&self, callback: impl Fn(String, String) + crate::platform::MaybeSendSync + 'static) {
+        self._state.add_on_callback_error(Arc::new(callback));
+    }
+
+    pub(crate) fn callback_failed(&self, target: &str, error: &str) {
+        self._state.callback_failed(target, error);
+    }
+
+    pub(crate) fn protocol_options(&self) -> crate::protocol::hub_protocol::ProtocolOptions {
+        self._actions.protocol_options()
+    }
+
+    /// Stops the connection to the hub.
+    ///
+    /// A close frame is sent to the hub and the receiver is stopped. The pending invocations and streams fail,
+    /// and so do the messages queued while reconnecting. The connection is stopped for every clone of the client,
+    /// and a reconnection in progress is abandoned.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.stop().await;
+    /// assert_eq!(client.state(), HubConnectionState::Disconnected(None));
+    /// ```
+    pub async fn stop(&self) {
+        if let Some(conn) = &self._connection {
+            conn.clone().stop(None).await;
         }
     }
 
-    pub fn disconnect(mut self) {
-        if let Some(ref mut conn) = self._connection {
-            futures::executor::block_on(conn.disconnect());
+    /// Stops the connection to the hub, after waiting for the pending invocations and streams to complete.
+    ///
+    /// New invocations are rejected while draining. The invocations and streams that are still pending
+    /// when the timeout elapses fail, then the connection is stopped like with `stop`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum time to wait for the pending invocations.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.stop_with_drain(Duration::from_secs(5)).await;
+    /// ```
+    pub async fn stop_with_drain(&self, timeout: std::time::Duration) {
+        if let Some(conn) = &self._connection {
+            conn.clone().stop(Some(timeout)).await;
         }
     }
+
+    /// Releases this client without blocking, the same as dropping it.
+    ///
+    /// The connection is closed when the last clone of the client is released, even if callbacks are still registered
+    /// or streams are still read. The clients passed to the callbacks do not keep the connection open. Use `stop` to close
+    /// the connection for every clone and to wait until the close frame is sent.
+    pub fn disconnect(self) {
+        drop(self);
+    }
 }
 
 impl Clone for SignalRClient {
     fn clone(&self) -> Self {
-        Self { _actions: self._actions.clone(), _connection: self._connection.clone() }
+        Self { _actions: self._actions.clone(), _connection: self._connection.clone(), _state: self._state.clone(), _handle: self._handle.clone() }
     }
 }
\ No newline at end of file
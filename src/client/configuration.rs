@@ -1,6 +1,16 @@
+use std::future::Future;
+use std::sync::Arc;
+
 use crate::client::client::DisconnectionHandler;
-use crate::communication::reconnection::ReconnectionConfig;
-use crate::protocol::hub_protocol::HubProtocolKind;
+use crate::client::hooks::ConnectedHooks;
+use crate::client::SignalRClient;
+use crate::communication::ConnectedHookError;
+use crate::communication::reconnection::{ReconnectionConfig, RetryPolicy};
+use crate::execution::DispatchMode;
+use crate::protocol::hub_protocol::{HubProtocol, HubProtocolKind, ProtocolOptions};
+use crate::protocol::json::JsonProtocolOptions;
+#[cfg(feature = "messagepack")]
+use crate::protocol::msgpack::MessagePackOptions;
 
 #[derive(Clone)]
 pub(crate) enum Authentication {
@@ -25,7 +35,12 @@ pub struct ConnectionConfiguration {
     #[cfg(target_arch = "wasm32")]
     _disconnection: Option<Box<dyn DisconnectionHandler>>,
     _reconnection: ReconnectionConfig,
-    _protocol: HubProtocolKind,
+    _automatic_reconnect: bool,
+    _reconnect_queue: Option<usize>,
+    _connected_hooks: ConnectedHooks,
+    _protocol: Arc<dyn HubProtocol>,
+    _protocol_options: ProtocolOptions,
+    _dispatch: DispatchMode,
 }
 
 impl ConnectionConfiguration {
@@ -38,7 +53,12 @@ impl ConnectionConfiguration {
             _port: None,
             _disconnection: None,
             _reconnection: ReconnectionConfig::default(),
-            _protocol: HubProtocolKind::default(),
+            _automatic_reconnect: false,
+            _reconnect_queue: None,
+            _connected_hooks: ConnectedHooks::default(),
+            _protocol: HubProtocolKind::default().create(),
+            _protocol_options: ProtocolOptions::default(),
+            _dispatch: DispatchMode::default(),
         }
     }
 
@@ -245,7 +265,18 @@ impl ConnectionConfiguration {
         self._reconnection.clone()
     }
 
+    pub(crate) fn is_automatic_reconnect(&self) -> bool {
+        self._automatic_reconnect
+    }
+
+    pub(crate) fn get_reconnect_queue_capacity(&self) -> Option<usize> {
+        self._reconnect_queue
+    }
+
     /// Sets the reconnection policy for the connection.
+    ///
+    /// Without a disconnection handler the client reconnects automatically using this policy.
+    /// With a disconnection handler the policy is used by `ReconnectionHandler::reconnect_with_policy`.
     /// 
     /// # Arguments
     /// 
@@ -259,15 +290,240 @@ impl ConnectionConfiguration {
         self
     }
 
+    /// Enables automatic reconnection with the given policy.
+    ///
+    /// When the connection is lost, the client moves to the `Reconnecting` state and retries as long as the policy
+    /// returns a delay. Each attempt negotiates a new connection with the hub. When the policy gives up, the client
+    /// moves to the terminal `Disconnected` state and the `on_closed` callbacks are called.
+    ///
+    /// Automatic reconnection takes precedence over a disconnection handler, which is ignored when both are set.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The `RetryPolicy` that decides the delay before each attempt. Any `ReconnectionPolicy` can be used.
+    ///
+    /// # Returns
+    ///
+    /// * `&ConnectionConfiguration` - Returns a reference to the updated connection configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect_with("localhost", "test", |c| {
+    ///     c.with_automatic_reconnect(ExponentialBackoffPolicy::new(Duration::from_secs(1), 2.0, Some(Duration::from_secs(30)), Some(10)));
+    /// }).await.unwrap();
+    ///
+    /// client.on_reconnected(|connection_id| info!("Reconnected as {:?}", connection_id));
+    /// client.on_closed(|error| info!("Gave up reconnecting: {:?}", error));
+    /// ```
+    pub fn with_automatic_reconnect(&mut self, policy: impl RetryPolicy + 'static) -> &ConnectionConfiguration {
+        self._reconnection = ReconnectionConfig {
+            policy: std::sync::Arc::new(policy),
+        };
+        self._automatic_reconnect = true;
+        self
+    }
+
+    /// Queues the outgoing messages while the client is reconnecting, instead of failing them immediately.
+    ///
+    /// The queued `send` and `invoke` calls wait until the connection is restored, then the messages are sent
+    /// in the order they were queued, right after the handshake. When the queue is full, further calls fail
+    /// immediately. When the reconnection is abandoned or the client is disconnected, the queued calls fail.
+    ///
+    /// The queue works the same way on native and WASM targets, with automatic and manual reconnection.
+    /// It is disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of messages waiting for the reconnection.
+    ///
+    /// # Returns
+    ///
+    /// * `&ConnectionConfiguration` - Returns a reference to the updated connection configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect_with("localhost", "test", |c| {
+    ///     c.with_automatic_reconnect(ConstantDelayPolicy::new(Duration::from_secs(2), Some(5)));
+    ///     c.with_reconnect_queue(100);
+    /// }).await.unwrap();
+    /// ```
+    pub fn with_reconnect_queue(&mut self, capacity: usize) -> &ConnectionConfiguration {
+        self._reconnect_queue = Some(capacity);
+        self
+    }
+
+    /// Sets how the callbacks registered with `SignalRClient::register` run when the hub invokes them.
+    ///
+    /// The callbacks run on a worker, so the connection keeps receiving while a callback runs.
+    /// By default the invocations run one at a time, in the order they arrived, like in the .NET client.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - A `DispatchMode` specifying the ordering and the parallelism of the callbacks.
+    ///
+    /// # Returns
+    ///
+    /// * `&ConnectionConfiguration` - Returns a reference to the updated connection configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect_with("localhost", "test", |c| {
+    ///     c.with_dispatch_mode(DispatchMode::Concurrent { max_parallelism: 4 });
+    /// }).await.unwrap();
+    /// ```
+    pub fn with_dispatch_mode(&mut self, mode: DispatchMode) -> &ConnectionConfiguration {
+        self._dispatch = mode;
+        self
+    }
+
+    pub(crate) fn get_dispatch_mode(&self) -> DispatchMode {
+        self._dispatch
+    }
+
+    /// Adds a hook that sets up the connection, for example joins groups or restores server-side subscriptions.
+    ///
+    /// The server sees every reconnection as a new connection, so the state set up by the client is lost.
+    /// The hooks run in the order they were added, after the initial connection and after every successful
+    /// reconnection, before the client is marked connected and before the messages queued while reconnecting are sent.
+    ///
+    /// A hook returning `ConnectedHookError::Retry` drops the new connection and the client opens another one
+    /// after the next delay of the reconnection policy, for the initial connection as for a reconnection.
+    /// `ConnectedHookError::Abort`, or a policy that gives up, closes the client. When the initial connection
+    /// fails to set up, `connect_with` returns the error of the hook.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - An async function that receives a client sending on the new connection.
+    ///
+    /// # Returns
+    ///
+    /// * `&ConnectionConfiguration` - Returns a reference to the updated connection configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect_with("localhost", "test", |c| {
+    ///     c.with_automatic_reconnect(ConstantDelayPolicy::new(Duration::from_secs(2), Some(5)));
+    ///     c.on_connected(|mut client| async move {
+    ///         client.invoke::<bool>("JoinGroup".to_string()).await?;
+    ///         Ok(())
+    ///     });
+    /// }).await.unwrap();
+    /// ```
+    pub fn on_connected<F, Fut>(&mut self, hook: F) -> &ConnectionConfiguration
+        where F: Fn(SignalRClient) -> Fut + crate::platform::MaybeSendSync + 'static,
+              Fut: Future<Output = Result<(), ConnectedHookError>> + crate::platform::MaybeSend + 'static
+    {
+        self._connected_hooks.add(hook);
+        self
+    }
+
+    pub(crate) fn get_connected_hooks(&self) -> ConnectedHooks {
+        self._connected_hooks.clone()
+    }
+
+    /// Configures how the JSON hub protocol names the keys of the objects, and how it handles null values.
+    ///
+    /// The options apply to the arguments and results sent to the hub, and to the values received from it.
+    /// They have no effect when the MessagePack protocol is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The `JsonProtocolOptions` of the connection.
+    ///
+    /// # Returns
+    ///
+    /// * `&ConnectionConfiguration` - Returns a reference to the updated `ConnectionConfiguration` instance.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect_with("localhost", "test", |c| {
+    ///     c.with_json_options(JsonProtocolOptions {
+    ///         key_naming: KeyNaming::CamelCase,
+    ///         ..Default::default()
+    ///     });
+    /// }).await.unwrap();
+    /// ```
+    pub fn with_json_options(&mut self, options: JsonProtocolOptions) -> &ConnectionConfiguration {
+        self._protocol_options.json = options;
+        self
+    }
+
     /// Configures the connection to use the MessagePack hub protocol instead of JSON.
     /// Requires the `messagepack` cargo feature to be enabled.
     #[cfg(feature = "messagepack")]
     pub fn with_messagepack_protocol(&mut self) -> &ConnectionConfiguration {
-        self._protocol = HubProtocolKind::MessagePack;
+        self._protocol = HubProtocolKind::MessagePack.create();
         self
     }
 
-    pub(crate) fn get_protocol_kind(&self) -> HubProtocolKind {
-        self._protocol
+    /// Configures the connection to use the MessagePack hub protocol with the given options.
+    /// Requires the `messagepack` cargo feature to be enabled.
+    ///
+    /// The options decide how structs are laid out, how the map keys are named, and how strictly the values of the hub are decoded.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The `MessagePackOptions` of the connection.
+    ///
+    /// # Returns
+    ///
+    /// * `&ConnectionConfiguration` - Returns a reference to the updated `ConnectionConfiguration` instance.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect_with("localhost", "test", |c| {
+    ///     c.with_messagepack_options(MessagePackOptions {
+    ///         struct_encoding: StructEncoding::Array,
+    ///         ..Default::default()
+    ///     });
+    /// }).await.unwrap();
+    /// ```
+    #[cfg(feature = "messagepack")]
+    pub fn with_messagepack_options(&mut self, options: MessagePackOptions) -> &ConnectionConfiguration {
+        self._protocol = HubProtocolKind::MessagePack.create();
+        self._protocol_options.messagepack = options;
+        self
+    }
+
+    /// Configures the connection to use a custom hub protocol.
+    ///
+    /// The protocol parses the frames received from the hub and writes the messages sent to it.
+    /// The values are still serialized with the JSON or MessagePack options, as the payload format of the protocol says.
+    ///
+    /// # Arguments
+    ///
+    /// * `protocol` - The `HubProtocol` of the connection.
+    ///
+    /// # Returns
+    ///
+    /// * `&ConnectionConfiguration` - Returns a reference to the updated `ConnectionConfiguration` instance.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let client = SignalRClient::connect_with("localhost", "test", |c| {
+    ///     c.with_hub_protocol(CompressedJson);
+    /// }).await.unwrap();
+    /// ```
+    pub fn with_hub_protocol(&mut self, protocol: impl HubProtocol + 'static) -> &ConnectionConfiguration {
+        self._protocol = Arc::new(protocol);
+        self
+    }
+
+    pub(crate) fn get_hub_protocol(&self) -> Arc<dyn HubProtocol> {
+        self._protocol.clone()
+    }
+
+    pub(crate) fn get_protocol_options(&self) -> ProtocolOptions {
+        ProtocolOptions {
+            format: self._protocol.payload_format(),
+            ..self._protocol_options.clone()
+        }
     }
 }
\ No newline at end of file
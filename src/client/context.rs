@@ -2,8 +2,7 @@
 use core::future::Future;
 
 use serde::{de::DeserializeOwned, Serialize};
-use crate::protocol::{messages, invoke::{Completion, Invocation}};
-use self::messages::MessageParser;
+use crate::protocol::{hub_protocol::{CompletionResult, HubMessage, HubPayload}, invoke::Invocation, value::HubValue};
 use super::SignalRClient;
 
 /// The context for an invocation, providing access to arguments, the ability to complete the invocation, and a client for additional hub interactions.
@@ -134,6 +133,8 @@ impl InvocationContext {
     /// Retrieves the argument of the given type from the invocation context.
     ///
     /// The argument index should be a zero-based order of the argument provided by the hub call.
+    /// With the MessagePack protocol the argument is deserialized straight from the bytes the hub sent,
+    /// so binary data can be received as a `Vec<u8>` or a `serde_bytes::ByteBuf`.
     ///
     /// # Arguments
     ///
@@ -161,31 +162,11 @@ impl InvocationContext {
     /// }
     /// ```    
     pub fn argument<T: DeserializeOwned + Unpin>(&self, index: usize) -> Result<T, String> {
-        if self.invocation.arguments.is_some() {
-            let arguments = self.invocation.arguments.as_ref().unwrap();
-
-            if arguments.len() > index {
-                let arg = arguments.get(index);
-
-                if arg.is_some() {
-                    let value = arg.unwrap();
-                    let strvalue = value.to_string();
-                    let res = MessageParser::parse_message::<T>(&strvalue);
-
-                    if res.is_ok() {
-                        return Ok(res.unwrap());
-                    } else {
-                        return Err(format!("The argument cannot be deserialized to the requested type {:?}", arg.unwrap().as_str().unwrap()));
-                    }
-                } else {
-                    return Err(format!("The argument does not exist at the given index {}", index));
-                }
-            } else {
-                return Err(format!("The argument count is not greater than the index {}", index));
-            }
-        } else {
-            return Err(format!("There are no arguments for the invocation"));
-        }        
+        match self.invocation.arguments.get(index) {
+            Some(argument) => argument.decode::<T>(&self.client.protocol_options())
+                .map_err(|e| format!("The argument cannot be deserialized to the requested type: {}", e)),
+            None => Err(format!("The argument count is not greater than the index {}", index)),
+        }
     }
 
     /// Returns a specific result from the callback to the hub.
@@ -221,17 +202,89 @@ impl InvocationContext {
     /// }
     /// ```    
     pub async fn complete<T: Serialize>(&mut self, result: T) -> Result<(), String> {
-        let invocation_id = self.invocation.get_invocation_id();
+        match self.invocation.get_invocation_id() {
+            Some(invocation_id) => {
+                // Serialized in the payload format of the protocol, so MessagePack connections complete with MessagePack
+                let result = HubPayload::encode(&result, &self.client.protocol_options())?;
+                let completion = HubMessage::Completion { invocation_id, result: CompletionResult::Result(result) };
 
-        if invocation_id.is_some() {
-            let completion = Completion::create_result(invocation_id.unwrap(), result);
+                self.client.send_message(completion).await
+            },
+            None => Err("The completion cannot be sent, because there was no invocation id for the call".to_string()),
+        }
+    }
 
-            return self.client.send_direct(completion).await;
-        } else {
-            return Err(format!("The completion cannot be sent, because there was no invocation id for the call"));
+    /// Reports an error to the hub instead of a result.
+    ///
+    /// This method should be used only when the hub invokes the callback and awaits the response to arrive.
+    /// The hub call fails with the given error message.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error message to send back to the hub.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), String>` - On success, returns `Ok(())`. On failure, returns an error message as a `String`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let result = ctx.complete_error("The entity cannot be processed").await;
+    /// if let Err(e) = result {
+    ///     error!("Failed to complete callback: {}", e);
+    /// }
+    /// ```
+    pub async fn complete_error(&mut self, error: impl Into<String>) -> Result<(), String> {
+        match self.invocation.get_invocation_id() {
+            Some(invocation_id) => {
+                let completion = HubMessage::Completion { invocation_id, result: CompletionResult::Error(error.into()) };
+
+                self.client.send_message(completion).await
+            },
+            None => Err("The completion cannot be sent, because there was no invocation id for the call".to_string()),
         }
     }
 
+    /// Returns the number of arguments sent by the hub.
+    pub fn argument_count(&self) -> usize {
+        self.invocation.arguments.len()
+    }
+
+    /// Returns the arguments sent by the hub, without deserializing them to Rust types.
+    ///
+    /// With the MessagePack protocol the values keep their MessagePack types, e.g. binary data and extension types.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// client.register("callback1".to_string(), |ctx| {
+    ///     for (index, argument) in ctx.arguments_raw().iter().enumerate() {
+    ///         info!("{} argument #{}: {:?}", ctx.target(), index, argument);
+    ///     }
+    /// });
+    /// ```
+    pub fn arguments_raw(&self) -> Vec<HubValue> {
+        self.invocation.arguments.iter()
+            .map(HubPayload::to_hub_value)
+            .collect()
+    }
+
+    /// Returns the name of the client method the hub invoked.
+    pub fn target(&self) -> String {
+        self.invocation.get_target()
+    }
+
+    /// Returns the id of the invocation, if the hub awaits a result.
+    pub fn invocation_id(&self) -> Option<String> {
+        self.invocation.get_invocation_id()
+    }
+
+    /// Returns `true` if the hub awaits a result for this invocation.
+    pub fn expects_result(&self) -> bool {
+        self.invocation.get_invocation_id().is_some()
+    }
+
     /// Spawns the given async block into a new thread.
     ///
     /// This method is a convenience method for writing cross-platform code, as the package supports both Tokio and WASM.
@@ -285,4 +338,4 @@ impl InvocationContext {
     {
         wasm_bindgen_futures::spawn_local(future);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,52 @@
+use std::{future::Future, sync::Arc};
+
+use futures::FutureExt;
+use log::warn;
+
+use crate::communication::ConnectedHookError;
+
+use super::SignalRClient;
+
+#[cfg(not(target_arch = "wasm32"))]
+type HookFuture = futures::future::BoxFuture<'static, Result<(), ConnectedHookError>>;
+#[cfg(target_arch = "wasm32")]
+type HookFuture = futures::future::LocalBoxFuture<'static, Result<(), ConnectedHookError>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+type HookFn = dyn Fn(SignalRClient) -> HookFuture + Send + Sync + 'static;
+#[cfg(target_arch = "wasm32")]
+type HookFn = dyn Fn(SignalRClient) -> HookFuture + 'static;
+
+/// The `on_connected` hooks of a connection, run in the order they were added.
+#[derive(Clone, Default)]
+pub(crate) struct ConnectedHooks {
+    _hooks: Vec<Arc<HookFn>>,
+}
+
+impl ConnectedHooks {
+    pub(crate) fn add<F, Fut>(&mut self, hook: F)
+        where F: Fn(SignalRClient) -> Fut + crate::platform::MaybeSendSync + 'static,
+              Fut: Future<Output = Result<(), ConnectedHookError>> + crate::platform::MaybeSend + 'static
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        self._hooks.push(Arc::new(move |client| hook(client).boxed()));
+        #[cfg(target_arch = "wasm32")]
+        self._hooks.push(Arc::new(move |client| hook(client).boxed_local()));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self._hooks.is_empty()
+    }
+
+    /// Runs the hooks one after the other, and stops at the first failure.
+    pub(crate) async fn run(&self, client: SignalRClient) -> Result<(), ConnectedHookError> {
+        for (index, hook) in self._hooks.iter().enumerate() {
+            if let Err(e) = hook(client.clone()).await {
+                warn!("The connected hook #{} failed: {:?}", index, e);
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 mod client;
 mod context;
 mod configuration;
+mod hooks;
 
 pub use client::{SignalRClient, DisconnectionHandler, ReconnectionHandler};
 pub use context::InvocationContext;
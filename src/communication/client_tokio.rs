@@ -1,17 +1,22 @@
-use std::{str::FromStr, sync::{Arc, Weak}};
+use std::{str::FromStr, sync::{atomic::{AtomicBool, Ordering}, Arc, Weak}, time::Duration};
 
-use crate::{execution::{Storage, UpdatableActionStorage}, protocol::{hub_protocol::{HubProtocolKind, MessagePayload}, messages::{MessageParser, RECORD_SEPARATOR}, negotiate::{HandshakeRequest, Ping}}};
+use crate::{execution::{DispatchMode, Storage, UpdatableActionStorage}, protocol::{hub_protocol::{HubMessage, HubProtocol, ProtocolOptions, TransferFormat}, messages::{MessageParser, RECORD_SEPARATOR}, negotiate::{HandshakeRequest, HandshakeResponse}}};
 
-use super::{Communication, reconnection::ReconnectionConfig};
+use super::{Communication, ConnectedHook, ConnectionStateTracker, HttpClient, PendingReconnection, NegotiationOptions, common::{CLIENT_DROPPED_ERROR, CONNECTION_CLOSED_ERROR, CONNECTION_LOST_ERROR, HANDSHAKE_TIMEOUT, LOCALLY_CLOSED_ERROR}, queue::{wait_for_send, OutgoingQueue, RECONNECT_ABANDONED_ERROR}, reconnection::{AttemptError, ConnectionError, DisconnectCause, ReconnectionConfig, RetryContext}};
 use futures::{stream::{SplitSink, SplitStream}, SinkExt, StreamExt};
 use http::Uri;
-use log::{error, info};
+use log::{error, info, warn};
 use tokio::{net::TcpStream, sync::Mutex, task::JoinHandle};
 use tokio_native_tls::native_tls::TlsConnector;
-use tokio_websockets::{ClientBuilder, MaybeTlsStream, Message, WebSocketStream};
+use tokio_websockets::{ClientBuilder, CloseCode, MaybeTlsStream, Message, WebSocketStream};
+
+/// How long sending the close frame may take, before the connection is dropped without it.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the pending invocations are checked while draining.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 trait CommunicationDisconnectionHandler: Send {
-    fn on_connection_dropped(&self);
+    fn on_connection_dropped(&self, cause: DisconnectCause);
 }
 
 /// Context for manual reconnection, passed to user's disconnection handler.
@@ -22,99 +27,184 @@ pub struct ReconnectionContext {
     endpoint: Uri,
     actions: UpdatableActionStorage,
     reconnection_config: ReconnectionConfig,
-    protocol_kind: HubProtocolKind,
+    protocol: Arc<dyn HubProtocol>,
+    tracker: ConnectionStateTracker,
+    negotiation: NegotiationOptions,
+    queue: OutgoingQueue<Message>,
+    connected_hook: Option<ConnectedHook>,
+    cause: DisconnectCause,
+    /// Set for the disconnection handler, closes the client when the handler drops the context without reconnecting.
+    pending: Option<Arc<PendingReconnection>>,
 }
 
 impl ReconnectionContext {
     /// Attempt to reconnect once. Returns Ok(()) on success.
     pub async fn reconnect(&self) -> Result<(), String> {
-        let state = self.state.upgrade().ok_or("Client has been dropped")?;
+        match self.try_reconnect().await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let error = e.message();
+                self.close_unless_local(&error).await;
 
-        // Check if already connected
-        {
-            let guard = state.lock().await;
-            if let ConnectionState::Connected(_) = &*guard {
-                return Ok(());
-            }
+                Err(error)
+            },
         }
+    }
+
+    async fn try_reconnect(&self) -> Result<(), AttemptError> {
+        let state = self.state.upgrade().ok_or_else(|| AttemptError::Abort(CLIENT_DROPPED_ERROR.to_string()))?;
 
-        // Set state to reconnecting
+        // Check if already connected, then set state to reconnecting
         {
             let mut guard = state.lock().await;
-            if let ConnectionState::NotConnected(DisconnectionReason::LocalClosed) = *guard {
-                return Err("Client was locally disconnected".to_string());
+
+            match *guard {
+                ConnectionState::Connected(_) => return Ok(()),
+                ConnectionState::NotConnected(DisconnectionReason::LocalClosed) => {
+                    return Err(AttemptError::Abort(LOCALLY_CLOSED_ERROR.to_string()));
+                },
+                _ => *guard = ConnectionState::NotConnected(DisconnectionReason::Reconnecting),
             }
-            *guard = ConnectionState::NotConnected(DisconnectionReason::Reconnecting);
         }
 
+        self.tracker.reconnecting(None);
         info!("Manual reconnection attempt to {}...", self.endpoint);
 
-        match CommunicationClient::connect_to_server(self.endpoint.clone(), self.protocol_kind).await {
-            Ok((write, read)) => {
-                let mut guard = state.lock().await;
-
-                // Check again if locally closed during reconnection
-                if let ConnectionState::NotConnected(DisconnectionReason::LocalClosed) = *guard {
-                    return Err("Client was locally disconnected".to_string());
-                }
+        let res = self.establish().await;
 
-                let new_handler = ClientDisconnectionHandler {
-                    state: self.state.clone(),
-                    endpoint: self.endpoint.clone(),
-                    actions: self.actions.clone(),
-                    reconnection_config: self.reconnection_config.clone(),
-                    user_handler: None, // Manual mode doesn't re-trigger automatic reconnection
-                    protocol_kind: self.protocol_kind,
-                };
-
-                let mut conn_struct = CommunicationConnection {
-                    _receiver: None,
-                    _sink: write,
-                };
-                conn_struct.start_receiving(read, self.actions.clone(), new_handler, self.protocol_kind);
-                *guard = ConnectionState::Connected(Arc::new(Mutex::new(conn_struct)));
-                info!("Manual reconnection successful");
-                Ok(())
-            },
-            Err(e) => {
-                error!("Manual reconnection failed: {}", e);
-                let mut guard = state.lock().await;
-                *guard = ConnectionState::NotConnected(DisconnectionReason::RemoteClosed);
-                Err(e)
-            }
+        match &res {
+            Ok(()) => info!("Manual reconnection successful"),
+            Err(e) => error!("Manual reconnection failed: {}", e.message()),
         }
+
+        res
     }
 
     /// Attempt reconnection with automatic retries using the configured policy.
     /// Returns Ok(()) on success, Err if all attempts are exhausted.
     pub async fn reconnect_with_policy(&self) -> Result<(), String> {
         let mut retry_count = 0u32;
+        let mut previous_error = None;
         let start_time = std::time::Instant::now();
 
         loop {
-            let delay = self.reconnection_config.policy.next_retry_delay(
-                retry_count,
-                start_time.elapsed().as_millis() as u64
-            );
+            let context = RetryContext::new(retry_count, start_time.elapsed(), previous_error.take(), self.cause.clone());
+            let delay = self.reconnection_config.policy.next_delay(&context);
 
             if let Some(d) = delay {
                 if retry_count > 0 {
                     tokio::time::sleep(d).await;
                 }
 
-                match self.reconnect().await {
+                match self.try_reconnect().await {
                     Ok(()) => return Ok(()),
-                    Err(e) => {
-                        if e.contains("locally disconnected") {
-                            return Err(e);
-                        }
+                    Err(AttemptError::Abort(e)) => {
+                        self.close_unless_local(&e).await;
+
+                        return Err(e);
+                    },
+                    Err(AttemptError::Retry(e)) => {
+                        previous_error = Some(e);
                         retry_count += 1;
                     }
                 }
             } else {
-                return Err("Reconnection attempts exhausted".to_string());
+                let error = "Reconnection attempts exhausted".to_string();
+                self.close_unless_local(&error).await;
+
+                return Err(error);
+            }
+        }
+    }
+
+    /// A copy of the context for a new connection, which does not keep the pending reconnection alive.
+    fn detached(&self) -> Self {
+        ReconnectionContext { pending: None, ..self.clone() }
+    }
+
+    /// Opens a new connection and sets it up.
+    async fn establish(&self) -> Result<(), AttemptError> {
+        let (write, read, connection_id) = CommunicationClient::reconnect_to_server(&self.negotiation, self.protocol.as_ref()).await
+            .map_err(AttemptError::Retry)?;
+
+        let handler = ClientDisconnectionHandler {
+            context: self.detached(),
+            user_handler: None, // Reconnected connections are handled by automatic reconnection
+        };
+
+        self.set_up(write, read, connection_id, handler).await
+    }
+
+    /// Runs the connected hook on an opened connection, then sends the queued messages and marks the client connected.
+    ///
+    /// The client stays in its previous state while the hook runs, so the other senders keep queueing
+    /// and their messages are sent after the ones of the hook.
+    async fn set_up(&self, write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>, read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>, connection_id: String, handler: ClientDisconnectionHandler) -> Result<(), AttemptError> {
+        let state = self.state.upgrade().ok_or_else(|| AttemptError::Abort(CLIENT_DROPPED_ERROR.to_string()))?;
+
+        let mut conn_struct = CommunicationConnection::new(write);
+        conn_struct.start_receiving(read, self.actions.clone(), handler, self.protocol.clone());
+        let connection = Arc::new(Mutex::new(conn_struct));
+
+        if let Some(hook) = &self.connected_hook {
+            hook(self.setup_client(state.clone(), &connection, connection_id.clone())).await?;
+        }
+
+        let mut guard = state.lock().await;
+
+        // Check again if locally closed during reconnection
+        if let ConnectionState::NotConnected(DisconnectionReason::LocalClosed) = *guard {
+            return Err(AttemptError::Abort(LOCALLY_CLOSED_ERROR.to_string()));
+        }
+
+        {
+            let mut conn = connection.lock().await;
+
+            if conn.is_lost() {
+                return Err(AttemptError::Retry(ConnectionError::Transport(CONNECTION_LOST_ERROR.to_string())));
+            }
+
+            conn.flush(&self.queue).await;
+        }
+
+        *guard = ConnectionState::Connected(connection);
+        self.tracker.connected(Some(connection_id));
+
+        Ok(())
+    }
+
+    /// Creates the client passed to the connected hook, which sends on the new connection directly.
+    fn setup_client(&self, state: Arc<Mutex<ConnectionState>>, connection: &Arc<Mutex<CommunicationConnection>>, connection_id: String) -> CommunicationClient {
+        CommunicationClient {
+            _endpoint: self.endpoint.clone(),
+            _state: state,
+            _actions: self.actions.clone(),
+            _reconnection_config: self.reconnection_config.clone(),
+            _disconnection_handler: None,
+            _protocol: self.protocol.clone(),
+            _tracker: self.tracker.clone(),
+            _connection_id: connection_id,
+            _negotiation: self.negotiation.clone(),
+            _queue: self.queue.clone(),
+            _connected_hook: self.connected_hook.clone(),
+            _direct: Some(Arc::downgrade(connection)),
+        }
+    }
+
+    /// Closes the connection after a failed reconnection, unless the client was closed locally meanwhile.
+    async fn close_unless_local(&self, error: &str) {
+        if let Some(state) = self.state.upgrade() {
+            let mut guard = state.lock().await;
+
+            if let ConnectionState::NotConnected(DisconnectionReason::LocalClosed) = *guard {
+                return;
             }
+
+            *guard = ConnectionState::NotConnected(DisconnectionReason::RemoteClosed);
         }
+
+        self.queue.fail_all(RECONNECT_ABANDONED_ERROR);
+        self.tracker.closed(Some(error.to_string()));
     }
 
     /// Check if currently connected
@@ -131,67 +221,99 @@ impl ReconnectionContext {
     pub fn endpoint(&self) -> &Uri {
         &self.endpoint
     }
+
+    /// Get the cause of the lost connection
+    pub fn disconnect_cause(&self) -> &DisconnectCause {
+        &self.cause
+    }
 }
 
 struct CommunicationConnection {
     _sink: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
     _receiver: Option<JoinHandle<()>>,
+    _lost: Arc<AtomicBool>,
 }
 
 impl CommunicationConnection {
-    fn start_receiving(&mut self, mut stream: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>, mut storage: impl Storage + Send + 'static, disconnection_handler: impl CommunicationDisconnectionHandler + 'static, protocol_kind: HubProtocolKind) {
+    fn new(sink: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>) -> Self {
+        CommunicationConnection {
+            _sink: sink,
+            _receiver: None,
+            _lost: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns `true` if the receiver has stopped, because the connection dropped.
+    fn is_lost(&self) -> bool {
+        self._lost.load(Ordering::SeqCst)
+    }
+
+    fn start_receiving(&mut self, mut stream: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>, mut storage: impl Storage + Send + 'static, disconnection_handler: impl CommunicationDisconnectionHandler + 'static, protocol: Arc<dyn HubProtocol>) {
+        let lost = self._lost.clone();
         let handle = tokio::spawn(async move {
-            while let Some(item) = stream.next().await {
+            let mut cause = DisconnectCause::ConnectionLost;
+
+            'receiving: while let Some(item) = stream.next().await {
                 if item.is_ok() {
                     let ws_message = item.unwrap();
-                    match protocol_kind {
-                        HubProtocolKind::Json => {
-                            for message in CommunicationClient::get_text_messages(&ws_message) {
-                                let ping = MessageParser::parse_message::<Ping>(&message);
-
-                                if ping.is_ok() {
-                                    let res = storage.process_message(MessagePayload::Text(message), ping.unwrap().message_type());
-
-                                    if res.is_err() {
-                                        error!("Error occured parsing message {}", res.unwrap_err());
-                                    }
-                                } else {
-                                    error!("Message could not be parsed: {:?}", message);
-                                }
-                            }
-                        },
-                        #[cfg(feature = "messagepack")]
-                        HubProtocolKind::MessagePack => {
-                            for payload in CommunicationClient::get_binary_messages(&ws_message) {
-                                match crate::protocol::msgpack::read_message_type(&payload) {
-                                    Ok(msg_type) => {
-                                        let res = storage.process_message(MessagePayload::Binary(payload), msg_type);
-                                        if res.is_err() {
-                                            error!("Error processing msgpack message: {}", res.unwrap_err());
-                                        }
-                                    },
-                                    Err(e) => error!("Cannot read msgpack message type: {}", e),
-                                }
-                            }
-                        },
+
+                    if !ws_message.is_text() && !ws_message.is_binary() {
+                        continue;
+                    }
+
+                    for message in protocol.parse_messages(ws_message.as_payload()) {
+                        match message {
+                            Ok(HubMessage::Close { error, allow_reconnect }) => {
+                                cause = CommunicationClient::get_close_cause(error, allow_reconnect);
+                                break 'receiving;
+                            },
+                            Ok(message) => match storage.process_message(message) {
+                                // a stream buffer is full, the connection is read again once it has room
+                                Ok(Some(space)) => space.await,
+                                Ok(None) => {},
+                                Err(e) => error!("Error occured processing message {}", e),
+                            },
+                            Err(e) => error!("Message could not be parsed: {}", e),
+                        }
                     }
                 }
             }
 
-            disconnection_handler.on_connection_dropped();
+            lost.store(true, Ordering::SeqCst);
+            disconnection_handler.on_connection_dropped(cause);
         });
 
         self._receiver = Some(handle);
     }
 
-    async fn send<T: serde::Serialize>(&mut self, data: T) -> Result<(), String> {
-        let json = MessageParser::to_json(&data).unwrap();
+    async fn send(&mut self, message: Message) -> Result<(), String> {
+        self._sink.send(message).await.map_err(|e| e.to_string())
+    }
+
+    /// Sends the messages queued while reconnecting, in the order they were queued.
+    async fn flush(&mut self, queue: &OutgoingQueue<Message>) {
+        let items = queue.drain();
+
+        if !items.is_empty() {
+            info!("Sending {} messages queued while reconnecting", items.len());
+        }
 
-        self._sink.send(Message::text(json)).await.map_err(|e| e.to_string())
+        for (message, result) in items {
+            let _ = result.send(self.send(message).await);
+        }
     }
 
-    async fn send_binary(&mut self, data: Vec<u8>) -> Result<(), String> {
-        self._sink.send(Message::binary(data)).await.map_err(|e| e.to_string())
+    /// Stops the receiver and sends a close frame to the hub.
+    async fn close(&mut self) {
+        self.stop_receiving();
+
+        let close = self._sink.send(Message::close(Some(CloseCode::NORMAL_CLOSURE), ""));
+
+        match tokio::time::timeout(CLOSE_TIMEOUT, close).await {
+            Ok(Ok(())) => info!("Close frame sent"),
+            Ok(Err(e)) => warn!("Cannot send the close frame: {}", e),
+            Err(_) => warn!("Sending the close frame timed out after {:?}", CLOSE_TIMEOUT),
+        }
     }
 
     fn stop_receiving(&mut self) {
@@ -241,7 +363,14 @@ pub struct CommunicationClient {
     _actions: UpdatableActionStorage,
     _reconnection_config: ReconnectionConfig,
     _disconnection_handler: Option<Arc<Box<dyn Fn(ReconnectionContext) + Send + Sync>>>,
-    _protocol_kind: HubProtocolKind,
+    _protocol: Arc<dyn HubProtocol>,
+    _tracker: ConnectionStateTracker,
+    _connection_id: String,
+    _negotiation: NegotiationOptions,
+    _queue: OutgoingQueue<Message>,
+    _connected_hook: Option<ConnectedHook>,
+    /// The connection a connected hook sends on, while the client is still reconnecting.
+    _direct: Option<Weak<Mutex<CommunicationConnection>>>,
 }
 
 impl Clone for CommunicationClient {
@@ -252,82 +381,109 @@ impl Clone for CommunicationClient {
             _actions: self._actions.clone(),
             _reconnection_config: self._reconnection_config.clone(),
             _disconnection_handler: self._disconnection_handler.clone(),
-            _protocol_kind: self._protocol_kind,
+            _protocol: self._protocol.clone(),
+            _tracker: self._tracker.clone(),
+            _connection_id: self._connection_id.clone(),
+            _negotiation: self._negotiation.clone(),
+            _queue: self._queue.clone(),
+            _connected_hook: self._connected_hook.clone(),
+            _direct: self._direct.clone(),
         }
     }
 }
 
 impl Communication for CommunicationClient {
-    async fn connect(configuration: &super::ConnectionData) -> Result<Self, String> {
-        let mut ret = CommunicationClient::create(configuration);
-
-        let res = ret.connect_internal().await;
-
-        if res.is_ok() {
-            return Ok(ret);
-        } else {
-            return Err(res.err().unwrap());
-        }
-    }
-
     fn get_storage(&self) -> Result<crate::execution::UpdatableActionStorage, String> {
         Ok(self._actions.clone())
     }
 
-    fn get_protocol_kind(&self) -> HubProtocolKind {
-        self._protocol_kind
+    fn get_state_tracker(&self) -> ConnectionStateTracker {
+        self._tracker.clone()
     }
 
-    async fn send<T: serde::Serialize>(&mut self, data: T) -> Result<(), String> {
-        let state = self._state.lock().await;
-        match &*state {
-            ConnectionState::NotConnected(reason) => Err(format!("Client is not connected, cannot send: {:?}", reason)),
-            ConnectionState::Connected(mutex) => {
-                let mut connection = mutex.lock().await;
+    async fn send(&mut self, message: &HubMessage) -> Result<(), String> {
+        let data = self._protocol.write_message(message)?;
+        let message = match self._protocol.transfer_format() {
+            TransferFormat::Text => Message::text(String::from_utf8(data).map_err(|e| format!("The protocol wrote invalid text: {}", e))?),
+            TransferFormat::Binary => Message::binary(data),
+        };
 
-                connection.send(data).await
-            },
-        }
+        self.send_message(message).await
     }
 
-    async fn send_binary(&mut self, data: Vec<u8>) -> Result<(), String> {
-        let state = self._state.lock().await;
-        match &*state {
-            ConnectionState::NotConnected(reason) => Err(format!("Client is not connected, cannot send: {:?}", reason)),
-            ConnectionState::Connected(mutex) => {
-                let mut connection = mutex.lock().await;
-                connection.send_binary(data).await
+    async fn stop(&mut self, drain_timeout: Option<Duration>) {
+        let previous = {
+            let mut state = self._state.lock().await;
+            std::mem::replace(&mut *state, ConnectionState::NotConnected(DisconnectionReason::LocalClosed))
+        };
+
+        match previous {
+            ConnectionState::NotConnected(DisconnectionReason::LocalClosed) => {
+                info!("The client is already disconnected");
+                return;
+            },
+            ConnectionState::NotConnected(reason) => {
+                // stops a reconnection in progress
+                info!("The client is not connected: {:?}, stopping", reason);
+            },
+            ConnectionState::Connected(connection) => {
+                info!("Stopping the connection...");
+
+                // the receiver keeps running, so the pending invocations can still complete
+                if let Some(timeout) = drain_timeout {
+                    self.drain(timeout).await;
+                }
+
+                connection.lock().await.close().await;
             },
         }
+
+        self.fail_all(CONNECTION_CLOSED_ERROR);
     }
 
-    async fn disconnect(&mut self) {
-        // Check how many CommunicationClient clones share this state.
-        // strong_count includes this instance, so subtract 1 to get "other" references.
-        // Only disconnect when this is the last client using the connection.
-        let other_refs = Arc::strong_count(&self._state) - 1;
+    fn release(&mut self) {
+        // the callbacks and streams keep clones of the client, which must not keep the connection open
+        let Ok(mut state) = self._state.try_lock() else {
+            // the state is locked by a send or a reconnection attempt, the client is stopped when it is released
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    let mut client = self.clone();
+                    handle.spawn(async move { client.stop(None).await });
+                },
+                Err(_) => warn!("The connection is in use and cannot be disposed without a runtime"),
+            }
 
-        if other_refs > 0 {
-            info!("The underlying connection has {} more references, not disconnecting.", other_refs);
             return;
-        }
+        };
 
-        let mut state = self._state.lock().await;
+        let previous = std::mem::replace(&mut *state, ConnectionState::NotConnected(DisconnectionReason::LocalClosed));
+        drop(state);
 
-        match &*state {
+        match previous {
+            ConnectionState::NotConnected(DisconnectionReason::LocalClosed) => {
+                info!("The client is already disconnected");
+                return;
+            },
             ConnectionState::NotConnected(reason) => {
-                info!("The client is not connected: {:?}, cannot disconnect", reason);
+                // stops a reconnection in progress
+                info!("The client is not connected: {:?}, closing", reason);
             },
-            ConnectionState::Connected(_) => {
+            ConnectionState::Connected(connection) => {
                 info!("The underlying connection is going to be disposed.");
-                *state = ConnectionState::NotConnected(DisconnectionReason::LocalClosed);
+
+                // the close frame is sent in the background, without a runtime the connection is simply dropped
+                if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                    handle.spawn(async move { connection.lock().await.close().await });
+                }
             },
         }
-    }    
+
+        self.fail_all(CONNECTION_CLOSED_ERROR);
+    }
 }
 
 impl CommunicationClient {
-    fn create(configuration: &super::ConnectionData) -> Self {
+    pub(crate) fn create(configuration: &super::ConnectionData) -> Self {
         info!("Creating communication client to {}", &configuration.get_endpoint());
         let endpoint = Uri::from_str(&configuration.get_endpoint()).expect(&format!("The endpoint Uri {:?} is invalid", configuration.get_endpoint().as_str()));
 
@@ -337,7 +493,13 @@ impl CommunicationClient {
             _actions: UpdatableActionStorage::new(),
             _reconnection_config: ReconnectionConfig::default(),
             _disconnection_handler: None,
-            _protocol_kind: configuration.get_protocol_kind(),
+            _protocol: configuration.get_protocol(),
+            _tracker: ConnectionStateTracker::new(),
+            _connection_id: configuration.get_connection_id(),
+            _negotiation: configuration.get_negotiation_options(),
+            _queue: OutgoingQueue::new(),
+            _connected_hook: None,
+            _direct: None,
         }
     }
 
@@ -345,17 +507,92 @@ impl CommunicationClient {
         self._reconnection_config = config;
     }
 
+    /// Enables queueing the outgoing messages while reconnecting, up to the given number of messages.
+    pub fn set_reconnect_queue_capacity(&mut self, capacity: Option<usize>) {
+        self._queue.set_capacity(capacity);
+    }
+
+    /// Sets how the registered callbacks run when the hub invokes them.
+    pub(crate) fn set_dispatch_mode(&mut self, mode: DispatchMode) {
+        self._actions.dispatcher().set_mode(mode);
+    }
+
+    /// Sets the options the invocations, streams and callbacks serialize and deserialize their values with.
+    pub(crate) fn set_protocol_options(&mut self, options: ProtocolOptions) {
+        self._actions.set_protocol_options(options);
+    }
+
+    /// Waits until the pending invocations and streams complete, or the timeout elapses.
+    async fn drain(&self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let pending = self._actions.pending_count();
+
+            if pending == 0 {
+                info!("All pending invocations completed");
+                break;
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                warn!("{} invocations are still pending after {:?}, failing them", pending, timeout);
+                break;
+            }
+
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Fails everything waiting for the connection and reports the closed state.
+    fn fail_all(&self, error: &str) {
+        self._queue.fail_all(error);
+        self._actions.clone().fail_pending(error);
+        self._tracker.closed(None);
+    }
+
+    /// Sets the hook that sets up every new connection opened by a reconnection.
+    pub(crate) fn set_connected_hook(&mut self, hook: ConnectedHook) {
+        self._connected_hook = Some(hook);
+    }
+
+    /// Sends the message on the current connection, or queues it if the client is reconnecting and queueing is enabled.
+    async fn send_message(&mut self, message: Message) -> Result<(), String> {
+        if let Some(connection) = self._direct.as_ref().and_then(Weak::upgrade) {
+            let mut connection = connection.lock().await;
+
+            return connection.send(message).await;
+        }
+
+        let queued = {
+            let state = self._state.lock().await;
+
+            match &*state {
+                ConnectionState::NotConnected(DisconnectionReason::Reconnecting) if self._queue.is_enabled() => {
+                    self._queue.enqueue(message)?
+                },
+                ConnectionState::NotConnected(reason) => return Err(format!("Client is not connected, cannot send: {:?}", reason)),
+                ConnectionState::Connected(mutex) => {
+                    let mut connection = mutex.lock().await;
+
+                    return connection.send(message).await;
+                },
+            }
+        };
+
+        wait_for_send(queued).await
+    }
+
     pub fn set_disconnection_handler(&mut self, handler: impl Fn(ReconnectionContext) + Send + Sync + 'static) {
         self._disconnection_handler = Some(Arc::new(Box::new(handler)));
     }
 
-    async fn connect_to_server(endpoint: Uri, protocol_kind: HubProtocolKind) -> Result<(SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>, SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>), String> {
+    async fn connect_to_server(endpoint: Uri, protocol: &dyn HubProtocol) -> Result<(SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>, SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>), ConnectionError> {
         let stream: Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, http::Response<()>), tokio_websockets::Error>;
         info!("Connecting to endpoint {}", endpoint);
 
         if Some("wss") == endpoint.scheme_str() {
             info!("Connection to secure endpoint...");
-            let Ok(connector) = TlsConnector::new() else { return Err("Cannot create default TLS connector".to_string()); };
+            let Ok(connector) = TlsConnector::new() else { return Err(ConnectionError::Transport("Cannot create default TLS connector".to_string())); };
 
             let connector = tokio_websockets::Connector::NativeTls(connector.into());
             stream = ClientBuilder::from_uri(endpoint.clone()).connector(&connector).connect().await;
@@ -369,203 +606,242 @@ impl CommunicationClient {
                 let (mut write, mut read) = ws.split();
 
                 info!("Initiating handshake...");
-                // Handshake is always JSON, even for MessagePack protocol
-                let handshake = HandshakeRequest::new(protocol_kind.protocol_name().to_string());
+                // Handshake is always JSON, whatever the protocol is
+                let handshake = HandshakeRequest::new(protocol.name(), protocol.version());
                 let message = MessageParser::to_json(&handshake).unwrap();
                 let hsres = write.send(Message::text(message)).await;
         
                 if hsres.is_ok() {            
-                    if let Some(hand) = read.next().await {
-                        if hand.is_ok() {                            
-                            Ok((write, read))
-                        } else {
-                            Err(hand.err().unwrap().to_string())
-                        }
-                    } else {
-                        Err("Handshake error".to_string())
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, read.next()).await {
+                        Ok(Some(Ok(hand))) => match CommunicationClient::get_handshake_error(&hand) {
+                            Some(error) => Err(ConnectionError::Handshake(error)),
+                            None => Ok((write, read)),
+                        },
+                        Ok(Some(Err(error))) => Err(ConnectionError::Handshake(error.to_string())),
+                        Ok(None) => Err(ConnectionError::Handshake("Handshake error".to_string())),
+                        Err(_) => Err(ConnectionError::Handshake("The hub did not answer the handshake in time".to_string())),
                     }
                 } else {
-                    Err(hsres.err().unwrap().to_string())
+                    Err(ConnectionError::Transport(hsres.err().unwrap().to_string()))
                 }    
             },
             Err(error) => {
-                Err(error.to_string())
+                Err(ConnectionError::Transport(error.to_string()))
             },
         }
     }
 
-    async fn connect_internal(&mut self) -> Result<(), String> {
-        let res = CommunicationClient::connect_to_server(self._endpoint.clone(), self._protocol_kind).await;
+    /// Opens the initial connection and runs the connected hook on it, before the client is marked connected.
+    ///
+    /// A connection that cannot be opened fails right away. A connection that fails to set up, because the hook
+    /// returned `ConnectedHookError::Retry` or the connection dropped meanwhile, is retried with the reconnection policy.
+    pub(crate) async fn connect_internal(&mut self) -> Result<(), String> {
+        let (write, read) = match CommunicationClient::connect_to_server(self._endpoint.clone(), self._protocol.as_ref()).await {
+            Ok(opened) => opened,
+            Err(e) => {
+                self._tracker.closed(Some(e.to_string()));
+                return Err(e.to_string());
+            },
+        };
+
+        let context = ReconnectionContext {
+            state: Arc::downgrade(&self._state),
+            endpoint: self._endpoint.clone(),
+            actions: self._actions.clone(),
+            reconnection_config: self._reconnection_config.clone(),
+            protocol: self._protocol.clone(),
+            tracker: self._tracker.clone(),
+            negotiation: self._negotiation.clone(),
+            queue: self._queue.clone(),
+            connected_hook: self._connected_hook.clone(),
+            cause: DisconnectCause::ConnectionLost,
+            pending: None,
+        };
+
+        let handler = ClientDisconnectionHandler {
+            context: context.clone(),
+            user_handler: self._disconnection_handler.clone(),
+        };
+
+        let mut result = context.set_up(write, read, self._connection_id.clone(), handler).await;
+        let mut retry_count = 0u32;
+        let start_time = std::time::Instant::now();
 
-        match res {
-            Ok((write, read)) => {
-                let mut connection = CommunicationConnection {
-                    _receiver: None,
-                    _sink: write,
-                };
+        loop {
+            let error = match result {
+                Ok(()) => return Ok(()),
+                Err(AttemptError::Abort(e)) => e,
+                Err(AttemptError::Retry(e)) => {
+                    let retry = RetryContext::new(retry_count, start_time.elapsed(), Some(e.clone()), DisconnectCause::ConnectionLost);
 
-                let handler = ClientDisconnectionHandler {
-                    state: Arc::downgrade(&self._state),
-                    endpoint: self._endpoint.clone(),
-                    actions: self._actions.clone(),
-                    reconnection_config: self._reconnection_config.clone(),
-                    user_handler: self._disconnection_handler.clone(),
-                    protocol_kind: self._protocol_kind,
-                };
+                    if let Some(delay) = self._reconnection_config.policy.next_delay(&retry) {
+                        warn!("The connection could not be set up, retrying in {:?}: {}", delay, e);
+                        tokio::time::sleep(delay).await;
 
-                connection.start_receiving(read, self._actions.clone(), handler, self._protocol_kind);
-                
-                let mut state = self._state.lock().await;
-                *state = ConnectionState::Connected(Arc::new(Mutex::new(connection)));
+                        retry_count += 1;
+                        result = context.establish().await;
+                        continue;
+                    }
 
-                Ok(())
-            },
-            Err(e) => Err(e),
+                    e.to_string()
+                },
+            };
+
+            let error = format!("The connection could not be set up: {}", error);
+            let mut state = self._state.lock().await;
+
+            if !matches!(*state, ConnectionState::NotConnected(DisconnectionReason::LocalClosed)) {
+                *state = ConnectionState::NotConnected(DisconnectionReason::RemoteClosed);
+            }
+
+            self._tracker.closed(Some(error.clone()));
+
+            return Err(error);
         }
     }
     
-    fn get_text_messages(message: &Message) -> Vec<String> {
-        if message.is_text() {
-            if let Some(txt) = message.as_text() {
-                return txt.split(RECORD_SEPARATOR)
-                   .map(|s| MessageParser::strip_record_separator(s).to_string())
-                   .filter(|s| s.len() > 0)
-                   .collect();
-            }
-        }
-
-        Vec::new()
+    /// Negotiates a new connection token and opens a new connection with it.
+    ///
+    /// The token of the lost connection cannot be reused, the hub does not accept it anymore.
+    async fn reconnect_to_server(negotiation: &NegotiationOptions, protocol: &dyn HubProtocol) -> Result<(SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>, SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>, String), ConnectionError> {
+        let configuration = HttpClient::negotiate(negotiation).await?;
+        let endpoint = Uri::from_str(&configuration.get_endpoint())
+            .map_err(|e| ConnectionError::Transport(format!("The endpoint Uri {:?} is invalid: {}", configuration.get_endpoint(), e)))?;
+        let (write, read) = CommunicationClient::connect_to_server(endpoint, protocol).await?;
+
+        Ok((write, read, configuration.get_connection_id()))
     }
 
-    #[cfg(feature = "messagepack")]
-    fn get_binary_messages(message: &Message) -> Vec<Vec<u8>> {
-        if message.is_binary() {
-            let data = message.as_payload();
-            match crate::protocol::msgpack::split_framed_messages(data) {
-                Ok(frames) => frames,
-                Err(e) => {
-                    error!("Failed to split binary frames: {}", e);
-                    Vec::new()
-                }
-            }
-        } else {
-            Vec::new()
+    /// Returns the error of the handshake response, if the hub rejected the handshake.
+    fn get_handshake_error(message: &Message) -> Option<String> {
+        let text = message.as_text()?;
+        let response = text.split(RECORD_SEPARATOR).next()?;
+
+        match MessageParser::parse_message::<HandshakeResponse>(MessageParser::strip_record_separator(response)) {
+            Ok(response) => response.error(),
+            Err(e) => Some(format!("Invalid handshake response: {}", e)),
         }
     }
+
+    /// Returns the cause of a close message sent by the hub.
+    fn get_close_cause(error: Option<String>, allow_reconnect: bool) -> DisconnectCause {
+        info!("The hub closed the connection: {:?}, reconnect allowed: {}", error, allow_reconnect);
+        DisconnectCause::ServerClosed { error, allow_reconnect }
+    }
 }
 
 struct ClientDisconnectionHandler {
-    state: Weak<Mutex<ConnectionState>>,
-    endpoint: Uri,
-    actions: UpdatableActionStorage,
-    reconnection_config: ReconnectionConfig,
+    context: ReconnectionContext,
     /// If set, user has full control over reconnection (manual mode).
     /// If None, automatic reconnection is used.
     user_handler: Option<Arc<Box<dyn Fn(ReconnectionContext) + Send + Sync>>>,
-    protocol_kind: HubProtocolKind,
+}
+
+impl ClientDisconnectionHandler {
+    /// Returns `true` if the client was closed locally or dropped, so no reconnection should happen.
+    async fn is_closed(state: &Weak<Mutex<ConnectionState>>) -> bool {
+        match state.upgrade() {
+            Some(s) => matches!(*s.lock().await, ConnectionState::NotConnected(DisconnectionReason::LocalClosed)),
+            None => true,
+        }
+    }
+
+    /// Returns `true` if the dropped connection needs no handling. Besides a closed client, a connection
+    /// that drops while the initial connection or a reconnection attempt is still setting it up is handled by that attempt.
+    async fn is_handled(state: &Weak<Mutex<ConnectionState>>) -> bool {
+        match state.upgrade() {
+            Some(s) => matches!(*s.lock().await, ConnectionState::NotConnected(DisconnectionReason::LocalClosed | DisconnectionReason::Reconnecting | DisconnectionReason::NeverOpened)),
+            None => true,
+        }
+    }
+
+    async fn set_state(state: &Weak<Mutex<ConnectionState>>, reason: DisconnectionReason) {
+        if let Some(s) = state.upgrade() {
+            let mut guard = s.lock().await;
+            *guard = ConnectionState::NotConnected(reason);
+        }
+    }
 }
 
 impl CommunicationDisconnectionHandler for ClientDisconnectionHandler {
-    fn on_connection_dropped(&self) {
-        let state = self.state.clone();
-        let endpoint = self.endpoint.clone();
-        let actions = self.actions.clone();
-        let config = self.reconnection_config.clone();
+    fn on_connection_dropped(&self, cause: DisconnectCause) {
+        self.context.actions.clone().fail_pending(CONNECTION_LOST_ERROR);
+
+        let mut context = self.context.clone();
+        context.cause = cause;
         let user_handler = self.user_handler.clone();
-        let protocol_kind = self.protocol_kind;
 
         tokio::spawn(async move {
-            // Check if locally closed - if so, don't do anything
-            if let Some(s) = state.upgrade() {
-                let guard = s.lock().await;
-                if let ConnectionState::NotConnected(DisconnectionReason::LocalClosed) = *guard {
-                    return;
-                }
-            } else {
+            // Check if locally closed or still being set up - if so, don't do anything
+            if ClientDisconnectionHandler::is_handled(&context.state).await {
                 return;
             }
 
             // If user has a handler, give them full control (manual mode)
             if let Some(handler) = user_handler {
-                // Set state to RemoteClosed (user will change it if they reconnect)
-                if let Some(s) = state.upgrade() {
-                    let mut guard = s.lock().await;
-                    *guard = ConnectionState::NotConnected(DisconnectionReason::RemoteClosed);
-                }
-
-                // Create context for manual reconnection
-                let context = ReconnectionContext {
-                    state: state.clone(),
-                    endpoint: endpoint.clone(),
-                    actions: actions.clone(),
-                    reconnection_config: config.clone(),
-                    protocol_kind,
-                };
+                // Set state to RemoteClosed (user will change it if they reconnect), the client is
+                // reconnecting for the user until the handler reconnects or drops the context
+                ClientDisconnectionHandler::set_state(&context.state, DisconnectionReason::RemoteClosed).await;
+                context.tracker.reconnecting(Some(context.cause.to_string()));
+                context.pending = Some(Arc::new(PendingReconnection::new(context.tracker.clone(), context.cause.to_string())));
 
                 info!("Connection dropped. Calling user's disconnection handler (manual mode).");
                 handler(context);
                 return;
             }
 
-            // No user handler - use automatic reconnection
-            if let Some(s) = state.upgrade() {
-                let mut guard = s.lock().await;
-                *guard = ConnectionState::NotConnected(DisconnectionReason::Reconnecting);
+            // No user handler - use automatic reconnection, unless the policy does not allow any attempt
+            if context.reconnection_config.policy.next_delay(&RetryContext::new(0, Duration::ZERO, None, context.cause.clone())).is_none() {
+                info!("Connection dropped. The reconnection policy does not allow reconnecting.");
+                ClientDisconnectionHandler::set_state(&context.state, DisconnectionReason::RemoteClosed).await;
+                context.tracker.closed(Some(context.cause.to_string()));
+                return;
             }
 
+            ClientDisconnectionHandler::set_state(&context.state, DisconnectionReason::Reconnecting).await;
+            context.tracker.reconnecting(Some(context.cause.to_string()));
+
             let mut retry_count = 0;
+            let mut last_error: Option<ConnectionError> = None;
             let start_time = std::time::Instant::now();
 
             loop {
-                let delay = config.policy.next_retry_delay(retry_count, start_time.elapsed().as_millis() as u64);
+                let retry = RetryContext::new(retry_count, start_time.elapsed(), last_error.clone(), context.cause.clone());
+                let delay = context.reconnection_config.policy.next_delay(&retry);
 
                 if let Some(d) = delay {
                     tokio::time::sleep(d).await;
-                    info!("Reconnecting to {} (attempt {})...", endpoint, retry_count + 1);
-
-                    match CommunicationClient::connect_to_server(endpoint.clone(), protocol_kind).await {
-                        Ok((write, read)) => {
-                             if let Some(s) = state.upgrade() {
-                                let mut guard = s.lock().await;
-                                if let ConnectionState::NotConnected(DisconnectionReason::LocalClosed) = *guard {
-                                    return;
-                                }
-
-                                let new_handler = ClientDisconnectionHandler {
-                                    state: state.clone(),
-                                    endpoint: endpoint.clone(),
-                                    actions: actions.clone(),
-                                    reconnection_config: config.clone(),
-                                    user_handler: None, // Automatic mode continues without user handler
-                                    protocol_kind,
-                                };
-
-                                let mut conn_struct = CommunicationConnection {
-                                    _receiver: None,
-                                    _sink: write,
-                                };
-                                conn_struct.start_receiving(read, actions.clone(), new_handler, protocol_kind);
-                                *guard = ConnectionState::Connected(Arc::new(Mutex::new(conn_struct)));
-                                info!("Reconnected successfully (automatic mode)");
-                                return;
-                             } else {
-                                 return;
-                             }
+
+                    if ClientDisconnectionHandler::is_closed(&context.state).await {
+                        return;
+                    }
+
+                    info!("Reconnecting to {} (attempt {})...", context.endpoint, retry_count + 1);
+
+                    match context.establish().await {
+                        Ok(()) => {
+                            info!("Reconnected successfully (automatic mode)");
+                            return;
+                        },
+                        Err(AttemptError::Abort(e)) => {
+                            error!("Reconnection aborted: {}", e);
+                            context.close_unless_local(&e).await;
+                            return;
                         },
-                        Err(e) => {
+                        Err(AttemptError::Retry(e)) => {
                             error!("Reconnection failed: {}", e);
+                            last_error = Some(e);
                             retry_count += 1;
                         }
                     }
                 } else {
                     info!("Automatic reconnection attempts exhausted.");
-                    if let Some(s) = state.upgrade() {
-                        let mut guard = s.lock().await;
-                        *guard = ConnectionState::NotConnected(DisconnectionReason::RemoteClosed);
-                    }
+                    // terminal state, the client does not try to reconnect anymore
+                    let error = last_error.map(|e| e.to_string()).unwrap_or_else(|| context.cause.to_string());
+                    context.close_unless_local(&format!("Reconnection attempts exhausted: {}", error)).await;
                     return;
                 }
             }
         });
     }
-}
\ No newline at end of file
+}
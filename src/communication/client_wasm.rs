@@ -1,392 +1,982 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::{Rc, Weak}, sync::Arc, time::Duration};
 
 use log::{debug, error, info, warn};
-use wasm_bindgen::prelude::wasm_bindgen;
-use wasm_sockets::{ConnectionStatus, PollingClient};
+use wasm_bindgen::{prelude::Closure, JsCast};
+use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
 
-use crate::{completer::CompletedFuture,
-    execution::
-        {ManualFutureState, Storage, UpdatableActionStorage}, protocol::{hub_protocol::{HubProtocolKind, MessagePayload}, messages::{MessageParser, RECORD_SEPARATOR}, negotiate::{HandshakeRequest, HandshakeResponse, Ping}}};
+use crate::{execution::
+        {DispatchMode, ManualFutureState, Storage, UpdatableActionStorage}, protocol::{hub_protocol::{HubMessage, HubProtocol, ProtocolOptions, TransferFormat}, messages::{MessageParser, RECORD_SEPARATOR}, negotiate::{HandshakeRequest, HandshakeResponse}}};
 
-use super::common::Communication;
+use super::{ConnectedHook, ConnectionStateTracker, HttpClient, PendingReconnection, NegotiationOptions, common::{Communication, CLIENT_DROPPED_ERROR, CONNECTION_CLOSED_ERROR, CONNECTION_LOST_ERROR, HANDSHAKE_TIMEOUT, LOCALLY_CLOSED_ERROR}, queue::{wait_for_send, OutgoingQueue, RECONNECT_ABANDONED_ERROR}, reconnection::{AttemptError, ConnectionError, DisconnectCause, ReconnectionConfig, RetryContext}};
 
-#[wasm_bindgen]
-extern "C" {
-    fn setInterval(closure: &wasm_bindgen::prelude::Closure<dyn Fn()>, time: u32) -> f64;
-    fn clearInterval(token: f64);
-}
+/// How often the pending invocations are checked while draining.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
-#[derive(Clone)]
-pub enum ConnectionState {
-    Connect(ManualFutureState),
-    Handshake(ManualFutureState),
-    Process(UpdatableActionStorage),
+/// A message sent or received on the web socket. Outgoing messages are kept in the queue while reconnecting.
+enum SocketMessage {
+    Text(String),
+    Binary(Vec<u8>),
 }
 
-pub struct CommunicationClient {
-    _client: Option<Rc<RefCell<PollingClient>>>,
-    _state: Rc<RefCell<ConnectionState>>,
-    _token: Option<f64>,
-    _protocol_kind: HubProtocolKind,
-}
-
-impl Clone for CommunicationClient {
-    fn clone(&self) -> Self {
-        if self._client.is_some() {
-            let count = Rc::strong_count(self._client.as_ref().unwrap());
-
-            info!("Cloning communication client {} times", count + 1);
-        } else {
-            info!("Cloning empty communication client");
+impl SocketMessage {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            SocketMessage::Text(text) => text.as_bytes(),
+            SocketMessage::Binary(data) => data,
         }
-        Self { _client: self._client.clone(), _state: self._state.clone(), _token: self._token.clone(), _protocol_kind: self._protocol_kind }
     }
 }
 
-impl Drop for CommunicationClient {
-    fn drop(&mut self) {
-        self.disconnect_internal();
-    }
+/// A web socket with its event handlers.
+///
+/// The handlers are detached when the connection is closed, so a replaced web socket cannot affect the client anymore.
+struct WebSocketConnection {
+    _socket: WebSocket,
+    _onopen: Closure<dyn FnMut(Event)>,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+    _onclose: Closure<dyn FnMut(CloseEvent)>,
+    _onerror: Closure<dyn FnMut(Event)>,
 }
 
-impl Communication for CommunicationClient {
-    async fn connect(configuration: &super::ConnectionData) -> Result<Self, String> {
-        let mut ret = CommunicationClient::create(configuration);
+impl WebSocketConnection {
+    /// Opens a web socket to the endpoint, which reports its events to the context.
+    fn open(endpoint: &str, context: &ReconnectionContext) -> Result<Self, ConnectionError> {
+        let socket = WebSocket::new(endpoint)
+            .map_err(|e| ConnectionError::Transport(format!("The web socket cannot be created: {:?}", e)))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let ctx = context.clone();
+        let onopen = Closure::<dyn FnMut(Event)>::new(move |_: Event| ctx.on_open());
+        let ctx = context.clone();
+        let onmessage = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            match WebSocketConnection::read_message(&event) {
+                Some(message) => ctx.on_message(message),
+                None => warn!("Received a message of unknown type, ignoring"),
+            }
+        });
+        let ctx = context.clone();
+        let onclose = Closure::<dyn FnMut(CloseEvent)>::new(move |event: CloseEvent| ctx.on_close(&event));
+        let endpoint_name = endpoint.to_string();
+        let onerror = Closure::<dyn FnMut(Event)>::new(move |_: Event| error!("Web socket error at endpoint {}", endpoint_name));
+
+        socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(onerror.as_ref().unchecked_ref()));
+
+        Ok(WebSocketConnection {
+            _socket: socket,
+            _onopen: onopen,
+            _onmessage: onmessage,
+            _onclose: onclose,
+            _onerror: onerror,
+        })
+    }
 
-        let res = ret.connect_internal().await;
+    fn read_message(event: &MessageEvent) -> Option<SocketMessage> {
+        let data = event.data();
 
-        if res.is_ok() {
-            return Ok(ret);
+        if let Some(text) = data.as_string() {
+            Some(SocketMessage::Text(text))
+        } else if let Ok(buffer) = data.dyn_into::<js_sys::ArrayBuffer>() {
+            Some(SocketMessage::Binary(js_sys::Uint8Array::new(&buffer).to_vec()))
         } else {
-            return Err(res.err().unwrap());
+            None
         }
     }
 
-    async fn send<T: serde::Serialize>(&mut self, data: T) -> Result<(), String> {
-        let res = self.send_internal(data);
+    fn send(&self, message: SocketMessage) -> Result<(), String> {
+        if self._socket.ready_state() != WebSocket::OPEN {
+            return Err("The web socket is not open. Cannot send data".to_string());
+        }
+
+        let res = match message {
+            SocketMessage::Text(text) => self._socket.send_with_str(&text),
+            SocketMessage::Binary(data) => self._socket.send_with_u8_array(&data),
+        };
 
-        CompletedFuture::new(res).await
+        res.map_err(|e| e.as_string().unwrap_or_else(|| format!("{:?}", e)))
     }
 
-    async fn send_binary(&mut self, data: Vec<u8>) -> Result<(), String> {
-        let res = self.send_binary_internal(data);
+    /// Detaches the event handlers and closes the web socket.
+    fn close(self) {
+        self._socket.set_onopen(None);
+        self._socket.set_onmessage(None);
+        self._socket.set_onclose(None);
+        self._socket.set_onerror(None);
+
+        if let Err(e) = self._socket.close() {
+            warn!("Cannot close the web socket: {:?}", e);
+        }
 
-        CompletedFuture::new(res).await
+        // the connection may be closed from one of its own handlers, which must not be dropped while it runs
+        wasm_bindgen_futures::spawn_local(async move {
+            drop(self);
+        });
     }
+}
+
+/// Context for manual reconnection, passed to user's disconnection handler.
+/// Allows the user to manually trigger reconnection attempts.
+#[derive(Clone)]
+pub struct ReconnectionContext {
+    state: Weak<RefCell<ConnectionState>>,
+    client: Weak<RefCell<Option<WebSocketConnection>>>,
+    endpoint: String,
+    actions: UpdatableActionStorage,
+    reconnection_config: ReconnectionConfig,
+    protocol: Arc<dyn HubProtocol>,
+    tracker: ConnectionStateTracker,
+    negotiation: NegotiationOptions,
+    queue: OutgoingQueue<SocketMessage>,
+    connected_hook: Option<ConnectedHook>,
+    /// If set, user has full control over reconnection (manual mode).
+    /// If None, automatic reconnection is used.
+    user_handler: Option<Rc<dyn Fn(ReconnectionContext)>>,
+    cause: DisconnectCause,
+    /// Set for the disconnection handler, closes the client when the handler drops the context without reconnecting.
+    pending: Option<Rc<PendingReconnection>>,
+}
 
-    fn get_protocol_kind(&self) -> HubProtocolKind {
-        self._protocol_kind
+impl ReconnectionContext {
+    /// Attempt to reconnect once. Returns Ok(()) on success.
+    pub async fn reconnect(&self) -> Result<(), String> {
+        match self.try_reconnect().await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let error = e.message();
+                self.close_unless_local(&error);
+
+                Err(error)
+            },
+        }
     }
 
-    fn get_storage(&self) -> Result<UpdatableActionStorage, String> {
-        let procstate: ConnectionState;
+    async fn try_reconnect(&self) -> Result<(), AttemptError> {
+        let state = self.state.upgrade().ok_or_else(|| AttemptError::Abort(CLIENT_DROPPED_ERROR.to_string()))?;
 
+        // Check if already connected, then set state to reconnecting
         {
-            let st = self._state.borrow();
-            procstate = st.clone();
-            drop(st);
+            let mut guard = state.borrow_mut();
+
+            match *guard {
+                ConnectionState::Process => return Ok(()),
+                ConnectionState::NotConnected(DisconnectionReason::LocalClosed) => {
+                    return Err(AttemptError::Abort(LOCALLY_CLOSED_ERROR.to_string()));
+                },
+                _ => *guard = ConnectionState::NotConnected(DisconnectionReason::Reconnecting),
+            }
         }
 
-        if let ConnectionState::Process(storage) = procstate {
-            Ok(storage)
-        } else {
-            Err(format!("The connection is in a bad state"))
+        self.tracker.reconnecting(None);
+        info!("Manual reconnection attempt to {}...", self.endpoint);
+
+        let res = self.establish().await;
+
+        match &res {
+            Ok(()) => info!("Manual reconnection successful"),
+            Err(e) => error!("Manual reconnection failed: {}", e.message()),
         }
+
+        res
     }
 
-    async fn disconnect(&mut self) {
-        let task = CompletedFuture::new(());
-        task.await;
+    /// Attempt reconnection with automatic retries using the configured policy.
+    /// Returns Ok(()) on success, Err if all attempts are exhausted.
+    pub async fn reconnect_with_policy(&self) -> Result<(), String> {
+        let mut retry_count = 0u32;
+        let mut previous_error = None;
+        let start_time = wasm_timer::Instant::now();
 
-        self.disconnect_internal();
-    }    
-}
+        loop {
+            let context = RetryContext::new(retry_count, start_time.elapsed(), previous_error.take(), self.cause.clone());
+            let delay = self.reconnection_config.policy.next_delay(&context);
 
-impl CommunicationClient {
-    fn create(configuration: &super::ConnectionData) -> Self {
-        info!("Creating communication client to {}", &configuration.get_endpoint());
-        let res = PollingClient::new(&configuration.get_endpoint());
-        let protocol_kind = configuration.get_protocol_kind();
-
-        if res.is_ok() {
-            CommunicationClient {
-                _state: Rc::new(RefCell::new(ConnectionState::Connect(ManualFutureState::new()))),
-                _client: Some(Rc::new(RefCell::new(res.unwrap()))),
-                _token: None,
-                _protocol_kind: protocol_kind,
-            }
-        } else {
-            CommunicationClient {
-                _state: Rc::new(RefCell::new(ConnectionState::Connect(ManualFutureState::new()))),
-                _client: None,
-                _token: None,
-                _protocol_kind: protocol_kind,
+            if let Some(d) = delay {
+                if retry_count > 0 {
+                    let _ = wasm_timer::Delay::new(d).await;
+                }
+
+                match self.try_reconnect().await {
+                    Ok(()) => return Ok(()),
+                    Err(AttemptError::Abort(e)) => {
+                        self.close_unless_local(&e);
+
+                        return Err(e);
+                    },
+                    Err(AttemptError::Retry(e)) => {
+                        previous_error = Some(e);
+                        retry_count += 1;
+                    }
+                }
+            } else {
+                let error = "Reconnection attempts exhausted".to_string();
+                self.close_unless_local(&error);
+
+                return Err(error);
             }
         }
     }
 
-    async fn connect_internal(&mut self) -> Result<(), String> {
-        let connstate: ConnectionState;
-        {
-            let st = self._state.borrow_mut();
-            connstate = st.clone();
-            drop(st);
-        }
-
-        if let ConnectionState::Connect(mut connected) = connstate {
-            if self._client.is_some() {
-                let refclient = self._client.as_ref().unwrap().clone();
-                let refstate = self._state.clone();
-                let protocol_kind = self._protocol_kind;
-
-                let closure = wasm_bindgen::prelude::Closure::wrap(Box::new(move || {
-                    CommunicationClient::polling_loop(&refclient, &refstate, protocol_kind);
-                }) as Box<dyn Fn()>);
-        
-                info!("Starting poll loop");
-                let token = setInterval(&closure, 100);
-                closure.forget();
-        
-                info!("Waiting for uplink...");
-                connected.awaiter().await;
-                self._token = Some(token);
-    
-                info!("Initiating handshake...");
-                let r = self.send(HandshakeRequest::new(self._protocol_kind.protocol_name().to_string())).await;
-    
-                if r.is_err() {
-                    return Err(format!("Handshake cannot be sent. {}", r.unwrap_err()));
+    /// Retries with the configured policy until the connection is restored or the policy gives up.
+    async fn reconnect_automatically(&self) {
+        let mut retry_count = 0;
+        let mut last_error: Option<ConnectionError> = None;
+        let start_time = wasm_timer::Instant::now();
+
+        loop {
+            let retry = RetryContext::new(retry_count, start_time.elapsed(), last_error.clone(), self.cause.clone());
+            let delay = self.reconnection_config.policy.next_delay(&retry);
+
+            if let Some(d) = delay {
+                let _ = wasm_timer::Delay::new(d).await;
+
+                if self.is_closed() {
+                    return;
+                }
+
+                info!("Reconnecting to {} (attempt {})...", self.endpoint, retry_count + 1);
+
+                match self.establish().await {
+                    Ok(()) => {
+                        info!("Reconnected successfully (automatic mode)");
+                        return;
+                    },
+                    Err(AttemptError::Abort(e)) => {
+                        error!("Reconnection aborted: {}", e);
+                        self.close_unless_local(&e);
+                        return;
+                    },
+                    Err(AttemptError::Retry(e)) => {
+                        error!("Reconnection failed: {}", e);
+                        last_error = Some(e);
+                        retry_count += 1;
+                    }
                 }
-    
-                let mut state = self._state.borrow_mut(); 
-                *state = ConnectionState::Handshake(ManualFutureState::new());    
             } else {
-                return Err(format!("Connection client is not created properly. Connection has failed"));
+                info!("Automatic reconnection attempts exhausted.");
+                // terminal state, the client does not try to reconnect anymore
+                let error = last_error.map(|e| e.to_string()).unwrap_or_else(|| self.cause.to_string());
+                self.close_unless_local(&format!("Reconnection attempts exhausted: {}", error));
+                return;
             }
         }
+    }
 
-        let handstate: ConnectionState;
+    /// A copy of the context for a new connection, which does not keep the pending reconnection alive.
+    fn detached(&self) -> Self {
+        ReconnectionContext { pending: None, ..self.clone() }
+    }
 
-        {
-            let st = self._state.borrow_mut();
-            handstate = st.clone();
-            drop(st);
-        }
-        
-        if let ConnectionState::Handshake(mut handshake) = handstate {
-            let shook = handshake.awaiter().await;
+    /// Opens a new web socket and runs the connected hook on it, then sends the queued messages and marks the client connected.
+    ///
+    /// A failed attempt closes the new web socket, so its messages are not processed until the next attempt.
+    async fn establish(&self) -> Result<(), AttemptError> {
+        let res = self.open_connection().await;
 
-            if shook {
-                let mut state = self._state.borrow_mut(); 
-                *state = ConnectionState::Process(UpdatableActionStorage::new());
-            } else {
-                return Err("Unsuccessfull handshake".to_string());
+        if res.is_err() {
+            if let Some(client) = self.client.upgrade() {
+                CommunicationClient::close_client(&client);
+            }
+
+            if let Some(state) = self.state.upgrade() {
+                CommunicationClient::set_state(&state, ConnectionState::NotConnected(DisconnectionReason::Reconnecting));
             }
         }
 
+        res
+    }
+
+    async fn open_connection(&self) -> Result<(), AttemptError> {
+        let (state, client) = match (self.state.upgrade(), self.client.upgrade()) {
+            (Some(state), Some(client)) => (state, client),
+            _ => return Err(AttemptError::Abort(CLIENT_DROPPED_ERROR.to_string())),
+        };
+
+        let configuration = HttpClient::negotiate(&self.negotiation).await.map_err(AttemptError::Retry)?;
+        self.check_closed()?;
+
+        let opened = self.open(&state, &client, &configuration.get_endpoint()).await;
+        self.check_closed()?;
+        opened.map_err(AttemptError::Retry)?;
+
+        self.set_up(&state, &client, configuration.get_connection_id()).await
+    }
+
+    /// Runs the connected hook on an opened web socket, then sends the queued messages and marks the client connected.
+    async fn set_up(&self, state: &Rc<RefCell<ConnectionState>>, client: &Rc<RefCell<Option<WebSocketConnection>>>, connection_id: String) -> Result<(), AttemptError> {
+        // the messages are processed while the hook runs, but the other senders keep queueing
+        CommunicationClient::set_state(state, ConnectionState::Setup);
+
+        if let Some(hook) = &self.connected_hook {
+            hook(self.setup_client(state.clone(), client.clone(), connection_id.clone())).await?;
+        }
+
+        match &*state.borrow() {
+            ConnectionState::Setup => {},
+            ConnectionState::NotConnected(DisconnectionReason::LocalClosed) => return Err(AttemptError::Abort(LOCALLY_CLOSED_ERROR.to_string())),
+            _ => return Err(AttemptError::Retry(ConnectionError::Transport(CONNECTION_LOST_ERROR.to_string()))),
+        }
+
+        for (message, sender) in self.queue.drain() {
+            let _ = sender.send(CommunicationClient::send_on(client, message));
+        }
+
+        CommunicationClient::set_state(state, ConnectionState::Process);
+        self.tracker.connected(Some(connection_id));
+
         Ok(())
     }
 
-    fn get_text_messages(message: wasm_sockets::Message) -> Vec<String> {
-        match message {
-            wasm_sockets::Message::Text(txt) => {
-                txt.split(RECORD_SEPARATOR).map(|s| MessageParser::strip_record_separator(s).to_string()).collect()
-            },
-            wasm_sockets::Message::Binary(_) => {
-                debug!("Received binary message in text mode, ignoring");
-                Vec::new()
-            },
+    /// Opens a web socket to the endpoint, waits until it is open, then performs the handshake.
+    async fn open(&self, state: &Rc<RefCell<ConnectionState>>, client: &Rc<RefCell<Option<WebSocketConnection>>>, endpoint: &str) -> Result<(), ConnectionError> {
+        let mut connected = ManualFutureState::new();
+        CommunicationClient::set_state(state, ConnectionState::Connect(connected.clone()));
+
+        let connection = WebSocketConnection::open(endpoint, &self.detached())?;
+
+        if let Some(previous) = client.replace(Some(connection)) {
+            previous.close();
+        }
+
+        info!("Waiting for uplink...");
+        if !connected.awaiter().await {
+            return Err(ConnectionError::Transport("The web socket could not be opened".to_string()));
+        }
+
+        let mut handshake = ManualFutureState::new();
+        CommunicationClient::set_state(state, ConnectionState::Handshake(handshake.clone()));
+
+        info!("Initiating handshake...");
+        let request = MessageParser::to_json(&HandshakeRequest::new(self.protocol.name(), self.protocol.version())).unwrap();
+        CommunicationClient::send_on(client, SocketMessage::Text(request))
+            .map_err(|e| ConnectionError::Handshake(format!("Handshake cannot be sent. {}", e)))?;
+
+        let response = futures::future::select(Box::pin(handshake.awaiter()), wasm_timer::Delay::new(HANDSHAKE_TIMEOUT)).await;
+
+        match response {
+            futures::future::Either::Left((true, _)) => Ok(()),
+            futures::future::Either::Left((false, _)) => Err(ConnectionError::Handshake("Unsuccessfull handshake".to_string())),
+            futures::future::Either::Right(_) => Err(ConnectionError::Handshake("The hub did not answer the handshake in time".to_string())),
         }
     }
 
-    #[cfg(feature = "messagepack")]
-    fn get_binary_messages(message: wasm_sockets::Message) -> Vec<Vec<u8>> {
-        match message {
-            wasm_sockets::Message::Binary(data) => {
-                match crate::protocol::msgpack::split_framed_messages(&data) {
-                    Ok(frames) => frames,
-                    Err(e) => {
-                        error!("Failed to split binary frames: {}", e);
-                        Vec::new()
-                    }
+    /// Creates the client passed to the connected hook, which sends on the new web socket directly.
+    fn setup_client(&self, state: Rc<RefCell<ConnectionState>>, client: Rc<RefCell<Option<WebSocketConnection>>>, connection_id: String) -> CommunicationClient {
+        CommunicationClient {
+            _client: client,
+            _state: state,
+            _endpoint: self.endpoint.clone(),
+            _actions: self.actions.clone(),
+            _reconnection_config: self.reconnection_config.clone(),
+            _disconnection_handler: self.user_handler.clone(),
+            _protocol: self.protocol.clone(),
+            _tracker: self.tracker.clone(),
+            _connection_id: connection_id,
+            _negotiation: self.negotiation.clone(),
+            _queue: self.queue.clone(),
+            _connected_hook: self.connected_hook.clone(),
+            _direct: true,
+        }
+    }
+
+    fn on_open(&self) {
+        let Some(state) = self.state.upgrade() else {
+            return;
+        };
+
+        if let ConnectionState::Connect(connected) = &mut *state.borrow_mut() {
+            connected.complete(true);
+        };
+    }
+
+    fn on_message(&self, message: SocketMessage) {
+        let Some(state) = self.state.upgrade() else {
+            return;
+        };
+
+        let (message, processing) = match &mut *state.borrow_mut() {
+            ConnectionState::Handshake(handshake) => {
+                let Some((accepted, rest)) = CommunicationClient::read_handshake(&message) else {
+                    return;
+                };
+
+                handshake.complete(accepted);
+
+                // the first messages of the hub may follow the response in the same frame
+                match rest {
+                    Some(rest) if accepted => (rest, false),
+                    _ => return,
                 }
             },
-            wasm_sockets::Message::Text(_) => {
-                warn!("Received text message in binary mode, ignoring");
-                Vec::new()
+            ConnectionState::Setup => (message, false),
+            ConnectionState::Process => (message, true),
+            // the pending invocations can still complete while the client drains
+            ConnectionState::NotConnected(DisconnectionReason::LocalClosed) => (message, false),
+            _ => {
+                debug!("Message received while not connected, ignoring");
+                return;
             },
+        };
+
+        // the state is not borrowed while the messages are processed, so the callbacks can use the client
+        if let Some(cause) = CommunicationClient::process_messages(message, &self.actions, self.protocol.as_ref()) {
+            self.connection_dropped(&state, cause, processing);
         }
     }
 
-    fn send_internal<T: serde::Serialize>(&self, data: T) -> Result<(), String> {
-        let json = MessageParser::to_json(&data).unwrap();
+    fn on_close(&self, event: &CloseEvent) {
+        info!("The web socket to {} was closed with code {}: {}", self.endpoint, event.code(), event.reason());
 
-        if self._client.is_some() {
-            let bclient = self._client.as_ref().unwrap().borrow();
-            return bclient.send_string(&json).map_err(|e| e.as_string().unwrap());
-        } else {
-            return Err(format!("The client is not connected. Cannot send data"));
+        let Some(state) = self.state.upgrade() else {
+            return;
+        };
+
+        let processing = match &mut *state.borrow_mut() {
+            ConnectionState::Connect(pending) | ConnectionState::Handshake(pending) => {
+                pending.complete(false);
+                return;
+            },
+            ConnectionState::Setup => false,
+            ConnectionState::Process => true,
+            ConnectionState::NotConnected(_) => return,
+        };
+
+        self.connection_dropped(&state, DisconnectCause::ConnectionLost, processing);
+    }
+
+    /// Closes the lost connection. A connection lost while it is set up is handled by the reconnection attempt.
+    fn connection_dropped(&self, state: &Rc<RefCell<ConnectionState>>, cause: DisconnectCause, was_connected: bool) {
+        CommunicationClient::set_state(state, ConnectionState::NotConnected(DisconnectionReason::RemoteClosed));
+
+        if let Some(client) = self.client.upgrade() {
+            CommunicationClient::close_client(&client);
+        }
+
+        if was_connected {
+            self.on_connection_dropped(cause);
+        }
+    }
+
+    fn on_connection_dropped(&self, cause: DisconnectCause) {
+        self.actions.clone().fail_pending(CONNECTION_LOST_ERROR);
+
+        let mut context = self.clone();
+        context.cause = cause;
+
+        // If user has a handler, give them full control (manual mode), the client is
+        // reconnecting for the user until the handler reconnects or drops the context
+        if let Some(handler) = &self.user_handler {
+            context.tracker.reconnecting(Some(context.cause.to_string()));
+            context.pending = Some(Rc::new(PendingReconnection::new(context.tracker.clone(), context.cause.to_string())));
+
+            info!("Connection dropped. Calling user's disconnection handler (manual mode).");
+            handler(context);
+            return;
+        }
+
+        // No user handler - use automatic reconnection, unless the policy does not allow any attempt
+        if context.reconnection_config.policy.next_delay(&RetryContext::new(0, Duration::ZERO, None, context.cause.clone())).is_none() {
+            info!("Connection dropped. The reconnection policy does not allow reconnecting.");
+            context.tracker.closed(Some(context.cause.to_string()));
+            return;
         }
+
+        if let Some(state) = context.state.upgrade() {
+            CommunicationClient::set_state(&state, ConnectionState::NotConnected(DisconnectionReason::Reconnecting));
+        }
+
+        context.tracker.reconnecting(Some(context.cause.to_string()));
+
+        wasm_bindgen_futures::spawn_local(async move {
+            context.reconnect_automatically().await;
+        });
     }
 
-    fn send_binary_internal(&self, data: Vec<u8>) -> Result<(), String> {
-        if self._client.is_some() {
-            let bclient = self._client.as_ref().unwrap().borrow();
-            return bclient.send_binary(data).map_err(|e| e.as_string().unwrap());
+    /// Returns `true` if the client was closed locally or dropped, so no reconnection should happen.
+    fn is_closed(&self) -> bool {
+        match self.state.upgrade() {
+            Some(state) => matches!(*state.borrow(), ConnectionState::NotConnected(DisconnectionReason::LocalClosed)),
+            None => true,
+        }
+    }
+
+    fn check_closed(&self) -> Result<(), AttemptError> {
+        if self.is_closed() {
+            Err(AttemptError::Abort(LOCALLY_CLOSED_ERROR.to_string()))
         } else {
-            return Err(format!("The client is not connected. Cannot send data"));
+            Ok(())
         }
     }
 
-    fn polling_loop(client: &Rc<RefCell<wasm_sockets::PollingClient>>, state: &Rc<RefCell<ConnectionState>>, protocol_kind: HubProtocolKind) {
-        let status = client.borrow().status();
+    /// Closes the connection after a failed reconnection, unless the client was closed locally meanwhile.
+    fn close_unless_local(&self, error: &str) {
+        if let Some(state) = self.state.upgrade() {
+            if let ConnectionState::NotConnected(DisconnectionReason::LocalClosed) = *state.borrow() {
+                return;
+            }
 
-        if status == ConnectionStatus::Connected {
-            let mstate = &mut *state.borrow_mut();
+            CommunicationClient::set_state(&state, ConnectionState::NotConnected(DisconnectionReason::RemoteClosed));
+        }
 
-            match mstate {
-                ConnectionState::Connect(connected) => {
-                    connected.complete(true);
-                },
-                ConnectionState::Handshake(handshake) => {
-                    // Drain ALL messages from the buffer in one call.
-                    // The handshake response is JSON even for MessagePack, but
-                    // ASP.NET Core may send it as a binary WebSocket frame when
-                    // MessagePack is negotiated — so we must check both.
-                    let raw_messages = client.borrow_mut().receive();
-
-                    let mut handshake_text: Option<String> = None;
-                    for msg in &raw_messages {
-                        if handshake_text.is_some() { break; }
-                        match msg {
-                            wasm_sockets::Message::Text(t) => {
-                                let stripped = MessageParser::strip_record_separator(t).to_string();
-                                if !stripped.is_empty() {
-                                    handshake_text = Some(stripped);
-                                }
-                            }
-                            wasm_sockets::Message::Binary(b) => {
-                                if let Ok(text) = std::str::from_utf8(b) {
-                                    let stripped = MessageParser::strip_record_separator(text).to_string();
-                                    if !stripped.is_empty() {
-                                        handshake_text = Some(stripped);
-                                    }
-                                }
-                            }
-                        }
-                    }
+        if let Some(client) = self.client.upgrade() {
+            CommunicationClient::close_client(&client);
+        }
+
+        self.queue.fail_all(RECONNECT_ABANDONED_ERROR);
+        self.tracker.closed(Some(error.to_string()));
+    }
+
+    /// Check if currently connected
+    pub async fn is_connected(&self) -> bool {
+        match self.state.upgrade() {
+            Some(state) => matches!(*state.borrow(), ConnectionState::Process),
+            None => false,
+        }
+    }
+
+    /// Get the endpoint URI
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Get the cause of the lost connection
+    pub fn disconnect_cause(&self) -> &DisconnectCause {
+        &self.cause
+    }
+}
+
+#[derive(Clone, Debug)]
+enum DisconnectionReason {
+    RemoteClosed,
+    LocalClosed,
+    Reconnecting,
+}
+
+#[derive(Clone)]
+enum ConnectionState {
+    Connect(ManualFutureState),
+    Handshake(ManualFutureState),
+    /// The connection is open and a connected hook sets it up.
+    Setup,
+    Process,
+    NotConnected(DisconnectionReason),
+}
+
+pub struct CommunicationClient {
+    /// The web socket of the current connection, replaced when reconnecting.
+    _client: Rc<RefCell<Option<WebSocketConnection>>>,
+    _state: Rc<RefCell<ConnectionState>>,
+    _endpoint: String,
+    _actions: UpdatableActionStorage,
+    _reconnection_config: ReconnectionConfig,
+    _disconnection_handler: Option<Rc<dyn Fn(ReconnectionContext)>>,
+    _protocol: Arc<dyn HubProtocol>,
+    _tracker: ConnectionStateTracker,
+    _connection_id: String,
+    _negotiation: NegotiationOptions,
+    _queue: OutgoingQueue<SocketMessage>,
+    _connected_hook: Option<ConnectedHook>,
+    /// Set for the client of a connected hook, which sends on the new web socket while the client is still reconnecting.
+    _direct: bool,
+}
+
+impl Clone for CommunicationClient {
+    fn clone(&self) -> Self {
+        info!("Cloning communication client {} times", Rc::strong_count(&self._state) + 1);
+
+        Self {
+            _client: self._client.clone(),
+            _state: self._state.clone(),
+            _endpoint: self._endpoint.clone(),
+            _actions: self._actions.clone(),
+            _reconnection_config: self._reconnection_config.clone(),
+            _disconnection_handler: self._disconnection_handler.clone(),
+            _protocol: self._protocol.clone(),
+            _tracker: self._tracker.clone(),
+            _connection_id: self._connection_id.clone(),
+            _negotiation: self._negotiation.clone(),
+            _queue: self._queue.clone(),
+            _connected_hook: self._connected_hook.clone(),
+            _direct: self._direct,
+        }
+    }
+}
+
+impl Drop for CommunicationClient {
+    fn drop(&mut self) {
+        self.disconnect_internal();
+    }
+}
+
+impl Communication for CommunicationClient {
+    async fn send(&mut self, message: &HubMessage) -> Result<(), String> {
+        let data = self._protocol.write_message(message)?;
+        let message = match self._protocol.transfer_format() {
+            TransferFormat::Text => SocketMessage::Text(String::from_utf8(data).map_err(|e| format!("The protocol wrote invalid text: {}", e))?),
+            TransferFormat::Binary => SocketMessage::Binary(data),
+        };
+
+        self.send_message(message).await
+    }
+
+    fn get_state_tracker(&self) -> ConnectionStateTracker {
+        self._tracker.clone()
+    }
+
+    fn get_storage(&self) -> Result<UpdatableActionStorage, String> {
+        Ok(self._actions.clone())
+    }
+
+    async fn stop(&mut self, drain_timeout: Option<Duration>) {
+        match self.close_state() {
+            ConnectionState::NotConnected(DisconnectionReason::LocalClosed) => {
+                info!("The client is already disconnected");
+                return;
+            },
+            ConnectionState::Process => {
+                info!("Stopping the connection...");
+
+                // the web socket keeps receiving the messages, so the pending invocations can still complete
+                if let Some(timeout) = drain_timeout {
+                    self.drain(timeout).await;
+                }
+            },
+            _ => info!("The client is not connected, stopping"),
+        }
+
+        self.shutdown();
+    }
+
+    fn release(&mut self) {
+        // the callbacks and streams keep clones of the client, which must not keep the connection open
+        self.close_unless_closed();
+    }
+}
+
+impl CommunicationClient {
+    pub(crate) fn create(configuration: &super::ConnectionData) -> Self {
+        info!("Creating communication client to {}", &configuration.get_endpoint());
+
+        CommunicationClient {
+            _client: Rc::new(RefCell::new(None)),
+            _state: Rc::new(RefCell::new(ConnectionState::Connect(ManualFutureState::new()))),
+            _endpoint: configuration.get_endpoint(),
+            _actions: UpdatableActionStorage::new(),
+            _reconnection_config: ReconnectionConfig::default(),
+            _disconnection_handler: None,
+            _protocol: configuration.get_protocol(),
+            _tracker: ConnectionStateTracker::new(),
+            _connection_id: configuration.get_connection_id(),
+            _negotiation: configuration.get_negotiation_options(),
+            _queue: OutgoingQueue::new(),
+            _connected_hook: None,
+            _direct: false,
+        }
+    }
+
+    pub fn set_reconnection_config(&mut self, config: ReconnectionConfig) {
+        self._reconnection_config = config;
+    }
+
+    /// Enables queueing the outgoing messages while reconnecting, up to the given number of messages.
+    pub fn set_reconnect_queue_capacity(&mut self, capacity: Option<usize>) {
+        self._queue.set_capacity(capacity);
+    }
 
-                    if let Some(ref hs_text) = handshake_text {
-                        let hs = MessageParser::parse_message::<HandshakeResponse>(hs_text);
-                        match hs {
-                            Ok(_) => {
-                                info!("Handshake completed");
-                                handshake.complete(true);
-                            }
-                            Err(e) => {
-                                error!("Handshake response parse failed: {}. Raw: {:?}", e, hs_text);
-                                handshake.complete(false);
-                            }
-                        }
+    /// Sets how the registered callbacks run when the hub invokes them.
+    pub(crate) fn set_dispatch_mode(&mut self, mode: DispatchMode) {
+        self._actions.dispatcher().set_mode(mode);
+    }
+
+    /// Sets the options the invocations, streams and callbacks serialize and deserialize their values with.
+    pub(crate) fn set_protocol_options(&mut self, options: ProtocolOptions) {
+        self._actions.set_protocol_options(options);
+    }
+
+    /// Sets the hook that sets up every new connection opened by a reconnection.
+    pub(crate) fn set_connected_hook(&mut self, hook: ConnectedHook) {
+        self._connected_hook = Some(hook);
+    }
+
+    pub fn set_disconnection_handler(&mut self, handler: impl Fn(ReconnectionContext) + 'static) {
+        self._disconnection_handler = Some(Rc::new(handler));
+    }
+
+    /// Opens the initial connection and runs the connected hook on it, before the client is marked connected.
+    ///
+    /// A connection that cannot be opened fails right away. A connection that fails to set up, because the hook
+    /// returned `ConnectedHookError::Retry` or the connection dropped meanwhile, is retried with the reconnection policy.
+    pub(crate) async fn connect_internal(&mut self) -> Result<(), String> {
+        let context = self.reconnection_context();
+
+        if let Err(e) = context.open(&self._state, &self._client, &self._endpoint).await {
+            self._tracker.closed(Some(e.to_string()));
+            self.shutdown();
+
+            return Err(e.to_string());
+        }
+
+        let mut result = context.set_up(&self._state, &self._client, self._connection_id.clone()).await;
+        let mut retry_count = 0u32;
+        let start_time = wasm_timer::Instant::now();
+
+        loop {
+            let error = match result {
+                Ok(()) => return Ok(()),
+                Err(AttemptError::Abort(e)) => e,
+                Err(AttemptError::Retry(e)) => {
+                    let retry = RetryContext::new(retry_count, start_time.elapsed(), Some(e.clone()), DisconnectCause::ConnectionLost);
+
+                    if let Some(delay) = self._reconnection_config.policy.next_delay(&retry) {
+                        warn!("The connection could not be set up, retrying in {:?}: {}", delay, e);
+                        CommunicationClient::close_client(&self._client);
+                        let _ = wasm_timer::Delay::new(delay).await;
+
+                        retry_count += 1;
+                        result = context.establish().await;
+                        continue;
                     }
+
+                    e.to_string()
                 },
-                ConnectionState::Process(storage) => {
-                    match protocol_kind {
-                        HubProtocolKind::Json => {
-                            let messages = CommunicationClient::receive_text_messages(client);
-
-                            for message in messages {
-                                let ping = MessageParser::parse_message::<Ping>(&message);
-
-                                if ping.is_ok() {
-                                    let r = storage.process_message(MessagePayload::Text(message), ping.unwrap().message_type());
-
-                                    if r.is_err() {
-                                        error!("Message could not be processed: {}", r.unwrap_err());
-                                    }
-                                } else {
-                                    error!("Message could not be parsed: {:?}", message);
-                                }
-                            }
-                        },
-                        #[cfg(feature = "messagepack")]
-                        HubProtocolKind::MessagePack => {
-                            let payloads = CommunicationClient::receive_binary_messages(client);
-
-                            for payload in payloads {
-                                match crate::protocol::msgpack::read_message_type(&payload) {
-                                    Ok(msg_type) => {
-                                        let r = storage.process_message(MessagePayload::Binary(payload), msg_type);
-                                        if r.is_err() {
-                                            error!("Error processing msgpack message: {}", r.unwrap_err());
-                                        }
-                                    },
-                                    Err(e) => error!("Cannot read msgpack message type: {}", e),
-                                }
-                            }
-                        },
-                    }
+            };
+
+            let error = format!("The connection could not be set up: {}", error);
+            self._tracker.closed(Some(error.clone()));
+            self.shutdown();
+
+            return Err(error);
+        }
+    }
+
+    fn reconnection_context(&self) -> ReconnectionContext {
+        ReconnectionContext {
+            state: Rc::downgrade(&self._state),
+            client: Rc::downgrade(&self._client),
+            endpoint: self._endpoint.clone(),
+            actions: self._actions.clone(),
+            reconnection_config: self._reconnection_config.clone(),
+            protocol: self._protocol.clone(),
+            tracker: self._tracker.clone(),
+            negotiation: self._negotiation.clone(),
+            queue: self._queue.clone(),
+            connected_hook: self._connected_hook.clone(),
+            user_handler: self._disconnection_handler.clone(),
+            cause: DisconnectCause::ConnectionLost,
+            pending: None,
+        }
+    }
+
+    /// Sets the state, unless the client has been closed locally meanwhile.
+    fn set_state(state: &Rc<RefCell<ConnectionState>>, new_state: ConnectionState) {
+        let mut guard = state.borrow_mut();
+
+        if !matches!(*guard, ConnectionState::NotConnected(DisconnectionReason::LocalClosed)) {
+            *guard = new_state;
+        }
+    }
+
+    /// Sets the locally closed state and returns the previous one. A connection attempt in progress fails.
+    fn close_state(&self) -> ConnectionState {
+        let previous = self._state.replace(ConnectionState::NotConnected(DisconnectionReason::LocalClosed));
+
+        match previous.clone() {
+            ConnectionState::Connect(mut pending) | ConnectionState::Handshake(mut pending) => pending.complete(false),
+            _ => {},
+        }
+
+        previous
+    }
+
+    /// Sends the message on the current web socket, or queues it if the client is reconnecting and queueing is enabled.
+    async fn send_message(&mut self, message: SocketMessage) -> Result<(), String> {
+        if self._direct {
+            return CommunicationClient::send_on(&self._client, message);
+        }
+
+        let queued = match &*self._state.borrow() {
+            ConnectionState::Process => return CommunicationClient::send_on(&self._client, message),
+            ConnectionState::NotConnected(reason @ (DisconnectionReason::LocalClosed | DisconnectionReason::RemoteClosed)) => {
+                return Err(format!("Client is not connected, cannot send: {:?}", reason));
+            },
+            _ if self._queue.is_enabled() => self._queue.enqueue(message)?,
+            _ => return Err("Client is reconnecting, cannot send".to_string()),
+        };
+
+        wait_for_send(queued).await
+    }
+
+    fn send_on(client: &Rc<RefCell<Option<WebSocketConnection>>>, message: SocketMessage) -> Result<(), String> {
+        match client.borrow().as_ref() {
+            Some(connection) => connection.send(message),
+            None => Err("The client is not connected. Cannot send data".to_string()),
+        }
+    }
+
+    fn close_client(client: &Rc<RefCell<Option<WebSocketConnection>>>) {
+        if let Some(connection) = client.borrow_mut().take() {
+            connection.close();
+        }
+    }
+
+    /// Reads the handshake response at the start of the message. Returns whether the hub accepted the handshake,
+    /// and the rest of the message after the response, or `None` if the message holds no response.
+    fn read_handshake(message: &SocketMessage) -> Option<(bool, Option<SocketMessage>)> {
+        // The handshake response is JSON even for MessagePack, but
+        // ASP.NET Core may send it as a binary WebSocket frame when
+        // MessagePack is negotiated, followed by MessagePack messages
+        // in the same frame — so the frame is split at the separator.
+        let bytes = message.as_bytes();
+        let end = bytes.iter().position(|byte| *byte == RECORD_SEPARATOR.as_bytes()[0]).unwrap_or(bytes.len());
+        let hs_text = std::str::from_utf8(&bytes[..end]).ok()?;
+
+        if hs_text.trim().is_empty() {
+            return None;
+        }
+
+        let rest = bytes.get(end + 1..).filter(|rest| !rest.is_empty()).map(|rest| match message {
+            SocketMessage::Text(_) => SocketMessage::Text(String::from_utf8_lossy(rest).into_owned()),
+            SocketMessage::Binary(_) => SocketMessage::Binary(rest.to_vec()),
+        });
+
+        let accepted = match MessageParser::parse_message::<HandshakeResponse>(hs_text) {
+            Ok(response) => match response.error() {
+                Some(e) => {
+                    error!("The hub rejected the handshake: {}", e);
+                    false
                 },
+                None => {
+                    info!("Handshake completed");
+                    true
+                },
+            },
+            Err(e) => {
+                error!("Handshake response parse failed: {}. Raw: {:?}", e, hs_text);
+                false
             }
-        } else if status == ConnectionStatus::Connecting {
-            info!("Hub is connecting");
-        } else if status == ConnectionStatus::Disconnected {
-            warn!("Hub is NOT connected at endpoint {}", client.borrow().url);
-        } else if status == ConnectionStatus::Error {
-            error!("Hub error at endpoint {}", client.borrow().url);
-        }
+        };
+
+        Some((accepted, rest))
     }
 
-    fn receive_text_messages(client: &Rc<RefCell<wasm_sockets::PollingClient>>) -> Vec<String> {
-        let response = client.borrow_mut().receive();
-        let mut ret = Vec::new();
+    /// Dispatches the messages of a received frame to the storage. Returns the cause if the hub closed the connection.
+    fn process_messages(message: SocketMessage, storage: &UpdatableActionStorage, protocol: &dyn HubProtocol) -> Option<DisconnectCause> {
+        let mut storage = storage.clone();
 
-        for msg in response {
-            for message in CommunicationClient::get_text_messages(msg).into_iter() {
-                if message.len() > 0 {
-                    ret.push(message);
-                }
+        for message in protocol.parse_messages(message.as_bytes()) {
+            match message {
+                Ok(HubMessage::Close { error, allow_reconnect }) => {
+                    return Some(CommunicationClient::get_close_cause(error, allow_reconnect));
+                },
+                // the browser keeps receiving, so a full stream buffer cannot hold back the connection
+                Ok(message) => if let Err(e) = storage.process_message(message) {
+                    error!("Message could not be processed: {}", e);
+                },
+                Err(e) => error!("Message could not be parsed: {}", e),
             }
         }
 
-        ret
+        None
     }
 
-    #[cfg(feature = "messagepack")]
-    fn receive_binary_messages(client: &Rc<RefCell<wasm_sockets::PollingClient>>) -> Vec<Vec<u8>> {
-        let response = client.borrow_mut().receive();
-        let mut ret = Vec::new();
+    /// Returns the cause of a close message sent by the hub.
+    fn get_close_cause(error: Option<String>, allow_reconnect: bool) -> DisconnectCause {
+        info!("The hub closed the connection: {:?}, reconnect allowed: {}", error, allow_reconnect);
+        DisconnectCause::ServerClosed { error, allow_reconnect }
+    }
 
-        for msg in response {
-            for payload in CommunicationClient::get_binary_messages(msg).into_iter() {
-                if !payload.is_empty() {
-                    ret.push(payload);
-                }
+    /// Waits until the pending invocations and streams complete, or the timeout elapses.
+    async fn drain(&self, timeout: Duration) {
+        let deadline = wasm_timer::Instant::now() + timeout;
+
+        loop {
+            let pending = self._actions.pending_count();
+
+            if pending == 0 {
+                info!("All pending invocations completed");
+                break;
             }
+
+            if wasm_timer::Instant::now() >= deadline {
+                warn!("{} invocations are still pending after {:?}, failing them", pending, timeout);
+                break;
+            }
+
+            let _ = wasm_timer::Delay::new(DRAIN_POLL_INTERVAL).await;
         }
+    }
 
-        ret
+    /// Fails everything waiting for the connection and reports the closed state.
+    fn fail_all(&self, error: &str) {
+        self._queue.fail_all(error);
+        self._actions.clone().fail_pending(error);
+        self._tracker.closed(None);
+    }
+
+    /// Closes the web socket and fails the pending invocations.
+    fn shutdown(&self) {
+        self.close_state();
+
+        info!("Closing the web socket, destroying clients...");
+        CommunicationClient::close_client(&self._client);
+        self.fail_all(CONNECTION_CLOSED_ERROR);
     }
 
     fn disconnect_internal(&mut self) {
-        if self._token.is_some() {
-            if self._client.is_some() {
-                let refc = self._client.as_ref().unwrap();
-                let count = Rc::strong_count(refc);
-
-                if count == 2 {
-                    info!("Breaking message loop, destroying clients...");
-                    let token = self._token.take().unwrap();
-    
-                    clearInterval(token);
-                } else {
-                    info!("Connection cannot be destroyed, has still {} references", count);
-                }
-            } else {
-                info!("Connection is already disconnected");
-            }
+        // the web socket handlers and the reconnection hold the state weakly, so only the clones count
+        let count = Rc::strong_count(&self._state);
+
+        if count > 1 {
+            info!("Connection cannot be destroyed, has still {} references", count - 1);
         } else {
-            info!("Message loop is presumably stopped already");
+            self.close_unless_closed();
         }
     }
-}
\ No newline at end of file
+
+    fn close_unless_closed(&self) {
+        if matches!(*self._state.borrow(), ConnectionState::NotConnected(DisconnectionReason::LocalClosed)) {
+            info!("Connection is already disconnected");
+        } else {
+            self.shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn test_handshake_followed_by_messages() {
+        let mut frame = b"{}\x1e".to_vec();
+        frame.extend_from_slice(&[0x91, 0x06]);
+
+        match CommunicationClient::read_handshake(&SocketMessage::Binary(frame)) {
+            Some((true, Some(SocketMessage::Binary(rest)))) => assert_eq!(rest, vec![0x91, 0x06]),
+            _ => panic!("the handshake should be accepted, with the rest of the frame"),
+        }
+
+        match CommunicationClient::read_handshake(&SocketMessage::Text("{}\x1e{\"type\":6}\x1e".to_string())) {
+            Some((true, Some(SocketMessage::Text(rest)))) => assert_eq!(rest, "{\"type\":6}\x1e"),
+            _ => panic!("the handshake should be accepted, with the rest of the frame"),
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_handshake_alone() {
+        assert!(matches!(CommunicationClient::read_handshake(&SocketMessage::Text("{}\x1e".to_string())), Some((true, None))));
+        assert!(matches!(CommunicationClient::read_handshake(&SocketMessage::Binary(b"{}\x1e".to_vec())), Some((true, None))));
+        assert!(CommunicationClient::read_handshake(&SocketMessage::Text("\x1e".to_string())).is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_rejected_handshake() {
+        let rejected = CommunicationClient::read_handshake(&SocketMessage::Text("{\"error\":\"unknown protocol\"}\x1e{\"type\":7}\x1e".to_string()));
+        assert!(matches!(rejected, Some((false, Some(_)))));
+
+        assert!(matches!(CommunicationClient::read_handshake(&SocketMessage::Binary(vec![0x91, 0x06, 0x1e])), Some((false, None))));
+    }
+}
@@ -1,17 +1,64 @@
+use std::sync::Arc;
+
 use crate::client::{Authentication, ConnectionConfiguration};
 use crate::execution::UpdatableActionStorage;
-use crate::protocol::hub_protocol::HubProtocolKind;
+use crate::protocol::hub_protocol::{HubMessage, HubProtocol};
 use crate::protocol::negotiate::NegotiateResponse;
+use super::ConnectionStateTracker;
+use super::reconnection::ConnectionError;
 use base64::{engine::general_purpose, Engine};
-use serde::Serialize;
 
 const WEB_SOCKET_TRANSPORT: &str = "WebSockets";
 
+/// Error used to fail pending invocations and streams when the connection drops.
+pub(crate) const CONNECTION_LOST_ERROR: &str = "The connection to the hub was lost";
+/// Error used to fail pending invocations and streams when the connection is closed locally.
+pub(crate) const CONNECTION_CLOSED_ERROR: &str = "The connection to the hub was closed";
+/// Error of a reconnection attempt when the client has been dropped meanwhile.
+pub(crate) const CLIENT_DROPPED_ERROR: &str = "Client has been dropped";
+/// Error of a reconnection attempt when the client has been stopped meanwhile.
+pub(crate) const LOCALLY_CLOSED_ERROR: &str = "Client was locally disconnected";
+/// How long the client waits for the handshake response of the hub, like the default `HandshakeTimeout` of the .NET client.
+pub(crate) const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// The parts of the connection configuration needed to negotiate a connection with the hub.
+///
+/// Kept with the connection data, so a new connection token can be negotiated when reconnecting.
+#[derive(Clone)]
+pub(crate) struct NegotiationOptions {
+    web_url: String,
+    socket_url: String,
+    protocol: Arc<dyn HubProtocol>,
+    authentication: Authentication,
+}
+
+impl NegotiationOptions {
+    pub(crate) fn from_configuration(options: &ConnectionConfiguration) -> Self {
+        NegotiationOptions {
+            web_url: options.get_web_url(),
+            socket_url: options.get_socket_url(),
+            protocol: options.get_hub_protocol(),
+            authentication: options.get_authentication(),
+        }
+    }
+}
+
+impl std::fmt::Debug for NegotiationOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the authentication is left out on purpose, so credentials do not end up in the logs
+        f.debug_struct("NegotiationOptions")
+            .field("web_url", &self.web_url)
+            .field("protocol", &self.protocol)
+            .finish()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ConnectionData {
     endpoint: String,
     connection_id: String,
-    protocol_kind: HubProtocolKind,
+    protocol: Arc<dyn HubProtocol>,
+    negotiation: NegotiationOptions,
 }
 
 impl ConnectionData {
@@ -19,23 +66,28 @@ impl ConnectionData {
         self.endpoint.clone()
     }
 
-    #[allow(dead_code)]
     pub fn get_connection_id(&self) -> String {
         self.connection_id.clone()
     }
 
-    pub fn get_protocol_kind(&self) -> HubProtocolKind {
-        self.protocol_kind
+    pub fn get_protocol(&self) -> Arc<dyn HubProtocol> {
+        self.protocol.clone()
+    }
+
+    pub(crate) fn get_negotiation_options(&self) -> NegotiationOptions {
+        self.negotiation.clone()
     }
 }
 
 pub trait Communication : Clone {
-    async fn connect(configuration: &ConnectionData) -> Result<Self, String>;
-    async fn send<T: Serialize>(&mut self, data: T) -> Result<(), String>;
-    async fn send_binary(&mut self, data: Vec<u8>) -> Result<(), String>;
+    /// Writes the message with the protocol of the connection, and sends it to the hub.
+    async fn send(&mut self, message: &HubMessage) -> Result<(), String>;
     fn get_storage(&self) -> Result<UpdatableActionStorage, String>;
-    fn get_protocol_kind(&self) -> HubProtocolKind;
-    async fn disconnect(&mut self);
+    fn get_state_tracker(&self) -> ConnectionStateTracker;
+    /// Stops the connection for every clone of the client, after waiting for the pending invocations if a timeout is given.
+    async fn stop(&mut self, drain_timeout: Option<std::time::Duration>);
+    /// Closes the connection without blocking, once the last handle of the user is released.
+    fn release(&mut self);
 }
 
 pub struct HttpClient {
@@ -43,29 +95,35 @@ pub struct HttpClient {
 }
 
 impl HttpClient {
-    pub(crate) async fn negotiate(options: ConnectionConfiguration) -> Result<ConnectionData, String> {
-        let negotiate_endpoint = format!("{}/negotiate?negotiateVersion=1", options.get_web_url());
-        let protocol_kind = options.get_protocol_kind();
-        let authentication = options.get_authentication();
-        let json_text = HttpClient::post_text(negotiate_endpoint.clone(), authentication.clone()).await;
+    pub(crate) async fn negotiate(options: &NegotiationOptions) -> Result<ConnectionData, ConnectionError> {
+        let negotiate_endpoint = format!("{}/negotiate?negotiateVersion=1", options.web_url);
+        let transfer_format = options.protocol.transfer_format();
+        let json_text = HttpClient::post_text(negotiate_endpoint.clone(), options.authentication.clone()).await;
 
         match json_text {
             Ok(text) => {
                 let negotiate = NegotiateResponse::from_json(&text)
-                    .map_err(|e| format!("Failed to parse negotiate response: {e}"))?;
+                    .map_err(|e| ConnectionError::Negotiation { status: None, message: format!("Failed to parse negotiate response: {e}") })?;
 
-                HttpClient::create_configuration(options.get_socket_url(), negotiate, protocol_kind, &authentication)
-                    .ok_or_else(|| format!(
+                HttpClient::create_configuration(options, negotiate)
+                    .ok_or_else(|| ConnectionError::Negotiation { status: None, message: format!(
                         "The negotiation concluded no matching communication protocols for {:?} transfer format",
-                        protocol_kind.transfer_format()
-                    ))
+                        transfer_format.name()
+                    ) })
             }
-            Err(e) => Err(format!("HTTP negotiation with endpoint {} failed {}", negotiate_endpoint, e)),
+            Err(ConnectionError::Negotiation { status, message }) => Err(ConnectionError::Negotiation {
+                status,
+                message: format!("HTTP negotiation with endpoint {} failed {}", negotiate_endpoint, message),
+            }),
+            Err(e) => Err(ConnectionError::Transport(format!("HTTP negotiation with endpoint {} failed {}", negotiate_endpoint, e))),
         }
     }
 
-    fn create_configuration(endpoint: String, negotiate: NegotiateResponse, protocol_kind: HubProtocolKind, authentication: &Authentication) -> Option<ConnectionData> {
-        let required_format = protocol_kind.transfer_format();
+    fn create_configuration(options: &NegotiationOptions, negotiate: NegotiateResponse) -> Option<ConnectionData> {
+        let endpoint = &options.socket_url;
+        let protocol = options.protocol.clone();
+        let authentication = &options.authentication;
+        let required_format = protocol.transfer_format().name();
         let fit = negotiate
             .available_transports()
             .iter()
@@ -90,7 +148,8 @@ impl HttpClient {
             Some(ConnectionData {
                 endpoint: full_endpoint,
                 connection_id: negotiate.connection_id().to_string(),
-                protocol_kind,
+                protocol,
+                negotiation: options.clone(),
             })
         } else {
             None
@@ -110,8 +169,8 @@ impl HttpClient {
         format!("Basic {}", &ret)
     }
 
-    pub async fn post_text(endpoint: String, authentication: Authentication) -> Result<String, String> {
-        let (s, r) = futures::channel::oneshot::channel::<Result<String, String>>();
+    pub async fn post_text(endpoint: String, authentication: Authentication) -> Result<String, ConnectionError> {
+        let (s, r) = futures::channel::oneshot::channel::<Result<String, ConnectionError>>();
 
         let mut request = ehttp::Request::post(endpoint, vec![]);
 
@@ -127,20 +186,26 @@ impl HttpClient {
 
         ehttp::fetch(request, move |result| {
             match result {
+                Ok(response) if !response.ok => {
+                    _ = s.send(Err(ConnectionError::Negotiation {
+                        status: Some(response.status),
+                        message: format!("The hub responded with {} {}", response.status, response.status_text),
+                    }));
+                }
                 Ok(response) => {
                     if let Some(text) = response.text() {
                         _ = s.send(Ok(text.to_string()));
                     } else {
-                        _ = s.send(Err("The returned response has no text body".to_string()));
+                        _ = s.send(Err(ConnectionError::Negotiation { status: None, message: "The returned response has no text body".to_string() }));
                     }
                 }
                 Err(e) => {
-                    _ = s.send(Err(format!("The call failed: {e}")));
+                    _ = s.send(Err(ConnectionError::Transport(format!("The call failed: {e}"))));
                 }
             }
         });
 
-        r.await.unwrap_or(Err("The request is cancelled.".to_string()))
+        r.await.unwrap_or(Err(ConnectionError::Transport("The request is cancelled.".to_string())))
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+use std::fmt::Display;
+
+/// The error returned by a connected hook, which decides what happens to the connection.
+///
+/// A plain `String` error converts to `Retry`, so hub calls can be awaited with `?` inside a hook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectedHookError {
+    /// The connection is dropped and the client opens another one with the next attempt of the reconnection policy.
+    Retry(String),
+    /// The connection is dropped and the client stops reconnecting.
+    Abort(String),
+}
+
+impl ConnectedHookError {
+    /// Returns the error message.
+    pub fn message(&self) -> &str {
+        match self {
+            ConnectedHookError::Retry(message) => message,
+            ConnectedHookError::Abort(message) => message,
+        }
+    }
+}
+
+impl Display for ConnectedHookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<String> for ConnectedHookError {
+    fn from(value: String) -> Self {
+        ConnectedHookError::Retry(value)
+    }
+}
+
+impl From<&str> for ConnectedHookError {
+    fn from(value: &str) -> Self {
+        ConnectedHookError::Retry(value.to_string())
+    }
+}
+
+/// Sets up a newly opened connection before the client is marked connected.
+///
+/// Called with a client that sends on the new connection, while the other senders still wait for the reconnection.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type ConnectedHook = std::sync::Arc<dyn Fn(super::CommunicationClient) -> futures::future::BoxFuture<'static, Result<(), ConnectedHookError>> + Send + Sync>;
+#[cfg(target_arch = "wasm32")]
+pub(crate) type ConnectedHook = std::sync::Arc<dyn Fn(super::CommunicationClient) -> futures::future::LocalBoxFuture<'static, Result<(), ConnectedHookError>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failing_invoke() -> Result<bool, String> {
+        Err("The hub method failed".to_string())
+    }
+
+    fn hook() -> Result<(), ConnectedHookError> {
+        failing_invoke()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_errors_retry() {
+        let error = hook().unwrap_err();
+
+        assert_eq!(error, ConnectedHookError::Retry("The hub method failed".to_string()));
+        assert_eq!(error.to_string(), "The hub method failed");
+        assert_eq!(ConnectedHookError::Abort("stop".to_string()).message(), "stop");
+    }
+}
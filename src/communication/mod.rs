@@ -1,4 +1,7 @@
 mod common;
+mod hooks;
+mod queue;
+mod state;
 pub mod reconnection;
 
 #[cfg(target_arch = "wasm32")]
@@ -7,8 +10,11 @@ mod client_wasm;
 #[cfg(not(target_arch = "wasm32"))]
 mod client_tokio;
 
-pub(crate) use common::HttpClient;
+pub(crate) use common::{HttpClient, NegotiationOptions};
 pub use common::{ConnectionData, Communication};
+pub use hooks::ConnectedHookError;
+pub use state::HubConnectionState;
+pub(crate) use state::{ConnectionStateTracker, PendingReconnection};
 
 #[cfg(target_arch = "wasm32")]
 pub use client_wasm::CommunicationClient;
@@ -16,5 +22,10 @@ pub use client_wasm::CommunicationClient;
 #[cfg(not(target_arch = "wasm32"))]
 pub use client_tokio::CommunicationClient;
 
+#[cfg(target_arch = "wasm32")]
+pub use client_wasm::ReconnectionContext;
+
 #[cfg(not(target_arch = "wasm32"))]
-pub use client_tokio::ReconnectionContext;
\ No newline at end of file
+pub use client_tokio::ReconnectionContext;
+
+pub(crate) use hooks::ConnectedHook;
\ No newline at end of file
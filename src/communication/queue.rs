@@ -0,0 +1,150 @@
+use std::{collections::VecDeque, sync::{Arc, Mutex}};
+
+use futures::channel::oneshot;
+use log::{debug, error};
+
+/// Error used to fail the queued messages when the client gives up reconnecting.
+pub(crate) const RECONNECT_ABANDONED_ERROR: &str = "The reconnection was abandoned, the queued message was not sent";
+
+type SendResult = oneshot::Sender<Result<(), String>>;
+
+struct QueueData<M> {
+    capacity: Option<usize>,
+    items: VecDeque<(M, SendResult)>,
+}
+
+/// A bounded queue of outgoing messages, buffered while the client is reconnecting.
+///
+/// The queue is disabled until a capacity is set. Every queued message has a completion,
+/// which is resolved when the message is sent after the reconnection, or failed when it is abandoned.
+pub(crate) struct OutgoingQueue<M> {
+    _data: Arc<Mutex<QueueData<M>>>,
+}
+
+impl<M> Clone for OutgoingQueue<M> {
+    fn clone(&self) -> Self {
+        Self { _data: self._data.clone() }
+    }
+}
+
+impl<M> OutgoingQueue<M> {
+    pub(crate) fn new() -> Self {
+        OutgoingQueue {
+            _data: Arc::new(Mutex::new(QueueData {
+                capacity: None,
+                items: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Enables the queue with the given capacity, or disables it with `None`.
+    pub(crate) fn set_capacity(&self, capacity: Option<usize>) {
+        if let Ok(mut data) = self._data.lock() {
+            data.capacity = capacity;
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self._data.lock().map(|data| data.capacity.is_some()).unwrap_or(false)
+    }
+
+    /// Queues the message and returns the receiver of its send result.
+    pub(crate) fn enqueue(&self, message: M) -> Result<oneshot::Receiver<Result<(), String>>, String> {
+        let mut data = self._data.lock().map_err(|_| "Cannot lock the outgoing queue".to_string())?;
+
+        match data.capacity {
+            None => Err("The outgoing queue is disabled".to_string()),
+            Some(capacity) if data.items.len() >= capacity => {
+                Err(format!("The outgoing queue is full ({} messages), the message cannot be sent while reconnecting", capacity))
+            },
+            Some(_) => {
+                let (sender, receiver) = oneshot::channel();
+
+                data.items.push_back((message, sender));
+                debug!("Message queued while reconnecting, {} messages are waiting", data.items.len());
+
+                Ok(receiver)
+            },
+        }
+    }
+
+    /// Takes all the queued messages in the order they were queued.
+    pub(crate) fn drain(&self) -> Vec<(M, SendResult)> {
+        match self._data.lock() {
+            Ok(mut data) => data.items.drain(..).collect(),
+            Err(_) => {
+                error!("Cannot lock the outgoing queue");
+                Vec::new()
+            },
+        }
+    }
+
+    /// Fails all the queued messages with the given error.
+    pub(crate) fn fail_all(&self, error: &str) {
+        let items = self.drain();
+
+        if !items.is_empty() {
+            debug!("Failing {} queued messages: {}", items.len(), error);
+        }
+
+        for (_, sender) in items {
+            let _ = sender.send(Err(error.to_string()));
+        }
+    }
+}
+
+/// Waits for the send result of a queued message.
+pub(crate) async fn wait_for_send(receiver: oneshot::Receiver<Result<(), String>>) -> Result<(), String> {
+    receiver.await.unwrap_or_else(|_| Err(RECONNECT_ABANDONED_ERROR.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use super::*;
+
+    #[test]
+    fn test_disabled_queue_rejects_messages() {
+        let queue = OutgoingQueue::<String>::new();
+
+        assert!(!queue.is_enabled());
+        assert!(queue.enqueue("message".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_queue_keeps_order_and_capacity() {
+        let queue = OutgoingQueue::<String>::new();
+        queue.set_capacity(Some(2));
+
+        let first = queue.enqueue("first".to_string()).unwrap();
+        let second = queue.enqueue("second".to_string()).unwrap();
+        let overflow = queue.enqueue("third".to_string());
+
+        assert!(overflow.unwrap_err().contains("full"));
+
+        let items = queue.drain();
+        let messages: Vec<_> = items.iter().map(|(m, _)| m.clone()).collect();
+        assert_eq!(messages, vec!["first".to_string(), "second".to_string()]);
+
+        for (_, sender) in items {
+            let _ = sender.send(Ok(()));
+        }
+
+        assert_eq!(futures::executor::block_on(wait_for_send(first)), Ok(()));
+        assert_eq!(futures::executor::block_on(wait_for_send(second)), Ok(()));
+    }
+
+    #[test]
+    fn test_fail_all_fails_queued_messages() {
+        let queue = OutgoingQueue::<String>::new();
+        queue.set_capacity(Some(10));
+
+        let receiver = queue.enqueue("message".to_string()).unwrap();
+        queue.fail_all(RECONNECT_ABANDONED_ERROR);
+
+        assert_eq!(futures::executor::block_on(wait_for_send(receiver)), Err(RECONNECT_ABANDONED_ERROR.to_string()));
+        assert!(queue.drain().is_empty());
+    }
+}
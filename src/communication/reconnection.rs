@@ -1,4 +1,6 @@
-use std::time::Duration;
+use std::{fmt::Display, sync::Mutex, time::Duration};
+
+use super::{common::CONNECTION_LOST_ERROR, ConnectedHookError};
 
 /// Defines the behavior for reconnection attempts.
 pub trait ReconnectionPolicy: crate::platform::MaybeSendSync {
@@ -7,6 +9,157 @@ pub trait ReconnectionPolicy: crate::platform::MaybeSendSync {
     fn next_retry_delay(&self, retry_count: u32, elapsed_milliseconds: u64) -> Option<Duration>;
 }
 
+/// The error of a failed connection attempt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionError {
+    /// The negotiation with the hub failed. Holds the HTTP status code if the hub rejected the request,
+    /// for example 401 when the access token expired.
+    Negotiation { status: Option<u16>, message: String },
+    /// The web socket connection could not be opened.
+    Transport(String),
+    /// The hub did not accept the handshake.
+    Handshake(String),
+    /// A connected hook failed and asked for another attempt.
+    Setup(String),
+}
+
+impl ConnectionError {
+    /// Returns the HTTP status code the hub rejected the negotiation with, if any.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ConnectionError::Negotiation { status, .. } => *status,
+            _ => None,
+        }
+    }
+}
+
+impl Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionError::Negotiation { message, .. } => f.write_str(message),
+            ConnectionError::Transport(message) => f.write_str(message),
+            ConnectionError::Handshake(message) => f.write_str(message),
+            ConnectionError::Setup(message) => f.write_str(message),
+        }
+    }
+}
+
+/// The cause of a lost connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DisconnectCause {
+    /// The connection dropped without a close message from the hub, for example because of a network failure.
+    ConnectionLost,
+    /// The hub closed the connection with a close message. Holds the error if the connection was closed
+    /// because of one, and whether the hub allows the client to reconnect.
+    ServerClosed { error: Option<String>, allow_reconnect: bool },
+}
+
+impl DisconnectCause {
+    /// Returns `false` if the hub closed the connection and asked the client not to reconnect.
+    pub fn allow_reconnect(&self) -> bool {
+        match self {
+            DisconnectCause::ConnectionLost => true,
+            DisconnectCause::ServerClosed { allow_reconnect, .. } => *allow_reconnect,
+        }
+    }
+}
+
+impl Display for DisconnectCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectCause::ConnectionLost => f.write_str(CONNECTION_LOST_ERROR),
+            DisconnectCause::ServerClosed { error: Some(error), .. } => write!(f, "The hub closed the connection: {}", error),
+            DisconnectCause::ServerClosed { error: None, .. } => f.write_str("The hub closed the connection"),
+        }
+    }
+}
+
+/// Everything a `RetryPolicy` knows about the reconnection when deciding on the next attempt.
+#[derive(Clone, Debug)]
+pub struct RetryContext {
+    /// The number of failed attempts so far.
+    pub retry_count: u32,
+    /// The time elapsed since the connection was lost.
+    pub elapsed: Duration,
+    /// The error of the previous attempt, `None` before the first attempt.
+    pub previous_error: Option<ConnectionError>,
+    /// Why the connection was lost.
+    pub cause: DisconnectCause,
+    /// `false` if the hub closed the connection and asked the client not to reconnect.
+    pub allow_reconnect: bool,
+}
+
+impl RetryContext {
+    pub(crate) fn new(retry_count: u32, elapsed: Duration, previous_error: Option<ConnectionError>, cause: DisconnectCause) -> Self {
+        Self {
+            retry_count,
+            elapsed,
+            previous_error,
+            allow_reconnect: cause.allow_reconnect(),
+            cause,
+        }
+    }
+}
+
+/// The error of a failed reconnection attempt, which decides whether the client tries again.
+pub(crate) enum AttemptError {
+    Retry(ConnectionError),
+    Abort(String),
+}
+
+impl AttemptError {
+    pub(crate) fn message(&self) -> String {
+        match self {
+            AttemptError::Retry(e) => e.to_string(),
+            AttemptError::Abort(e) => e.clone(),
+        }
+    }
+}
+
+impl From<ConnectedHookError> for AttemptError {
+    fn from(value: ConnectedHookError) -> Self {
+        match value {
+            ConnectedHookError::Retry(e) => AttemptError::Retry(ConnectionError::Setup(e)),
+            ConnectedHookError::Abort(e) => AttemptError::Abort(e),
+        }
+    }
+}
+
+/// Defines the behavior for reconnection attempts, based on the full context of the reconnection.
+///
+/// Every `ReconnectionPolicy` is also a `RetryPolicy`: it gives up when the hub does not allow reconnecting,
+/// and otherwise decides from the retry count and the elapsed time.
+///
+/// # Examples
+///
+/// ```ignore
+/// struct StopOnUnauthorized(ExponentialBackoffPolicy);
+///
+/// impl RetryPolicy for StopOnUnauthorized {
+///     fn next_delay(&self, context: &RetryContext) -> Option<Duration> {
+///         match &context.previous_error {
+///             Some(error) if error.status() == Some(401) => None,
+///             _ => RetryPolicy::next_delay(&self.0, context),
+///         }
+///     }
+/// }
+/// ```
+pub trait RetryPolicy: crate::platform::MaybeSendSync {
+    /// Returns the delay before the next reconnection attempt.
+    /// Returns `None` if no more attempts should be made.
+    fn next_delay(&self, context: &RetryContext) -> Option<Duration>;
+}
+
+impl<P: ReconnectionPolicy + ?Sized> RetryPolicy for P {
+    fn next_delay(&self, context: &RetryContext) -> Option<Duration> {
+        if !context.allow_reconnect {
+            return None;
+        }
+
+        self.next_retry_delay(context.retry_count, context.elapsed.as_millis() as u64)
+    }
+}
+
 /// A reconnection policy that never retries.
 pub struct NoReconnectPolicy;
 
@@ -118,10 +271,160 @@ impl ReconnectionPolicy for ExponentialBackoffPolicy {
     }
 }
 
+/// Returns a random delay between `min` and `max`.
+fn random_delay(min: Duration, max: Duration) -> Duration {
+    if max <= min {
+        return min;
+    }
+
+    min + (max - min).mul_f64(fastrand::f64())
+}
+
+/// Returns `base * 2^retry_count`, capped at `max_delay`.
+fn exponential_delay(base: Duration, retry_count: u32, max_delay: Duration) -> Duration {
+    let delay = base.as_secs_f64() * 2f64.powi(retry_count.min(i32::MAX as u32) as i32);
+
+    Duration::try_from_secs_f64(delay).unwrap_or(max_delay).min(max_delay)
+}
+
+/// A reconnection policy that retries with an exponential backoff and full jitter.
+///
+/// Every delay is picked randomly between zero and `base * 2^retry_count`, capped at `max_delay`,
+/// so clients that lost their connection at the same time do not reconnect in lockstep.
+pub struct FullJitterBackoffPolicy {
+    base: Duration,
+    max_delay: Duration,
+    max_attempts: Option<u32>,
+}
+
+impl FullJitterBackoffPolicy {
+    pub fn new(base: Duration, max_delay: Duration, max_attempts: Option<u32>) -> Self {
+        Self {
+            base,
+            max_delay,
+            max_attempts,
+        }
+    }
+}
+
+impl ReconnectionPolicy for FullJitterBackoffPolicy {
+    fn next_retry_delay(&self, retry_count: u32, _elapsed_milliseconds: u64) -> Option<Duration> {
+        if let Some(max) = self.max_attempts {
+            if retry_count >= max {
+                return None;
+            }
+        }
+
+        Some(random_delay(Duration::ZERO, exponential_delay(self.base, retry_count, self.max_delay)))
+    }
+}
+
+/// A reconnection policy that retries with decorrelated jitter.
+///
+/// Every delay is picked randomly between `base` and three times the previous delay, capped at `max_delay`.
+/// The delays grow like an exponential backoff, but each one depends on the previous random delay.
+pub struct DecorrelatedJitterBackoffPolicy {
+    base: Duration,
+    max_delay: Duration,
+    max_attempts: Option<u32>,
+    previous: Mutex<Duration>,
+}
+
+impl DecorrelatedJitterBackoffPolicy {
+    pub fn new(base: Duration, max_delay: Duration, max_attempts: Option<u32>) -> Self {
+        Self {
+            base,
+            max_delay,
+            max_attempts,
+            previous: Mutex::new(base),
+        }
+    }
+}
+
+impl ReconnectionPolicy for DecorrelatedJitterBackoffPolicy {
+    fn next_retry_delay(&self, retry_count: u32, _elapsed_milliseconds: u64) -> Option<Duration> {
+        if let Some(max) = self.max_attempts {
+            if retry_count >= max {
+                return None;
+            }
+        }
+
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+
+        // a new reconnection starts from the base delay again
+        if retry_count == 0 {
+            *previous = self.base;
+        }
+
+        let delay = random_delay(self.base, previous.saturating_mul(3)).min(self.max_delay);
+        *previous = delay;
+
+        Some(delay)
+    }
+}
+
+/// A reconnection policy that retries after the delays of an explicit list, then gives up.
+///
+/// The default policy uses the delays of the .NET client: 0, 2, 10 and 30 seconds.
+pub struct DelayListPolicy {
+    delays: Vec<Duration>,
+}
+
+impl DelayListPolicy {
+    pub fn new(delays: Vec<Duration>) -> Self {
+        Self { delays }
+    }
+}
+
+impl Default for DelayListPolicy {
+    fn default() -> Self {
+        Self::new(vec![
+            Duration::ZERO,
+            Duration::from_secs(2),
+            Duration::from_secs(10),
+            Duration::from_secs(30),
+        ])
+    }
+}
+
+impl ReconnectionPolicy for DelayListPolicy {
+    fn next_retry_delay(&self, retry_count: u32, _elapsed_milliseconds: u64) -> Option<Duration> {
+        self.delays.get(retry_count as usize).copied()
+    }
+}
+
+/// A reconnection policy that limits the total time spent reconnecting.
+///
+/// Wraps another policy and gives up when the next attempt would start after `max_elapsed`,
+/// measured from the moment the connection was lost.
+pub struct TimeLimitedPolicy<P: ReconnectionPolicy> {
+    policy: P,
+    max_elapsed: Duration,
+}
+
+impl<P: ReconnectionPolicy> TimeLimitedPolicy<P> {
+    pub fn new(policy: P, max_elapsed: Duration) -> Self {
+        Self { policy, max_elapsed }
+    }
+}
+
+impl<P: ReconnectionPolicy> ReconnectionPolicy for TimeLimitedPolicy<P> {
+    fn next_retry_delay(&self, retry_count: u32, elapsed_milliseconds: u64) -> Option<Duration> {
+        let delay = self.policy.next_retry_delay(retry_count, elapsed_milliseconds)?;
+        let elapsed = Duration::from_millis(elapsed_milliseconds);
+
+        if elapsed + delay > self.max_elapsed {
+            return None;
+        }
+
+        Some(delay)
+    }
+}
+
 /// Configuration for reconnection.
 #[derive(Clone)]
 pub struct ReconnectionConfig {
-    pub policy: std::sync::Arc<dyn ReconnectionPolicy>,
+    pub policy: std::sync::Arc<dyn RetryPolicy>,
 }
 
 impl Default for ReconnectionConfig {
@@ -131,3 +434,70 @@ impl Default for ReconnectionConfig {
         }
     }
 }
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_jitter_stays_within_bounds() {
+        let policy = FullJitterBackoffPolicy::new(Duration::from_millis(100), Duration::from_secs(1), Some(20));
+
+        for retry_count in 0..20 {
+            let delay = policy.next_retry_delay(retry_count, 0).unwrap();
+            let cap = Duration::from_millis(100 * 2u64.pow(retry_count)).min(Duration::from_secs(1));
+
+            assert!(delay <= cap, "{:?} exceeds {:?}", delay, cap);
+        }
+
+        assert_eq!(policy.next_retry_delay(20, 0), None);
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_stays_within_bounds() {
+        let policy = DecorrelatedJitterBackoffPolicy::new(Duration::from_millis(100), Duration::from_secs(2), None);
+        let mut previous = Duration::from_millis(100);
+
+        for retry_count in 0..50 {
+            let delay = policy.next_retry_delay(retry_count, 0).unwrap();
+
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= (previous * 3).min(Duration::from_secs(2)));
+            previous = delay;
+        }
+    }
+
+    #[test]
+    fn test_delay_list_defaults_to_dotnet_delays() {
+        let policy = DelayListPolicy::default();
+        let delays: Vec<_> = (0..5).map(|retry_count| policy.next_retry_delay(retry_count, 0)).collect();
+
+        assert_eq!(delays, vec![
+            Some(Duration::ZERO),
+            Some(Duration::from_secs(2)),
+            Some(Duration::from_secs(10)),
+            Some(Duration::from_secs(30)),
+            None,
+        ]);
+    }
+
+    #[test]
+    fn test_adapter_respects_server_close() {
+        let policy = ConstantDelayPolicy::new(Duration::from_secs(1), None);
+        let lost = RetryContext::new(2, Duration::from_secs(5), Some(ConnectionError::Transport("refused".to_string())), DisconnectCause::ConnectionLost);
+        let refused = RetryContext::new(0, Duration::ZERO, None, DisconnectCause::ServerClosed { error: Some("failed".to_string()), allow_reconnect: false });
+
+        assert_eq!(RetryPolicy::next_delay(&policy, &lost), Some(Duration::from_secs(1)));
+        assert_eq!(RetryPolicy::next_delay(&policy, &refused), None);
+    }
+
+    #[test]
+    fn test_time_limit_stops_retrying() {
+        let policy = TimeLimitedPolicy::new(ConstantDelayPolicy::new(Duration::from_secs(2), None), Duration::from_secs(10));
+
+        assert_eq!(policy.next_retry_delay(0, 0), Some(Duration::from_secs(2)));
+        assert_eq!(policy.next_retry_delay(3, 8_000), Some(Duration::from_secs(2)));
+        assert_eq!(policy.next_retry_delay(4, 8_001), None);
+    }
+}
@@ -0,0 +1,311 @@
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use log::{error, info};
+
+/// The state of the connection between the client and the hub.
+///
+/// The state can be queried with `SignalRClient::state` and observed with `SignalRClient::state_changes`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HubConnectionState {
+    /// The client is establishing the initial connection.
+    Connecting,
+    /// The client is connected and can communicate with the hub.
+    Connected,
+    /// The connection was lost and the client is trying to reconnect.
+    Reconnecting,
+    /// The connection is closed. Holds the error that caused the disconnection,
+    /// or `None` if the connection was closed locally.
+    Disconnected(Option<String>),
+}
+
+impl HubConnectionState {
+    /// Returns `true` if the client is connected to the hub.
+    pub fn is_connected(&self) -> bool {
+        matches!(self, HubConnectionState::Connected)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type StateCallback = dyn Fn(Option<String>) + Send + Sync + 'static;
+#[cfg(target_arch = "wasm32")]
+type StateCallback = dyn Fn(Option<String>) + 'static;
+
+#[cfg(not(target_arch = "wasm32"))]
+type CallbackErrorCallback = dyn Fn(String, String) + Send + Sync + 'static;
+#[cfg(target_arch = "wasm32")]
+type CallbackErrorCallback = dyn Fn(String, String) + 'static;
+
+struct StateData {
+    state: HubConnectionState,
+    subscribers: Vec<UnboundedSender<HubConnectionState>>,
+    on_reconnecting: Vec<Arc<StateCallback>>,
+    on_reconnected: Vec<Arc<StateCallback>>,
+    on_closed: Vec<Arc<StateCallback>>,
+    on_callback_error: Vec<Arc<CallbackErrorCallback>>,
+}
+
+/// Tracks the public connection state, notifies the state subscribers and calls the lifecycle callbacks.
+///
+/// The callbacks are called after the lock is released, so they can safely query the state again.
+#[derive(Clone)]
+pub(crate) struct ConnectionStateTracker {
+    _data: Arc<Mutex<StateData>>,
+}
+
+impl ConnectionStateTracker {
+    #[cfg_attr(target_arch = "wasm32", allow(clippy::arc_with_non_send_sync, reason = "the WASM client is single-threaded, the tracker is shared with the Tokio client"))]
+    pub(crate) fn new() -> Self {
+        ConnectionStateTracker {
+            _data: Arc::new(Mutex::new(StateData {
+                state: HubConnectionState::Connecting,
+                subscribers: Vec::new(),
+                on_reconnecting: Vec::new(),
+                on_reconnected: Vec::new(),
+                on_closed: Vec::new(),
+                on_callback_error: Vec::new(),
+            })),
+        }
+    }
+
+    pub(crate) fn state(&self) -> HubConnectionState {
+        match self._data.lock() {
+            Ok(data) => data.state.clone(),
+            Err(_) => {
+                error!("Cannot lock connection state");
+                HubConnectionState::Disconnected(None)
+            },
+        }
+    }
+
+    /// Returns a receiver that gets the current state first, then every later transition.
+    pub(crate) fn subscribe(&self) -> UnboundedReceiver<HubConnectionState> {
+        let (sender, receiver) = unbounded();
+
+        if let Ok(mut data) = self._data.lock() {
+            if sender.unbounded_send(data.state.clone()).is_ok() {
+                data.subscribers.push(sender);
+            }
+        } else {
+            error!("Cannot lock connection state");
+        }
+
+        receiver
+    }
+
+    pub(crate) fn add_on_reconnecting(&self, callback: Arc<StateCallback>) {
+        if let Ok(mut data) = self._data.lock() {
+            data.on_reconnecting.push(callback);
+        }
+    }
+
+    pub(crate) fn add_on_reconnected(&self, callback: Arc<StateCallback>) {
+        if let Ok(mut data) = self._data.lock() {
+            data.on_reconnected.push(callback);
+        }
+    }
+
+    pub(crate) fn add_on_closed(&self, callback: Arc<StateCallback>) {
+        if let Ok(mut data) = self._data.lock() {
+            data.on_closed.push(callback);
+        }
+    }
+
+    pub(crate) fn add_on_callback_error(&self, callback: Arc<CallbackErrorCallback>) {
+        if let Ok(mut data) = self._data.lock() {
+            data.on_callback_error.push(callback);
+        }
+    }
+
+    /// A registered callback failed to run. Calls the callback error hooks with the target and the error.
+    pub(crate) fn callback_failed(&self, target: &str, error: &str) {
+        error!("The callback of {} failed: {}", target, error);
+
+        let callbacks = match self._data.lock() {
+            Ok(data) => data.on_callback_error.clone(),
+            Err(_) => return,
+        };
+
+        for callback in callbacks {
+            callback(target.to_string(), error.to_string());
+        }
+    }
+
+    /// The connection is established. Calls the reconnected callbacks if the client was reconnecting.
+    pub(crate) fn connected(&self, connection_id: Option<String>) {
+        if let Some(previous) = self.transition(HubConnectionState::Connected) {
+            if previous == HubConnectionState::Reconnecting {
+                info!("Reconnected to the hub");
+                self.notify(|data| data.on_reconnected.clone(), connection_id);
+            }
+        }
+    }
+
+    /// The connection is lost and the client starts reconnecting.
+    pub(crate) fn reconnecting(&self, error: Option<String>) {
+        if self.transition(HubConnectionState::Reconnecting).is_some() {
+            self.notify(|data| data.on_reconnecting.clone(), error);
+        }
+    }
+
+    /// The connection is closed, either locally (`None`) or because of the given error.
+    pub(crate) fn closed(&self, error: Option<String>) {
+        if self.transition(HubConnectionState::Disconnected(error.clone())).is_some() {
+            self.notify(|data| data.on_closed.clone(), error);
+        }
+    }
+
+    /// Sets the new state and returns the previous one, or `None` if nothing has changed.
+    ///
+    /// A closed connection stays closed with its original error, only a new connection can change it.
+    fn transition(&self, state: HubConnectionState) -> Option<HubConnectionState> {
+        let Ok(mut data) = self._data.lock() else {
+            error!("Cannot lock connection state");
+            return None;
+        };
+
+        let same_kind = std::mem::discriminant(&data.state) == std::mem::discriminant(&state);
+
+        if same_kind {
+            return None;
+        }
+
+        info!("Connection state changes from {:?} to {:?}", data.state, state);
+
+        let previous = std::mem::replace(&mut data.state, state.clone());
+        data.subscribers.retain(|s| s.unbounded_send(state.clone()).is_ok());
+
+        Some(previous)
+    }
+
+    fn notify(&self, callbacks: impl FnOnce(&StateData) -> Vec<Arc<StateCallback>>, argument: Option<String>) {
+        let callbacks = match self._data.lock() {
+            Ok(data) => callbacks(&data),
+            Err(_) => return,
+        };
+
+        for callback in callbacks {
+            callback(argument.clone());
+        }
+    }
+}
+
+/// Closes the connection when the disconnection handler releases its `ReconnectionHandler` without reconnecting.
+///
+/// The client is reconnecting while the handler may still reconnect, so the closed callbacks are called
+/// only when the handler gave up, and not before it gets a chance to reconnect.
+pub(crate) struct PendingReconnection {
+    tracker: ConnectionStateTracker,
+    error: String,
+}
+
+impl PendingReconnection {
+    pub(crate) fn new(tracker: ConnectionStateTracker, error: String) -> Self {
+        PendingReconnection { tracker, error }
+    }
+}
+
+impl Drop for PendingReconnection {
+    fn drop(&mut self) {
+        if self.tracker.state() == HubConnectionState::Reconnecting {
+            info!("The disconnection handler did not reconnect");
+            self.tracker.closed(Some(self.error.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[test]
+    fn test_state_transitions_are_published() {
+        let tracker = ConnectionStateTracker::new();
+        let changes = tracker.subscribe();
+
+        tracker.connected(Some("first".to_string()));
+        tracker.reconnecting(Some("lost".to_string()));
+        tracker.reconnecting(Some("lost again".to_string()));
+        tracker.connected(Some("second".to_string()));
+        tracker.closed(None);
+
+        drop(tracker);
+
+        let states: Vec<_> = futures::executor::block_on(changes.collect());
+
+        assert_eq!(states, vec![
+            HubConnectionState::Connecting,
+            HubConnectionState::Connected,
+            HubConnectionState::Reconnecting,
+            HubConnectionState::Connected,
+            HubConnectionState::Disconnected(None),
+        ]);
+    }
+
+    #[test]
+    fn test_lifecycle_callbacks() {
+        let tracker = ConnectionStateTracker::new();
+        let reconnecting = Arc::new(AtomicUsize::new(0));
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        let reconnected = Arc::new(Mutex::new(Vec::new()));
+
+        let counter = reconnecting.clone();
+        tracker.add_on_reconnecting(Arc::new(move |_| { counter.fetch_add(1, Ordering::SeqCst); }));
+        let ids = reconnected.clone();
+        tracker.add_on_reconnected(Arc::new(move |id| ids.lock().unwrap().push(id)));
+        let errors = closed.clone();
+        tracker.add_on_closed(Arc::new(move |error| errors.lock().unwrap().push(error)));
+
+        tracker.connected(Some("first".to_string()));
+        tracker.reconnecting(Some("lost".to_string()));
+        tracker.connected(Some("second".to_string()));
+        tracker.closed(Some("failed".to_string()));
+        tracker.closed(None);
+
+        assert_eq!(reconnecting.load(Ordering::SeqCst), 1);
+        assert_eq!(*reconnected.lock().unwrap(), vec![Some("second".to_string())]);
+        assert_eq!(*closed.lock().unwrap(), vec![Some("failed".to_string())]);
+        assert_eq!(tracker.state(), HubConnectionState::Disconnected(Some("failed".to_string())));
+    }
+
+    #[test]
+    fn test_pending_reconnection_closes_when_abandoned() {
+        let tracker = ConnectionStateTracker::new();
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        let errors = closed.clone();
+        tracker.add_on_closed(Arc::new(move |error| errors.lock().unwrap().push(error)));
+
+        tracker.connected(Some("first".to_string()));
+        tracker.reconnecting(Some("lost".to_string()));
+        let reconnected = PendingReconnection::new(tracker.clone(), "lost".to_string());
+        tracker.connected(Some("second".to_string()));
+        drop(reconnected);
+
+        assert!(closed.lock().unwrap().is_empty());
+
+        tracker.reconnecting(Some("lost again".to_string()));
+        drop(PendingReconnection::new(tracker.clone(), "lost again".to_string()));
+
+        assert_eq!(*closed.lock().unwrap(), vec![Some("lost again".to_string())]);
+        assert_eq!(tracker.state(), HubConnectionState::Disconnected(Some("lost again".to_string())));
+    }
+
+    #[test]
+    fn test_callback_error_hooks() {
+        let tracker = ConnectionStateTracker::new();
+        let failures = Arc::new(Mutex::new(Vec::new()));
+
+        tracker.callback_failed("ignored", "no hooks yet");
+
+        let items = failures.clone();
+        tracker.add_on_callback_error(Arc::new(move |target, error| items.lock().unwrap().push((target, error))));
+        tracker.callback_failed("callback1", "panicked");
+
+        assert_eq!(*failures.lock().unwrap(), vec![("callback1".to_string(), "panicked".to_string())]);
+    }
+}
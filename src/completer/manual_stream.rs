@@ -1,15 +1,22 @@
-use std::{collections::VecDeque, pin::Pin, sync::{Arc, Mutex}, task::{Context, Poll, Waker}};
+use std::{collections::VecDeque, future::Future, pin::Pin, sync::{Arc, Mutex}, task::{Context, Poll, Waker}};
 
 use futures::Stream;
 
+struct SpaceData {
+    length: usize,
+    released: bool,
+    waker: Option<Waker>,
+}
+
 struct ManualStreamState<T> {
     queue: Arc<Mutex<VecDeque<Option<T>>>>,
     waker: Arc<Mutex<Option<Waker>>>,
+    space: Arc<Mutex<SpaceData>>,
 }
 
 impl<T> Clone for ManualStreamState<T> {
     fn clone(&self) -> Self {
-        Self { queue: self.queue.clone(), waker: self.waker.clone() }
+        Self { queue: self.queue.clone(), waker: self.waker.clone(), space: self.space.clone() }
     }
 }
 
@@ -18,12 +25,14 @@ impl<T> ManualStreamState<T> {
         ManualStreamState {
             queue: Arc::new(Mutex::new(VecDeque::new())),
             waker: Arc::new(Mutex::new(None)),
+            space: Arc::new(Mutex::new(SpaceData { length: 0, released: false, waker: None })),
         }
     }
 
     fn push(&self, item: T) {
         let mut queue = self.queue.lock().unwrap();
         queue.push_back(Some(item));
+        self.space.lock().unwrap().length += 1;
         if let Some(waker) = self.waker.lock().unwrap().take() {
             // debug!("Waking stream...");
             waker.wake();
@@ -37,6 +46,28 @@ impl<T> ManualStreamState<T> {
             waker.wake();
         }
     }
+
+    fn pop_oldest(&self) -> Option<T> {
+        let mut queue = self.queue.lock().unwrap();
+
+        match queue.front() {
+            Some(Some(_)) => {
+                let item = queue.pop_front().flatten();
+                self.taken();
+                item
+            },
+            _ => None,
+        }
+    }
+
+    /// An item left the queue, so a producer waiting for space can go on.
+    fn taken(&self) {
+        let mut space = self.space.lock().unwrap();
+        space.length = space.length.saturating_sub(1);
+        if let Some(waker) = space.waker.take() {
+            waker.wake();
+        }
+    }
 }
 
 
@@ -56,6 +87,16 @@ impl<T> ManualStream<T> {
     }
 }
 
+impl<T> Drop for ManualStream<T> {
+    fn drop(&mut self) {
+        let mut space = self.state.space.lock().unwrap();
+        space.released = true;
+        if let Some(waker) = space.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
 pub struct ManualStreamCompleter<T> {
     state: ManualStreamState<T>,
 }
@@ -68,6 +109,29 @@ impl<T> ManualStreamCompleter<T> {
     pub fn close(&self) {
         self.state.close();
     }
+
+    /// Returns the number of items waiting to be read.
+    pub fn len(&self) -> usize {
+        self.state.space.lock().unwrap().length
+    }
+
+    /// Returns `true` if no item is waiting to be read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes the oldest item that was not read yet.
+    pub fn pop_oldest(&self) -> Option<T> {
+        self.state.pop_oldest()
+    }
+
+    /// Returns a future that completes when fewer than `capacity` items wait to be read, or the stream is dropped.
+    pub(crate) fn space(&self, capacity: usize) -> StreamSpace {
+        StreamSpace {
+            space: self.state.space.clone(),
+            capacity,
+        }
+    }
 }
 
 impl<T> Stream for ManualStream<T> {
@@ -79,8 +143,10 @@ impl<T> Stream for ManualStream<T> {
         if let Some(item) = queue.pop_front() {
             // debug!("Item popped...");
             match item {
-                Some(value) => { 
+                Some(value) => {
                     // debug!("Poll Ready with value");
+                    drop(queue);
+                    self.state.taken();
                     Poll::Ready(Some(value))
                 },
                 None => {
@@ -96,3 +162,75 @@ impl<T> Stream for ManualStream<T> {
         }
     }
 }
+
+/// Waits until a bounded stream has room for more items, so the receiver stops reading the connection meanwhile.
+pub(crate) struct StreamSpace {
+    space: Arc<Mutex<SpaceData>>,
+    capacity: usize,
+}
+
+impl StreamSpace {
+    /// Returns `true` if the stream is still read, but no more items fit into it.
+    pub(crate) fn is_full(&self) -> bool {
+        let space = self.space.lock().unwrap();
+
+        !space.released && space.length >= self.capacity
+    }
+}
+
+impl Future for StreamSpace {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut space = self.space.lock().unwrap();
+
+        if space.released || space.length < self.capacity {
+            Poll::Ready(())
+        } else {
+            space.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+    use futures::{FutureExt, StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn test_space_follows_the_reader() {
+        let (mut stream, completer) = ManualStream::create();
+        let mut space = completer.space(2);
+
+        completer.push(1);
+        completer.push(2);
+
+        assert!(space.is_full());
+        assert!((&mut space).now_or_never().is_none());
+
+        assert_eq!(futures::executor::block_on(stream.next()), Some(1));
+
+        assert!(!space.is_full());
+        assert!((&mut space).now_or_never().is_some());
+    }
+
+    #[test]
+    fn test_pop_oldest_and_release() {
+        let (stream, completer) = ManualStream::create();
+        let space = completer.space(1);
+
+        completer.push(1);
+        completer.push(2);
+
+        assert_eq!(completer.pop_oldest(), Some(1));
+        assert_eq!(completer.len(), 1);
+        assert!(space.is_full());
+
+        drop(stream);
+
+        assert!(!space.is_full());
+    }
+}
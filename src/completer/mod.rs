@@ -4,4 +4,5 @@ mod completed_future;
 
 pub use manual_future::{ManualFuture, ManualFutureCompleter};
 pub use manual_stream::{ManualStream, ManualStreamCompleter};
+pub(crate) use manual_stream::StreamSpace;
 pub use completed_future::CompletedFuture;
\ No newline at end of file
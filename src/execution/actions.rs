@@ -1,11 +1,20 @@
+use crate::completer::StreamSpace;
 use crate::platform::MaybeSend;
-use crate::protocol::hub_protocol::MessagePayload;
-use crate::protocol::negotiate::MessageType;
+use crate::protocol::hub_protocol::HubMessage;
 
 pub(crate) trait UpdatableAction: MaybeSend {
-    fn update_with(&mut self, message: &MessagePayload, message_type: MessageType);
+    fn update_with(&mut self, message: HubMessage);
     #[allow(dead_code)]
     fn is_completed(&self) -> bool;
+    /// Returns `true` while the action is waiting for the hub to answer an invocation or a stream.
+    /// Callbacks are never pending.
+    fn is_pending(&self) -> bool;
+    /// Returns the space to wait for before the connection is read again, if the buffer of a stream is full.
+    fn backpressure(&self) -> Option<StreamSpace> {
+        None
+    }
+    /// Resolves a pending action with the given error, e.g. when the connection is lost.
+    fn fail(&mut self, error: &str);
     #[allow(dead_code)]
     fn dispose(self);
 }
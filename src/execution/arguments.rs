@@ -1,17 +1,23 @@
 use log::error;
 use serde::Serialize;
 
-use crate::protocol::invoke::Invocation;
+use crate::protocol::{hub_protocol::ProtocolOptions, invoke::Invocation};
+
+use super::StreamBuffer;
 
 /// Lets the arguments to be configured for a method on the Hub
 pub struct ArgumentConfiguration {
     invocation: Option<Invocation>,
+    buffer: StreamBuffer,
+    options: ProtocolOptions,
 }
 
 impl ArgumentConfiguration {
-    pub(crate) fn new(invocation: Invocation) -> Self {
+    pub(crate) fn new(invocation: Invocation, options: ProtocolOptions) -> Self {
         Self {  
-            invocation: Some(invocation)
+            invocation: Some(invocation),
+            buffer: StreamBuffer::default(),
+            options,
         }
     }
 
@@ -51,8 +57,8 @@ impl ArgumentConfiguration {
     /// }
     /// ```    
     pub fn argument<T: Serialize>(&mut self, value: T) -> &mut ArgumentConfiguration {
-        if self.invocation.is_some() {
-            let succ = self.invocation.as_mut().unwrap().with_argument(value);
+        if let Some(invocation) = self.invocation.as_mut() {
+            let succ = invocation.with_argument(value, &self.options);
 
             if succ.is_err() {
                 error!("Argument could not be put into invocation data.");
@@ -62,6 +68,35 @@ impl ArgumentConfiguration {
         self
     }
 
+    /// Sets how many items of the stream are buffered until they are read, and what happens when the buffer is full.
+    ///
+    /// Used only by the `enumerate` calls, and ignored by `invoke` and `send`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - A `StreamBuffer` specifying the capacity and the overflow behavior of the stream.
+    ///
+    /// # Returns
+    ///
+    /// * `&mut ArgumentConfiguration` - Returns a mutable reference to the updated argument configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut stream = client.enumerate_with_args::<TestEntity, _>("MillionEntities".to_string(), |c| {
+    ///     c.stream_buffer(StreamBuffer::DropOldest { capacity: 100 });
+    /// }).await;
+    /// ```
+    pub fn stream_buffer(&mut self, buffer: StreamBuffer) -> &mut ArgumentConfiguration {
+        self.buffer = buffer;
+
+        self
+    }
+
+    pub(crate) fn get_stream_buffer(&self) -> StreamBuffer {
+        self.buffer
+    }
+
     pub(crate) fn build_invocation(mut self) -> Invocation {
         if self.invocation.is_some() {
             return self.invocation.take().unwrap();
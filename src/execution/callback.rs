@@ -1,68 +1,80 @@
-use crate::{client::SignalRClient, protocol::{hub_protocol::MessagePayload, invoke::Invocation, negotiate::MessageType}, InvocationContext};
-use crate::protocol::messages::MessageParser;
-use super::actions::UpdatableAction;
+use std::{any::Any, panic::{catch_unwind, AssertUnwindSafe}, sync::Arc};
+
+use crate::{client::SignalRClient, protocol::{hub_protocol::{CompletionResult, HubMessage}, invoke::Invocation}, InvocationContext};
+use super::{actions::UpdatableAction, dispatcher::CallbackDispatcher};
 
 #[cfg(not(target_arch = "wasm32"))]
-type CallbackFn = dyn Fn(InvocationContext) + Send + 'static;
+type CallbackFn = dyn Fn(InvocationContext) + Send + Sync + 'static;
 #[cfg(target_arch = "wasm32")]
 type CallbackFn = dyn Fn(InvocationContext) + 'static;
 
 pub(crate) struct CallbackAction {
-    #[allow(dead_code)]
     target: String,
-    callback: Box<CallbackFn>,
+    callback: Arc<CallbackFn>,
     client: SignalRClient,
+    dispatcher: CallbackDispatcher,
 }
 
 impl CallbackAction {
-    pub(crate) fn create(target: String, callback: impl Fn(InvocationContext) + crate::platform::MaybeSend + 'static, client: SignalRClient) -> CallbackAction {
+    pub(crate) fn create(target: String, callback: impl Fn(InvocationContext) + crate::platform::MaybeSendSync + 'static, client: SignalRClient, dispatcher: CallbackDispatcher) -> CallbackAction {
         CallbackAction {
-            target: target,
-            callback: Box::new(callback),
-            client: client
+            target,
+            callback: Arc::new(callback),
+            client,
+            dispatcher,
         }
     }
 }
 
-impl UpdatableAction for CallbackAction {
-    fn update_with(&mut self, message: &MessagePayload, message_type: MessageType) {
-        match message_type {
-            MessageType::Invocation => {
-                match message {
-                    MessagePayload::Text(s) => {
-                        let invocation: Invocation = MessageParser::parse_message(s).unwrap();
-                        let context = InvocationContext::create(self.client.clone(), invocation);
-                        (self.callback)(context);
-                    },
-                    #[cfg(feature = "messagepack")]
-                    MessagePayload::Binary(data) => {
-                        match crate::protocol::msgpack::parse_msgpack_message(data) {
-                            Ok(items) => {
-                                match crate::protocol::msgpack::parse_invocation(&items) {
-                                    Ok(parsed) => {
-                                        // Convert rmpv arguments to serde_json::Value for InvocationContext compatibility
-                                        let json_args: Vec<serde_json::Value> = parsed.arguments.iter()
-                                            .map(crate::protocol::msgpack::msgpack_value_to_json)
-                                            .collect();
-
-                                        let mut invocation = Invocation::create_single(parsed.target);
-                                        invocation.arguments = Some(json_args);
-                                        if let Some(id) = parsed.invocation_id {
-                                            invocation.with_invocation_id(id);
-                                        }
-
-                                        let context = InvocationContext::create(self.client.clone(), invocation);
-                                        (self.callback)(context);
-                                    },
-                                    Err(e) => log::error!("Cannot parse msgpack invocation: {}", e),
-                                }
-                            },
-                            Err(e) => log::error!("Cannot parse msgpack message: {}", e),
-                        }
-                    },
+/// Runs the callback, and isolates its panic from the worker of the dispatcher.
+///
+/// A panic is reported to the callback error hooks, and to the hub if it awaits a result.
+/// On wasm32 the panics abort, so `catch_unwind` cannot catch them and a panicking callback stops the client.
+fn run_callback(callback: &CallbackFn, client: SignalRClient, target: String, invocation: Invocation) {
+    let invocation_id = invocation.get_invocation_id();
+    let context = InvocationContext::create(client.clone(), invocation);
+
+    if let Err(panic) = catch_unwind(AssertUnwindSafe(|| callback(context))) {
+        let error = format!("The callback panicked: {}", panic_message(&panic));
+        client.callback_failed(&target, &error);
+
+        if let Some(invocation_id) = invocation_id {
+            let mut client = client;
+
+            InvocationContext::spawn(async move {
+                let completion = HubMessage::Completion { invocation_id, result: CompletionResult::Error(error) };
+
+                if let Err(e) = client.send_message(completion).await {
+                    log::error!("Cannot send the error of {}: {}", target, e);
                 }
+            });
+        }
+    }
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl UpdatableAction for CallbackAction {
+    fn update_with(&mut self, message: HubMessage) {
+        match message {
+            HubMessage::Invocation { invocation_id, target, arguments, .. } => {
+                // The arguments keep their encoding, and are deserialized only when the callback asks for them
+                let invocation = Invocation::received(invocation_id, target, arguments);
+                let callback = self.callback.clone();
+                let client = self.client.clone();
+                let target = self.target.clone();
+
+                self.dispatcher.dispatch(&self.target, Box::new(move || run_callback(callback.as_ref(), client, target, invocation)));
             },
-            _ => log::error!("Callbacks accept only invocation data, got {:?}", message_type),
+            other => log::error!("Callbacks accept only invocation data, got {:?}", other.message_type()),
         }
     }
 
@@ -70,9 +82,68 @@ impl UpdatableAction for CallbackAction {
         false
     }
 
+    fn is_pending(&self) -> bool {
+        false
+    }
+
+    fn fail(&mut self, _error: &str) {
+    }
+
     fn dispose(self) {
         drop(self.callback);
         drop(self.client);
         drop(self.target);
+        drop(self.dispatcher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let text = catch_unwind(|| panic!("static text")).unwrap_err();
+        let formatted = catch_unwind(|| panic!("formatted {}", 42)).unwrap_err();
+        let other = catch_unwind(|| std::panic::panic_any(42)).unwrap_err();
+
+        assert_eq!(panic_message(&text), "static text");
+        assert_eq!(panic_message(&formatted), "formatted 42");
+        assert_eq!(panic_message(&other), "unknown panic");
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_panicking_callback_keeps_the_client_processing() {
+        use std::sync::{atomic::{AtomicUsize, Ordering}, Mutex};
+        use crate::execution::{Storage, UpdatableActionStorage};
+
+        let mut storage = UpdatableActionStorage::new();
+        let client = SignalRClient::from_storage(storage.clone());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+
+        let failures = errors.clone();
+        client.on_callback_error(move |target, error| failures.lock().unwrap().push((target, error)));
+
+        let counter = calls.clone();
+        storage.add_callback("callback1".to_string(), move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("first call");
+            }
+        }, client);
+
+        for _ in 0..2 {
+            let invocation = HubMessage::Invocation { invocation_id: None, target: "callback1".to_string(), arguments: Vec::new(), stream_ids: Vec::new() };
+            assert!(storage.process_message(invocation).unwrap().is_none());
+        }
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(2);
+        while calls.load(Ordering::SeqCst) < 2 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(*errors.lock().unwrap(), vec![("callback1".to_string(), "The callback panicked: first call".to_string())]);
     }
 }
@@ -0,0 +1,234 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use futures::{channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender}, StreamExt};
+use log::error;
+
+use crate::InvocationContext;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type DispatchJob = Box<dyn FnOnce() + Send + 'static>;
+#[cfg(target_arch = "wasm32")]
+pub(crate) type DispatchJob = Box<dyn FnOnce() + 'static>;
+
+/// Decides how the callbacks registered with `SignalRClient::register` run.
+///
+/// The callbacks never run on the receiver of the connection, so a slow callback does not delay
+/// the results of the invocations and the items of the streams, and a callback can register or unregister callbacks.
+/// On native targets the callbacks run on the blocking threads of Tokio, so a callback that blocks
+/// does not stall the runtime, not even a `current_thread` one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DispatchMode {
+    /// The invocations run one at a time, in the order they arrived on the connection, like in the .NET client.
+    #[default]
+    Sequential,
+    /// The invocations of the same target run one at a time in order, while different targets run concurrently.
+    PerTarget,
+    /// Every invocation runs as soon as it arrives, with at most `max_parallelism` invocations running at the same time.
+    Concurrent {
+        max_parallelism: usize,
+    },
+}
+
+/// Hands the invocations of the hub to the workers that run the callbacks, according to the `DispatchMode`.
+///
+/// A worker is started for each queue when the first invocation arrives, and stops when the dispatcher is dropped
+/// or its mode is changed.
+#[derive(Clone, Default)]
+pub(crate) struct CallbackDispatcher {
+    _mode: Arc<Mutex<DispatchMode>>,
+    _queues: Arc<Mutex<HashMap<String, UnboundedSender<DispatchJob>>>>,
+}
+
+impl CallbackDispatcher {
+    /// Changes the mode of the dispatcher.
+    ///
+    /// The running workers finish the jobs already queued and stop, and the next invocations start new workers with the new mode.
+    pub(crate) fn set_mode(&self, mode: DispatchMode) {
+        let Ok(mut queues) = self._queues.lock() else {
+            error!("Cannot lock the callback queues");
+            return;
+        };
+
+        if let Ok(mut current) = self._mode.lock() {
+            if *current != mode {
+                *current = mode;
+                queues.clear();
+            }
+        }
+    }
+
+    pub(crate) fn mode(&self) -> DispatchMode {
+        self._mode.lock().map(|mode| *mode).unwrap_or_default()
+    }
+
+    /// Queues the job of an invocation of the given target.
+    pub(crate) fn dispatch(&self, target: &str, job: DispatchJob) {
+        let Ok(mut queues) = self._queues.lock() else {
+            error!("Cannot lock the callback queues");
+            return;
+        };
+
+        let mode = self.mode();
+        let key = match mode {
+            DispatchMode::PerTarget => target.to_string(),
+            DispatchMode::Sequential | DispatchMode::Concurrent { .. } => String::new(),
+        };
+
+        let sender = queues.entry(key.clone()).or_insert_with(|| CallbackDispatcher::start_worker(mode));
+
+        if let Err(e) = sender.unbounded_send(job) {
+            // the worker is gone, e.g. its runtime was shut down, so the job gets a new one
+            let sender = CallbackDispatcher::start_worker(mode);
+            let _ = sender.unbounded_send(e.into_inner());
+            queues.insert(key, sender);
+        }
+    }
+
+    fn start_worker(mode: DispatchMode) -> UnboundedSender<DispatchJob> {
+        let (sender, receiver) = unbounded::<DispatchJob>();
+
+        match mode {
+            DispatchMode::Sequential | DispatchMode::PerTarget => {
+                InvocationContext::spawn(CallbackDispatcher::run_in_order(receiver));
+            },
+            DispatchMode::Concurrent { max_parallelism } => {
+                InvocationContext::spawn(CallbackDispatcher::run_concurrently(receiver, max_parallelism.max(1)));
+            },
+        }
+
+        sender
+    }
+
+    async fn run_in_order(mut receiver: UnboundedReceiver<DispatchJob>) {
+        while let Some(job) = receiver.next().await {
+            CallbackDispatcher::run(job).await;
+        }
+    }
+
+    async fn run_concurrently(receiver: UnboundedReceiver<DispatchJob>, max_parallelism: usize) {
+        receiver.for_each_concurrent(max_parallelism, CallbackDispatcher::run).await;
+    }
+
+    /// Runs a job on a blocking thread, or inline on wasm, where there are no other threads.
+    async fn run(job: DispatchJob) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = tokio::task::spawn_blocking(job).await {
+            error!("The callback worker failed: {}", e);
+        }
+        #[cfg(target_arch = "wasm32")]
+        job();
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+    use std::{sync::atomic::{AtomicUsize, Ordering}, time::Duration};
+
+    use super::*;
+
+    fn dispatch_all(dispatcher: &CallbackDispatcher, targets: &[&str], delay: Duration) -> Arc<Mutex<Vec<String>>> {
+        let finished = Arc::new(Mutex::new(Vec::new()));
+
+        for (index, target) in targets.iter().enumerate() {
+            let finished = finished.clone();
+            let item = format!("{}{}", target, index);
+            let pause = if index == 0 { delay } else { Duration::ZERO };
+
+            dispatcher.dispatch(target, Box::new(move || {
+                std::thread::sleep(pause);
+                finished.lock().unwrap().push(item);
+            }));
+        }
+
+        finished
+    }
+
+    /// Waits until the condition holds, for at most two seconds.
+    async fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+
+        while !condition() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sequential_keeps_the_order() {
+        let dispatcher = CallbackDispatcher::default();
+        let finished = dispatch_all(&dispatcher, &["a", "b", "a"], Duration::from_millis(50));
+
+        wait_until(|| finished.lock().unwrap().len() == 3).await;
+
+        assert_eq!(*finished.lock().unwrap(), vec!["a0", "b1", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_per_target_orders_each_target() {
+        let dispatcher = CallbackDispatcher::default();
+        dispatcher.set_mode(DispatchMode::PerTarget);
+        let finished = dispatch_all(&dispatcher, &["a", "b", "a"], Duration::from_millis(50));
+
+        wait_until(|| finished.lock().unwrap().len() == 3).await;
+
+        assert_eq!(*finished.lock().unwrap(), vec!["b1", "a0", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_set_mode_restarts_the_workers() {
+        let dispatcher = CallbackDispatcher::default();
+        let first = dispatch_all(&dispatcher, &["a"], Duration::ZERO);
+
+        wait_until(|| first.lock().unwrap().len() == 1).await;
+
+        dispatcher.set_mode(DispatchMode::PerTarget);
+        let finished = dispatch_all(&dispatcher, &["a", "b", "a"], Duration::from_millis(50));
+
+        wait_until(|| finished.lock().unwrap().len() == 3).await;
+
+        assert_eq!(*finished.lock().unwrap(), vec!["b1", "a0", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_job_does_not_stall_the_runtime() {
+        let dispatcher = CallbackDispatcher::default();
+        let (release, released) = std::sync::mpsc::channel::<()>();
+
+        dispatcher.dispatch("a", Box::new(move || {
+            let _ = released.recv_timeout(Duration::from_secs(2));
+        }));
+
+        let started = std::time::Instant::now();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        release.send(()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_limits_parallelism() {
+        let dispatcher = CallbackDispatcher::default();
+        dispatcher.set_mode(DispatchMode::Concurrent { max_parallelism: 2 });
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..6 {
+            let (running, peak, done) = (running.clone(), peak.clone(), done.clone());
+
+            dispatcher.dispatch("a", Box::new(move || {
+                let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(current, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+
+        wait_until(|| done.load(Ordering::SeqCst) == 6).await;
+
+        assert_eq!(done.load(Ordering::SeqCst), 6);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+}
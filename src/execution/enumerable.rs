@@ -1,27 +1,159 @@
-use log::error;
+use std::{pin::Pin, sync::{atomic::{AtomicBool, Ordering}, Arc}, task::{Context, Poll}};
+
+use futures::Stream;
+use log::{error, warn};
 use serde::de::DeserializeOwned;
 
-use crate::{completer::{ManualStream, ManualStreamCompleter}, protocol::{hub_protocol::MessagePayload, messages::MessageParser, invoke::Completion, negotiate::MessageType, streaming::StreamItem}};
+use crate::{client::SignalRClient, completer::{ManualStream, ManualStreamCompleter, StreamSpace}, protocol::hub_protocol::{CompletionResult, HubMessage, ProtocolOptions}};
+
+use super::{actions::UpdatableAction, storage::{Storage, StorageEntryGuard}};
+
+/// The number of items a stream buffers by default, before the stream is cancelled.
+pub const DEFAULT_STREAM_CAPACITY: usize = 1024;
+
+/// Limits the items of a stream that were received from the hub, but not read yet.
+///
+/// Set for a single stream with `ArgumentConfiguration::stream_buffer`.
+/// The default is `Cancel` with a capacity of `DEFAULT_STREAM_CAPACITY` items, so a reader that falls behind
+/// gets an error instead of exhausting the memory, and the connection keeps processing the other messages.
+///
+/// **Awaiting an invocation of the same connection while reading a `Backpressure` stream can deadlock.**
+/// Once the buffer is full, the result of the invocation is not read until the stream is read, and the stream
+/// is not read until the invocation completes. Read such streams with `DropOldest`, `Cancel` or `Unbounded`,
+/// or collect the items before invoking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamBuffer {
+    /// Every item is buffered, however slowly the stream is read.
+    Unbounded,
+    /// When the buffer is full, the client stops reading the connection until the stream is read.
+    /// Meanwhile no other message of the connection is processed either, not even the result of an invocation
+    /// awaited by the reader of the stream.
+    ///
+    /// On WASM targets the browser cannot be told to stop receiving, so the buffer behaves like `Cancel` instead.
+    Backpressure {
+        capacity: usize,
+    },
+    /// When the buffer is full, the oldest item that was not read yet is dropped.
+    DropOldest {
+        capacity: usize,
+    },
+    /// When the buffer is full, the stream ends with an error, and the hub is asked to stop sending its items.
+    Cancel {
+        capacity: usize,
+    },
+}
+
+impl Default for StreamBuffer {
+    fn default() -> Self {
+        StreamBuffer::Cancel { capacity: DEFAULT_STREAM_CAPACITY }
+    }
+}
+
+pub(crate) const STREAM_BUFFER_FULL_ERROR: &str = "The stream buffer is full";
+
+/// Asks the hub to stop sending the items of a stream, with a `CancelInvocation` message sent at most once.
+///
+/// Shared by the stream in the storage, which cancels when its buffer overflows, and by the stream
+/// handed out to the caller, which cancels when it is dropped before the hub completes it.
+#[derive(Clone)]
+pub(crate) struct StreamCancellation {
+    invocation_id: String,
+    client: SignalRClient,
+    sent: Arc<AtomicBool>,
+}
+
+impl StreamCancellation {
+    pub(crate) fn new(invocation_id: String, client: SignalRClient) -> Self {
+        StreamCancellation {
+            invocation_id,
+            client,
+            sent: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Tells whether the hub was asked to stop the stream.
+    #[cfg(all(test, not(target_arch = "wasm32")))]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn cancel(&self) {
+        if self.sent.swap(true, Ordering::SeqCst) {
+            return;
+        }
 
-use super::actions::UpdatableAction;
+        let mut client = self.client.clone();
+        let invocation_id = self.invocation_id.clone();
+        let message = HubMessage::CancelInvocation { invocation_id: invocation_id.clone() };
+        let send = async move {
+            if let Err(e) = client.send_message(message).await {
+                warn!("Cannot cancel stream {}: {}", invocation_id, e);
+            }
+        };
+
+        // the stream may be dropped outside of the runtime, e.g. when the runtime shuts down
+        #[cfg(not(target_arch = "wasm32"))]
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(send);
+            },
+            Err(_) => warn!("Cannot cancel stream {} without a runtime", self.invocation_id),
+        }
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(send);
+    }
+}
 
 pub(crate) struct EnumerableAction<R: DeserializeOwned + Unpin> {
     invocation_id: String,
-    completer: ManualStreamCompleter<R>,
+    completer: ManualStreamCompleter<Result<R, String>>,
     completed: bool,
+    buffer: StreamBuffer,
+    options: ProtocolOptions,
+    cancellation: StreamCancellation,
 }
 
 impl<R: DeserializeOwned + Unpin> EnumerableAction<R> {
-    pub fn new(invocation_id: String) -> (Self, ManualStream<R>) {
+    pub fn new(invocation_id: String, buffer: StreamBuffer, options: ProtocolOptions, cancellation: StreamCancellation) -> (Self, ManualStream<Result<R, String>>) {
         let (s, c) = ManualStream::create();
 
+        // the browser cannot be told to stop receiving, so a full buffer ends the stream rather than growing
+        #[cfg(target_arch = "wasm32")]
+        let buffer = match buffer {
+            StreamBuffer::Backpressure { capacity } => StreamBuffer::Cancel { capacity },
+            buffer => buffer,
+        };
+
         (EnumerableAction {
             invocation_id: invocation_id,
             completer: c,
-            completed: false
+            completed: false,
+            buffer,
+            options,
+            cancellation,
         }, s)
     }
 
+    /// Buffers the item, or makes room for it according to the `StreamBuffer` of the stream.
+    fn push(&mut self, item: Result<R, String>) {
+        match self.buffer {
+            StreamBuffer::DropOldest { capacity } if self.completer.len() >= capacity.max(1) => {
+                warn!("The buffer of stream {} is full, dropping the oldest item", self.invocation_id);
+                self.completer.pop_oldest();
+            },
+            StreamBuffer::Cancel { capacity } if self.completer.len() >= capacity.max(1) => {
+                error!("Terminating stream {} with error: {}", self.invocation_id, STREAM_BUFFER_FULL_ERROR);
+                self.completer.push(Err(STREAM_BUFFER_FULL_ERROR.to_string()));
+                self.dispose_internal();
+                self.cancellation.cancel();
+                return;
+            },
+            _ => {},
+        }
+
+        self.completer.push(item);
+    }
+
     fn dispose_internal(&mut self) {
         self.completed = true;
         self.completer.close();
@@ -35,53 +167,31 @@ impl<R: DeserializeOwned + Unpin> Drop for EnumerableAction<R> {
 }
 
 impl<R: DeserializeOwned + Unpin + crate::platform::MaybeSend> UpdatableAction for EnumerableAction<R> {
-    fn update_with(&mut self, message: &MessagePayload, message_type: MessageType) {
-        match message_type {
-            MessageType::StreamItem => {
-                match message {
-                    MessagePayload::Text(s) => {
-                        if let Ok(item) = MessageParser::parse_message::<StreamItem<R>>(s) {
-                            self.completer.push(item.item);
-                        } else {
-                            error!("Cannot update stream {} with unparseable item {}", self.invocation_id, s);
-                        }
-                    },
-                    #[cfg(feature = "messagepack")]
-                    MessagePayload::Binary(data) => {
-                        match crate::protocol::msgpack::parse_msgpack_message(data) {
-                            Ok(items) => {
-                                match crate::protocol::msgpack::parse_stream_item(&items) {
-                                    Ok(si) => {
-                                        match crate::protocol::msgpack::value_to_type::<R>(&si.item) {
-                                            Ok(item) => self.completer.push(item),
-                                            Err(e) => error!("Cannot deserialize stream item: {}", e),
-                                        }
-                                    },
-                                    Err(e) => error!("Cannot parse msgpack stream item: {}", e),
-                                }
-                            },
-                            Err(e) => error!("Cannot parse msgpack message: {}", e),
-                        }
+    fn update_with(&mut self, message: HubMessage) {
+        match message {
+            HubMessage::StreamItem { item, .. } => {
+                if self.completed {
+                    return;
+                }
+
+                match item.decode::<R>(&self.options) {
+                    Ok(item) => self.push(Ok(item)),
+                    Err(e) => {
+                        let e = format!("Cannot deserialize stream item: {}", e);
+                        error!("Cannot update stream {} with item: {}", self.invocation_id, e);
+                        self.push(Err(e));
                     },
                 }
             },
-            MessageType::Completion => {
-                match message {
-                    MessagePayload::Text(s) => {
-                        if let Ok(_) = MessageParser::parse_message::<Completion<R>>(s) {
-                            self.completer.close();
-                        } else {
-                            error!("Cannot parse completition: {}", s);
-                        }
-                    },
-                    #[cfg(feature = "messagepack")]
-                    MessagePayload::Binary(_) => {
-                        // MessagePack completion for streams just means "stream ended"
-                        self.completer.close();
-                    },
+            HubMessage::Completion { result, .. } => {
+                if let CompletionResult::Error(e) = result {
+                    error!("Stream {} completed with error: {}", self.invocation_id, e);
+                    self.completer.push(Err(e));
                 }
+
+                self.dispose_internal();
             },
-            _ => error!("Cannot update stream {} with message type {:?}", self.invocation_id, message_type),
+            other => error!("Cannot update stream {} with message type {:?}", self.invocation_id, other.message_type()),
         }
     }
 
@@ -89,7 +199,80 @@ impl<R: DeserializeOwned + Unpin + crate::platform::MaybeSend> UpdatableAction f
         self.completed
     }
 
+    fn is_pending(&self) -> bool {
+        !self.completed
+    }
+
+    fn backpressure(&self) -> Option<StreamSpace> {
+        match self.buffer {
+            StreamBuffer::Backpressure { capacity } if !self.completed => {
+                Some(self.completer.space(capacity.max(1))).filter(|space| space.is_full())
+            },
+            _ => None,
+        }
+    }
+
+    fn fail(&mut self, error: &str) {
+        if !self.completed {
+            error!("Terminating stream {} with error: {}", self.invocation_id, error);
+            self.completer.push(Err(error.to_string()));
+            self.dispose_internal();
+        }
+    }
+
     fn dispose(mut self) {
         self.dispose_internal();
     }
-}
\ No newline at end of file
+}
+
+/// The stream handed out by `enumerate` and `try_enumerate`.
+///
+/// Dropping it before the hub completes the stream removes the pending `EnumerableAction` from the storage,
+/// and asks the hub to stop the stream. An item that cannot be deserialized is an error item, and the stream goes on. A failed hub method,
+/// or a lost connection, is an error item that ends the stream.
+pub(crate) struct EnumerableStream<R, S: Storage> {
+    stream: ManualStream<Result<R, String>>,
+    finished: bool,
+    cancellation: StreamCancellation,
+    _guard: StorageEntryGuard<S>,
+}
+
+impl<R, S: Storage> EnumerableStream<R, S> {
+    pub(crate) fn new(stream: ManualStream<Result<R, String>>, cancellation: StreamCancellation, guard: StorageEntryGuard<S>) -> Self {
+        EnumerableStream {
+            stream,
+            finished: false,
+            cancellation,
+            _guard: guard,
+        }
+    }
+}
+
+impl<R, S: Storage> Drop for EnumerableStream<R, S> {
+    fn drop(&mut self) {
+        // the hub removes the stream from the storage when it completes it
+        if self._guard.is_held() {
+            self.cancellation.cancel();
+        }
+    }
+}
+
+impl<R, S: Storage> Unpin for EnumerableStream<R, S> {}
+
+impl<R, S: Storage> Stream for EnumerableStream<R, S> {
+    type Item = Result<R, String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+
+        match Pin::new(&mut self.stream).poll_next(cx) {
+            Poll::Ready(None) => {
+                self.finished = true;
+                Poll::Ready(None)
+            },
+            other => other,
+        }
+    }
+}
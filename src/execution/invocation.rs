@@ -1,4 +1,4 @@
-use crate::{completer::{ManualFuture, ManualFutureCompleter}, protocol::{hub_protocol::MessagePayload, invoke::Completion, negotiate::MessageType}};
+use crate::{completer::{ManualFuture, ManualFutureCompleter}, protocol::hub_protocol::{CompletionResult, HubMessage, ProtocolOptions}};
 use log::{error, info};
 use serde::de::DeserializeOwned;
 
@@ -8,15 +8,17 @@ use super::actions::UpdatableAction;
 
 pub(crate) struct InvocationAction<R: DeserializeOwned + Unpin> {
     invocation_id: String,
-    completer: Option<ManualFutureCompleter<Result<R, String>>>
+    completer: Option<ManualFutureCompleter<Result<R, String>>>,
+    options: ProtocolOptions,
 }
 
 impl<R: DeserializeOwned + Unpin> InvocationAction<R> {
-    pub fn new(invocation_id: String) -> (Self, ManualFuture<Result<R, String>>) {
+    pub fn new(invocation_id: String, options: ProtocolOptions) -> (Self, ManualFuture<Result<R, String>>) {
         let (f, c) = ManualFuture::new();
         let invocation = InvocationAction {
             invocation_id: invocation_id,
-            completer: Some(c)
+            completer: Some(c),
+            options,
         };
 
         (invocation, f)
@@ -39,6 +41,14 @@ impl<R: DeserializeOwned + Unpin> InvocationAction<R> {
         completer.complete(Err(error));
     }
 
+    /// Completes the invocation of a void hub method, which succeeds only if `R` can be made of nothing, e.g. `()`.
+    pub fn complete_void(&mut self) {
+        match MessageParser::parse_message::<R>("null") {
+            Ok(result) => self.complete_ok(result),
+            Err(_) => self.complete_err("The hub method returned no result".to_string()),
+        }
+    }
+
     fn dispose_internal(&mut self) {
         let c = self.completer.take();
 
@@ -55,63 +65,24 @@ impl<R: DeserializeOwned + Unpin> Drop for InvocationAction<R> {
 }
 
 impl<R: DeserializeOwned + Unpin + crate::platform::MaybeSend> UpdatableAction for InvocationAction<R> {
-    fn update_with(&mut self, message: &MessagePayload, message_type: MessageType) {
-        match message_type {
-            MessageType::Completion => {
-                match message {
-                    MessagePayload::Text(s) => {
-                        if let Ok(completition) = MessageParser::parse_message::<Completion<R>>(s) {
-                            if completition.is_result() {
+    fn update_with(&mut self, message: HubMessage) {
+        match message {
+            HubMessage::Completion { result, .. } => {
+                match result {
+                    CompletionResult::Result(payload) => {
+                        match payload.decode::<R>(&self.options) {
+                            Ok(result) => {
                                 info!("Completition is parsed");
-                                self.complete_ok(completition.unwrap_result());
-                            } else {
-                                self.complete_err(completition.unwrap_error());
-                            }
-                        } else {
-                            self.complete_err(format!("Cannot parse completion: {}", s));
-                        }
-                    },
-                    #[cfg(feature = "messagepack")]
-                    MessagePayload::Binary(data) => {
-                        match crate::protocol::msgpack::parse_msgpack_message(data) {
-                            Ok(items) => {
-                                match crate::protocol::msgpack::parse_completion(&items) {
-                                    Ok(comp) => {
-                                        match comp.result_kind {
-                                            1 => {
-                                                let err = comp.payload
-                                                    .and_then(|v| v.as_str().map(|s| s.to_string()))
-                                                    .unwrap_or_else(|| "Unknown error".to_string());
-                                                self.complete_err(err);
-                                            },
-                                            2 => {
-                                                // Void completion - no result to deliver
-                                            },
-                                            3 => {
-                                                if let Some(val) = comp.payload {
-                                                    match crate::protocol::msgpack::value_to_type::<R>(&val) {
-                                                        Ok(result) => {
-                                                            info!("Completition is parsed");
-                                                            self.complete_ok(result);
-                                                        },
-                                                        Err(e) => self.complete_err(format!("Cannot deserialize completion result: {}", e)),
-                                                    }
-                                                } else {
-                                                    self.complete_err("Completion has no result payload".to_string());
-                                                }
-                                            },
-                                            _ => self.complete_err(format!("Unknown ResultKind: {}", comp.result_kind)),
-                                        }
-                                    },
-                                    Err(e) => self.complete_err(format!("Cannot parse msgpack completion: {}", e)),
-                                }
+                                self.complete_ok(result);
                             },
-                            Err(e) => self.complete_err(format!("Cannot parse msgpack message: {}", e)),
+                            Err(e) => self.complete_err(format!("Cannot deserialize completion result: {}", e)),
                         }
                     },
+                    CompletionResult::Error(error) => self.complete_err(error),
+                    CompletionResult::Void => self.complete_void(),
                 }
             },
-            _ => error!("Cannot complete invocation {} with message type {:?}", self.invocation_id, message_type),
+            other => error!("Cannot complete invocation {} with message {:?}", self.invocation_id, other.message_type()),
         }
     }
     
@@ -119,6 +90,16 @@ impl<R: DeserializeOwned + Unpin + crate::platform::MaybeSend> UpdatableAction f
         self.completer.is_none()
     }
 
+    fn is_pending(&self) -> bool {
+        self.completer.is_some()
+    }
+
+    fn fail(&mut self, error: &str) {
+        if self.completer.is_some() {
+            self.complete_err(error.to_string());
+        }
+    }
+
     fn dispose(mut self) {
         self.dispose_internal();
     }
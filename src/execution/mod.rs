@@ -3,13 +3,19 @@ mod invocation;
 mod enumerable;
 mod arguments;
 mod callback;
+mod dispatcher;
 mod storage;
+mod registrations;
 
 pub use arguments::ArgumentConfiguration;
 pub use storage::CallbackHandler;
+pub use registrations::CallbackRegistrations;
+pub use dispatcher::DispatchMode;
 
 pub(crate) use actions::UpdatableAction;
-pub(crate) use storage::{Storage, StorageUnregistrationHandler};
+pub(crate) use storage::{Storage, StorageEntryGuard, StorageUnregistrationHandler};
+pub(crate) use enumerable::{EnumerableStream, StreamCancellation};
+pub use enumerable::{StreamBuffer, DEFAULT_STREAM_CAPACITY};
 
 #[cfg(target_arch = "wasm32")]
 pub(crate) use storage::ManualFutureState;
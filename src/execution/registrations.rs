@@ -0,0 +1,84 @@
+use super::CallbackHandler;
+
+/// An object-safe counterpart of `CallbackHandler`, so different handlers can be kept together.
+trait BoxedCallbackHandler {
+    fn unregister_boxed(self: Box<Self>);
+}
+
+impl<T: CallbackHandler> BoxedCallbackHandler for T {
+    fn unregister_boxed(self: Box<Self>) {
+        (*self).unregister();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type BoxedHandler = Box<dyn BoxedCallbackHandler + Send>;
+#[cfg(target_arch = "wasm32")]
+type BoxedHandler = Box<dyn BoxedCallbackHandler>;
+
+/// A group of callback registrations that are unregistered together.
+///
+/// Unlike the handler returned by `SignalRClient::register`, the group unregisters all of its callbacks when dropped,
+/// so it can be kept alive for exactly as long as the callbacks are needed.
+///
+/// # Examples
+///
+/// ```ignore
+/// let mut registrations = CallbackRegistrations::new();
+///
+/// registrations.add(client.register("callback1".to_string(), |ctx| {
+///     info!("callback1 called");
+/// }));
+/// registrations.add(client.register("callback2".to_string(), |ctx| {
+///     info!("callback2 called");
+/// }));
+///
+/// // Unregisters both callbacks
+/// drop(registrations);
+/// ```
+#[derive(Default)]
+pub struct CallbackRegistrations {
+    _handlers: Vec<BoxedHandler>,
+}
+
+impl CallbackRegistrations {
+    /// Creates an empty group of registrations.
+    pub fn new() -> Self {
+        CallbackRegistrations {
+            _handlers: Vec::new(),
+        }
+    }
+
+    /// Adds a registered callback to the group.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - The handler returned by `SignalRClient::register`.
+    pub fn add(&mut self, handler: impl CallbackHandler + crate::platform::MaybeSend + 'static) {
+        self._handlers.push(Box::new(handler));
+    }
+
+    /// Returns the number of callbacks in the group.
+    pub fn len(&self) -> usize {
+        self._handlers.len()
+    }
+
+    /// Returns `true` if the group has no callbacks.
+    pub fn is_empty(&self) -> bool {
+        self._handlers.is_empty()
+    }
+}
+
+impl CallbackHandler for CallbackRegistrations {
+    fn unregister(self) {
+        // the callbacks are unregistered when the group is dropped
+    }
+}
+
+impl Drop for CallbackRegistrations {
+    fn drop(&mut self) {
+        for handler in self._handlers.drain(..) {
+            handler.unregister_boxed();
+        }
+    }
+}
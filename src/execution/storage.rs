@@ -1,7 +1,7 @@
 use log::{debug, info};
 use serde::de::DeserializeOwned;
-use crate::{completer::{CompletedFuture, ManualFuture, ManualFutureCompleter, ManualStream}, {client::SignalRClient, protocol::{hub_protocol::MessagePayload, invoke::{Invocation, PossibleInvocation}, messages::MessageParser, negotiate::{self, MessageType}}, InvocationContext}};
-use super::{callback::CallbackAction, enumerable::EnumerableAction, invocation::InvocationAction, UpdatableAction};
+use crate::{completer::{CompletedFuture, ManualFuture, ManualFutureCompleter, ManualStream, StreamSpace}, {client::SignalRClient, protocol::hub_protocol::{HubMessage, ProtocolOptions}, InvocationContext}};
+use super::{callback::CallbackAction, dispatcher::CallbackDispatcher, enumerable::{EnumerableAction, StreamBuffer, StreamCancellation}, invocation::InvocationAction, UpdatableAction};
 
 #[allow(dead_code)]
 #[derive(Clone)]
@@ -48,6 +48,31 @@ pub trait Storage : Clone {
     fn remove(&mut self, key: String);
     fn dispose(&mut self);
     fn increment(&mut self) -> usize;
+    fn pending_keys(&self) -> Vec<String>;
+    fn dispatcher(&self) -> CallbackDispatcher;
+    fn protocol_options(&self) -> ProtocolOptions;
+    fn set_protocol_options(&self, options: ProtocolOptions);
+
+    fn pending_count(&self) -> usize {
+        self.pending_keys().len()
+    }
+
+    /// Completes every pending invocation and stream with the given error and removes them.
+    fn fail_pending(&mut self, error: &str) {
+        let keys = self.pending_keys();
+
+        if !keys.is_empty() {
+            info!("Failing {} pending invocations: {}", keys.len(), error);
+        }
+
+        for key in keys {
+            self.update(key.clone(), |i| {
+                i.fail(error);
+            });
+
+            self.remove(key);
+        }
+    }
 
     fn create_key(&mut self, target: String) -> String {
         let index = self.increment();
@@ -55,13 +80,13 @@ pub trait Storage : Clone {
         format!("{}_{}", target, index)
     }
 
-    fn add_callback(&mut self, target: String, callback: impl Fn(InvocationContext) + crate::platform::MaybeSend + 'static, client: SignalRClient) {
+    fn add_callback(&mut self, target: String, callback: impl Fn(InvocationContext) + crate::platform::MaybeSendSync + 'static, client: SignalRClient) {
         debug!("Adding a callback for key {}", target);
-        self.insert(target.clone(), CallbackAction::create(target.clone(), callback, client));
+        self.insert(target.clone(), CallbackAction::create(target.clone(), callback, client, self.dispatcher()));
     }
 
     fn add_invocation<R: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend>(&mut self, invocation_id: String) -> ManualFuture<Result<R, String>> {
-        let (invocation, f) = InvocationAction::<R>::new(invocation_id.clone());
+        let (invocation, f) = InvocationAction::<R>::new(invocation_id.clone(), self.protocol_options());
 
         debug!("Inserting invocation for key {}", invocation_id);
         self.insert(invocation_id, invocation);
@@ -69,96 +94,74 @@ pub trait Storage : Clone {
         f
     }
 
-    fn add_stream<R: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend>(&mut self, invocation_id: String) -> ManualStream<R> {
-        let (stream, f) = EnumerableAction::<R>::new(invocation_id.clone());
+    fn add_stream<R: 'static + DeserializeOwned + Unpin + crate::platform::MaybeSend>(&mut self, invocation_id: String, buffer: StreamBuffer, cancellation: StreamCancellation) -> ManualStream<Result<R, String>> {
+        let (stream, f) = EnumerableAction::<R>::new(invocation_id.clone(), buffer, self.protocol_options(), cancellation);
 
         self.insert(invocation_id, stream);
 
         f
     }
 
-    fn process_message(&mut self, message: MessagePayload, message_type: MessageType) -> Result<(), String> {
-        debug!("MESSAGE: {:?} -> {:?}", message_type, message);
-
-        match message_type {
-            negotiate::MessageType::Invocation => {
-                let target = match &message {
-                    MessagePayload::Text(s) => {
-                        debug!("Server invocation {:?} -> {}", message_type, s);
-                        MessageParser::parse_message::<Invocation>(s).map(|inv| inv.get_target())
-                    },
-                    #[cfg(feature = "messagepack")]
-                    MessagePayload::Binary(data) => {
-                        let items = crate::protocol::msgpack::parse_msgpack_message(data)?;
-                        let inv = crate::protocol::msgpack::parse_invocation(&items)?;
-                        Ok(inv.target)
-                    },
-                }?;
-
-                self.update(target, |i| {
-                    i.update_with(&message, message_type);
-                });
+    /// Hands the message to the action it belongs to.
+    ///
+    /// Returns the space to wait for before reading the connection again, when the message filled the buffer of a stream.
+    fn process_message(&mut self, message: HubMessage) -> Result<Option<StreamSpace>, String> {
+        debug!("MESSAGE: {:?}", message);
+
+        match message {
+            HubMessage::Invocation { ref target, .. } => {
+                debug!("Server invocation {}", target);
+                self.deliver(target.clone(), message);
             },
-            negotiate::MessageType::StreamItem => {
-                let invocation_id = match &message {
-                    MessagePayload::Text(s) => {
-                        MessageParser::parse_message::<PossibleInvocation>(s).map(|p| p.invocation_id)
-                    },
-                    #[cfg(feature = "messagepack")]
-                    MessagePayload::Binary(data) => {
-                        let items = crate::protocol::msgpack::parse_msgpack_message(data)?;
-                        let si = crate::protocol::msgpack::parse_stream_item(&items)?;
-                        Ok(Some(si.invocation_id))
-                    },
-                }?;
-
-                if let Some(id) = invocation_id {
-                    self.update(id, |i| {
-                        i.update_with(&message, message_type);
-                    });
-                }
+            HubMessage::StreamItem { ref invocation_id, .. } => {
+                let mut space = None;
+                let mut message = Some(message.clone());
+
+                self.update(invocation_id.clone(), |i| {
+                    if let Some(message) = message.take() {
+                        i.update_with(message);
+                    }
+                    space = i.backpressure();
+                });
+
+                return Ok(space);
             },
-            negotiate::MessageType::Completion => {
-                let invocation_id = match &message {
-                    MessagePayload::Text(s) => {
-                        info!("Completition received {}", s);
-                        MessageParser::parse_message::<PossibleInvocation>(s).map(|p| p.invocation_id)
-                    },
-                    #[cfg(feature = "messagepack")]
-                    MessagePayload::Binary(data) => {
-                        let items = crate::protocol::msgpack::parse_msgpack_message(data)?;
-                        let comp = crate::protocol::msgpack::parse_completion(&items)?;
-                        info!("Completition received for {}", comp.invocation_id);
-                        Ok(Some(comp.invocation_id))
-                    },
-                }?;
-
-                if let Some(key) = invocation_id {
-                    self.update(key.clone(), |i| {
-                        i.update_with(&message, message_type);
-                    });
-
-                    self.remove(key.clone());
-                }
+            HubMessage::Completion { ref invocation_id, .. } => {
+                info!("Completition received for {}", invocation_id);
+                let key = invocation_id.clone();
+
+                self.deliver(key.clone(), message);
+                self.remove(key);
             },
-            negotiate::MessageType::StreamInvocation => {
+            HubMessage::StreamInvocation { .. } => {
                 debug!("Stream invocation is arrived");
             },
-            negotiate::MessageType::CancelInvocation => {
+            HubMessage::CancelInvocation { .. } => {
                 debug!("Cancel invocation is arrived");
             },
-            negotiate::MessageType::Ping => {
+            HubMessage::Ping => {
                 debug!("Ping is arrived");
             },
-            negotiate::MessageType::Close => {
+            HubMessage::Close { .. } => {
                 debug!("Close is arrived");
             },
-            negotiate::MessageType::Other => {
-                debug!("Other is arrived");
+            HubMessage::Ack { .. } | HubMessage::Sequence { .. } => {
+                debug!("Stateful reconnect message is arrived, it is not supported");
             },
         }
 
-        Ok(())
+        Ok(None)
+    }
+
+    /// Hands the message to the action of the key, if there is one.
+    fn deliver(&mut self, key: String, message: HubMessage) {
+        let mut message = Some(message);
+
+        self.update(key, |i| {
+            if let Some(message) = message.take() {
+                i.update_with(message);
+            }
+        });
     }
 }
 
@@ -186,4 +189,38 @@ impl<T: Storage> CallbackHandler for StorageUnregistrationHandler<T> {
     fn unregister(mut self) {
         self._storage.remove(self._key);
     }
+}
+
+/// Removes an invocation or stream entry from the storage when dropped.
+///
+/// Held by the futures and streams handed out to the caller, so an abandoned
+/// invocation does not leave its action behind in the storage.
+pub(crate) struct StorageEntryGuard<T>
+    where T : Storage
+{
+    _storage: T,
+    _key: String,
+}
+
+impl<T: Storage> StorageEntryGuard<T> {
+    pub(crate) fn new(storage: T, key: String) -> Self {
+        StorageEntryGuard {
+            _key: key,
+            _storage: storage
+        }
+    }
+
+    /// Tells whether the entry is still in the storage.
+    pub(crate) fn is_held(&self) -> bool {
+        self._storage.contains(self._key.clone())
+    }
+}
+
+impl<T: Storage> Drop for StorageEntryGuard<T> {
+    fn drop(&mut self) {
+        if self._storage.contains(self._key.clone()) {
+            debug!("Removing abandoned entry {} from storage", self._key);
+            self._storage.remove(self._key.clone());
+        }
+    }
 }
\ No newline at end of file
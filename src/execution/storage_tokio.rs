@@ -1,12 +1,15 @@
 use std::{collections::HashMap, sync::{Arc, Mutex}};
 use log::{error, info};
-use super::{Storage, UpdatableAction};
+use crate::protocol::hub_protocol::ProtocolOptions;
+use super::{dispatcher::CallbackDispatcher, Storage, UpdatableAction};
 
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Clone)]
 pub struct UpdatableActionStorage {
     _data: Arc<Mutex<HashMap<String, Mutex<Box<dyn UpdatableAction>>>>>,
     _index: Arc<Mutex<usize>>,
+    _dispatcher: CallbackDispatcher,
+    _protocol: Arc<Mutex<ProtocolOptions>>,
 }
 
 impl UpdatableActionStorage {
@@ -14,6 +17,8 @@ impl UpdatableActionStorage {
         UpdatableActionStorage {
             _data: Arc::new(Mutex::new(HashMap::new())),
             _index: Arc::new(Mutex::new(0)),
+            _dispatcher: CallbackDispatcher::default(),
+            _protocol: Arc::new(Mutex::new(ProtocolOptions::default())),
         }
     }
 }
@@ -103,4 +108,245 @@ impl Storage for UpdatableActionStorage {
 
         *index
     }
-}
\ No newline at end of file
+
+    fn pending_keys(&self) -> Vec<String> {
+        if let Ok(data) = self._data.lock() {
+            data.iter()
+                .filter(|(_, action)| action.lock().map(|a| a.is_pending()).unwrap_or(false))
+                .map(|(key, _)| key.clone())
+                .collect()
+        } else {
+            error!("Cannot lock storage");
+
+            Vec::new()
+        }
+    }
+
+    fn dispatcher(&self) -> CallbackDispatcher {
+        self._dispatcher.clone()
+    }
+
+    fn protocol_options(&self) -> ProtocolOptions {
+        self._protocol.lock().map(|options| options.clone()).unwrap_or_default()
+    }
+
+    fn set_protocol_options(&self, options: ProtocolOptions) {
+        if let Ok(mut current) = self._protocol.lock() {
+            *current = options;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use futures::StreamExt;
+
+    use crate::execution::{CallbackRegistrations, EnumerableStream, StorageEntryGuard, StorageUnregistrationHandler, StreamBuffer, StreamCancellation};
+    use serde_json::json;
+    use crate::protocol::hub_protocol::{CompletionResult, HubMessage, HubPayload};
+    use crate::SignalRClient;
+    use super::*;
+
+    #[test]
+    fn test_guard_removes_abandoned_invocation() {
+        let mut storage = UpdatableActionStorage::new();
+        let key = storage.create_key("SingleEntity".to_string());
+        let future = storage.add_invocation::<bool>(key.clone());
+        let guard = StorageEntryGuard::new(storage.clone(), key.clone());
+
+        assert_eq!(storage.pending_count(), 1);
+
+        drop(future);
+        drop(guard);
+
+        assert!(!storage.contains(key));
+        assert_eq!(storage.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_pending_count_ignores_callbacks() {
+        let mut storage = UpdatableActionStorage::new();
+        storage.add_callback("callback1".to_string(), |_| {}, SignalRClient::from_storage(storage.clone()));
+
+        assert_eq!(storage.pending_count(), 0);
+
+        let key = storage.create_key("HundredEntities".to_string());
+        let _stream = storage.add_stream::<bool>(key.clone(), StreamBuffer::default(), cancellation(&storage, &key));
+
+        assert_eq!(storage.pending_count(), 1);
+
+        storage.remove(key);
+
+        assert_eq!(storage.pending_count(), 0);
+        assert!(storage.contains("callback1".to_string()));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_blocking_callback_does_not_delay_completions() {
+        let mut storage = UpdatableActionStorage::new();
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = Mutex::new(released);
+
+        storage.add_callback("callback1".to_string(), move |_| {
+            let _ = released.lock().unwrap().recv_timeout(Duration::from_secs(2));
+        }, SignalRClient::from_storage(storage.clone()));
+        storage.process_message(HubMessage::Invocation { invocation_id: None, target: "callback1".to_string(), arguments: Vec::new(), stream_ids: Vec::new() }).unwrap();
+
+        let key = storage.create_key("SingleEntity".to_string());
+        let result = storage.add_invocation::<bool>(key.clone());
+        let mut receiver = storage.clone();
+        let started = Instant::now();
+
+        tokio::spawn(async move {
+            // the completion arrives after the worker of the callback got its turn on the runtime
+            tokio::task::yield_now().await;
+            receiver.process_message(HubMessage::Completion { invocation_id: key, result: CompletionResult::Result(HubPayload::from(json!(true))) }).unwrap();
+        });
+
+        assert_eq!(result.await, Ok(true));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let _ = release.send(());
+    }
+
+    #[test]
+    fn test_fail_pending_completes_invocations() {
+        let mut storage = UpdatableActionStorage::new();
+        let key = storage.create_key("SingleEntity".to_string());
+        let future = storage.add_invocation::<bool>(key.clone());
+
+        storage.fail_pending("Connection lost");
+
+        assert_eq!(futures::executor::block_on(future), Err("Connection lost".to_string()));
+        assert!(!storage.contains(key));
+    }
+
+    #[test]
+    fn test_registrations_unregister_on_drop() {
+        let mut storage = UpdatableActionStorage::new();
+        let mut registrations = CallbackRegistrations::new();
+        let mut keys = Vec::new();
+
+        for target in ["callback1", "callback2"] {
+            let key = storage.create_key(target.to_string());
+            let _future = storage.add_invocation::<bool>(key.clone());
+
+            registrations.add(StorageUnregistrationHandler::new(storage.clone(), key.clone()));
+            keys.push(key);
+        }
+
+        assert_eq!(registrations.len(), 2);
+
+        drop(registrations);
+
+        for key in keys {
+            assert!(!storage.contains(key));
+        }
+    }
+
+    #[test]
+    fn test_stream_reports_item_and_completion_errors() {
+        let mut storage = UpdatableActionStorage::new();
+        let key = storage.create_key("HundredEntities".to_string());
+        let cancel = cancellation(&storage, &key);
+        let stream = storage.add_stream::<u32>(key.clone(), StreamBuffer::Unbounded, cancel.clone());
+        let stream = EnumerableStream::new(stream, cancel.clone(), StorageEntryGuard::new(storage.clone(), key.clone()));
+
+        let messages = [
+            stream_item(&key, json!(1)),
+            stream_item(&key, json!("two")),
+            stream_item(&key, json!(3)),
+            HubMessage::Completion { invocation_id: key.clone(), result: CompletionResult::Error("The hub method failed".to_string()) },
+        ];
+
+        for message in messages {
+            assert!(storage.process_message(message).unwrap().is_none());
+        }
+
+        let items: Vec<_> = futures::executor::block_on(stream.collect());
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0], Ok(1));
+        assert!(items[1].is_err());
+        assert_eq!(items[2], Ok(3));
+        assert_eq!(items[3], Err("The hub method failed".to_string()));
+        assert!(!storage.contains(key));
+        assert!(!cancel.is_cancelled());
+    }
+
+    #[test]
+    fn test_dropped_stream_cancels_the_hub() {
+        let mut storage = UpdatableActionStorage::new();
+        let key = storage.create_key("HundredEntities".to_string());
+        let cancel = cancellation(&storage, &key);
+        let stream = storage.add_stream::<u32>(key.clone(), StreamBuffer::Unbounded, cancel.clone());
+        let stream = EnumerableStream::new(stream, cancel.clone(), StorageEntryGuard::new(storage.clone(), key.clone()));
+
+        push_items(&mut storage, &key, 2);
+        drop(stream);
+
+        assert!(cancel.is_cancelled());
+        assert!(!storage.contains(key));
+    }
+
+    fn cancellation(storage: &UpdatableActionStorage, key: &str) -> StreamCancellation {
+        StreamCancellation::new(key.to_string(), SignalRClient::from_storage(storage.clone()))
+    }
+
+    fn stream_item(key: &str, item: serde_json::Value) -> HubMessage {
+        HubMessage::StreamItem { invocation_id: key.to_string(), item: HubPayload::from(item) }
+    }
+
+    fn push_items(storage: &mut UpdatableActionStorage, key: &str, count: u32) -> Vec<bool> {
+        (0..count).map(|item| {
+            storage.process_message(stream_item(key, json!(item))).unwrap().is_some()
+        }).collect()
+    }
+
+    #[test]
+    fn test_stream_buffers() {
+        let mut storage = UpdatableActionStorage::new();
+
+        let key = storage.create_key("Backpressure".to_string());
+        let mut stream = storage.add_stream::<u32>(key.clone(), StreamBuffer::Backpressure { capacity: 2 }, cancellation(&storage, &key));
+        assert_eq!(push_items(&mut storage, &key, 3), vec![false, true, true]);
+        assert_eq!(futures::executor::block_on(stream.next()), Some(Ok(0)));
+        assert_eq!(push_items(&mut storage, &key, 1), vec![true]);
+
+        let key = storage.create_key("DropOldest".to_string());
+        let cancel = cancellation(&storage, &key);
+        let stream = storage.add_stream::<u32>(key.clone(), StreamBuffer::DropOldest { capacity: 2 }, cancel.clone());
+        assert_eq!(push_items(&mut storage, &key, 4), vec![false; 4]);
+        storage.remove(key);
+        assert_eq!(futures::executor::block_on(stream.collect::<Vec<_>>()), vec![Ok(2), Ok(3)]);
+        assert!(!cancel.is_cancelled());
+
+        let key = storage.create_key("Cancel".to_string());
+        let cancel = cancellation(&storage, &key);
+        let stream = storage.add_stream::<u32>(key.clone(), StreamBuffer::Cancel { capacity: 2 }, cancel.clone());
+        assert_eq!(push_items(&mut storage, &key, 4), vec![false; 4]);
+        assert!(cancel.is_cancelled());
+        storage.remove(key);
+        assert_eq!(futures::executor::block_on(stream.collect::<Vec<_>>()), vec![Ok(0), Ok(1), Err("The stream buffer is full".to_string())]);
+    }
+
+    #[test]
+    fn test_default_stream_buffer_never_holds_the_connection() {
+        let mut storage = UpdatableActionStorage::new();
+
+        let key = storage.create_key("Default".to_string());
+        let cancel = cancellation(&storage, &key);
+        let stream = storage.add_stream::<u32>(key.clone(), StreamBuffer::default(), cancel.clone());
+
+        // the receive loop is never asked to wait, the unread stream is cancelled instead
+        let capacity = crate::DEFAULT_STREAM_CAPACITY as u32;
+        assert!(push_items(&mut storage, &key, capacity + 1).iter().all(|waits| !waits));
+        assert!(cancel.is_cancelled());
+
+        storage.remove(key);
+        let items = futures::executor::block_on(stream.collect::<Vec<_>>());
+        assert_eq!(items.len(), crate::DEFAULT_STREAM_CAPACITY + 1);
+        assert_eq!(items.last(), Some(&Err("The stream buffer is full".to_string())));
+    }
+}
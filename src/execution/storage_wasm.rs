@@ -1,13 +1,16 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use log::{debug, error, info, warn};
 
-use super::{storage::Storage, UpdatableAction};
+use crate::protocol::hub_protocol::ProtocolOptions;
+use super::{dispatcher::CallbackDispatcher, storage::Storage, UpdatableAction};
 
 #[cfg(target_arch = "wasm32")]
 #[derive(Clone)]
 pub struct UpdatableActionStorage {
     _data: Rc<RefCell<HashMap<String, Box<dyn UpdatableAction>>>>,
     _index: Rc<RefCell<usize>>,
+    _dispatcher: CallbackDispatcher,
+    _protocol: Rc<RefCell<ProtocolOptions>>,
 }
 
 impl UpdatableActionStorage {
@@ -15,6 +18,8 @@ impl UpdatableActionStorage {
         UpdatableActionStorage {
             _data: Rc::new(RefCell::new(HashMap::new())),
             _index: Rc::new(RefCell::new(0)),
+            _dispatcher: CallbackDispatcher::default(),
+            _protocol: Rc::new(RefCell::new(ProtocolOptions::default())),
         }
     }
 }
@@ -91,6 +96,25 @@ impl Storage for UpdatableActionStorage {
 
         *index
     }
+
+    fn pending_keys(&self) -> Vec<String> {
+        self._data.borrow().iter()
+            .filter(|(_, action)| action.is_pending())
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn dispatcher(&self) -> CallbackDispatcher {
+        self._dispatcher.clone()
+    }
+
+    fn protocol_options(&self) -> ProtocolOptions {
+        self._protocol.borrow().clone()
+    }
+
+    fn set_protocol_options(&self, options: ProtocolOptions) {
+        *self._protocol.borrow_mut() = options;
+    }
 }
 
 
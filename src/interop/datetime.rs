@@ -0,0 +1,28 @@
+//! Serializes a `SystemTime` as a .NET `DateTime` in UTC.
+//!
+//! JSON gets an ISO 8601 string like `"2024-01-02T03:04:05.123Z"`, MessagePack gets a timestamp extension (type -1).
+//! A string without an offset is taken as UTC, a string with an offset is converted to UTC.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Entity {
+//!     #[serde(with = "signalr_client::interop::datetime")]
+//!     created: SystemTime,
+//! }
+//! ```
+
+use std::time::SystemTime;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::time::UnixTime;
+
+pub fn serialize<S: Serializer>(value: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    UnixTime::from(*value).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+    UnixTime::deserialize(deserializer)?.to_system_time().map_err(de::Error::custom)
+}
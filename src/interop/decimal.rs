@@ -0,0 +1,57 @@
+//! Serializes an `f64` as a .NET `decimal`.
+//!
+//! JSON gets a number, MessagePack gets a string like `"12.5"`, as `MessagePack-CSharp` writes decimals.
+//! Both numbers and strings are accepted with either protocol.
+//! The value is an `f64`, so a decimal with more than 15 significant digits loses precision.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Entity {
+//!     #[serde(with = "signalr_client::interop::decimal")]
+//!     price: f64,
+//! }
+//! ```
+
+use std::fmt;
+
+use serde::{de::{self, Visitor}, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_f64(*value)
+    } else {
+        serializer.serialize_str(&value.to_string())
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    deserializer.deserialize_any(DecimalVisitor)
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = f64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal number or string")
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<f64, E> {
+        Ok(v)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<f64, E> {
+        Ok(v as f64)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<f64, E> {
+        Ok(v as f64)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<f64, E> {
+        v.trim().parse().map_err(|_| E::custom(format!("Invalid decimal '{}'", v)))
+    }
+}
@@ -0,0 +1,151 @@
+//! Serializes .NET enums by their values or by their names.
+//!
+//! .NET serializes enums as numbers by default, and as names with a `JsonStringEnumConverter`.
+//! `as_int` writes the number and `as_string` writes the name, and both accept either form,
+//! the names case-insensitively.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! signalr_client::dotnet_enum! {
+//!     #[derive(Debug, Clone, Copy, PartialEq)]
+//!     pub enum OrderState {
+//!         Open = 0,
+//!         Shipped = 1,
+//!     }
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Order {
+//!     #[serde(with = "signalr_client::interop::enums::as_int")]
+//!     state: OrderState,
+//! }
+//! ```
+
+use std::fmt;
+
+use serde::{de::{self, Visitor}, Deserializer};
+
+/// An enum with the values and names of a .NET enum, usually implemented with `dotnet_enum!`.
+pub trait DotNetEnum: Sized {
+    /// Returns the value of the variant.
+    fn to_value(&self) -> i64;
+    /// Returns the variant of the given value.
+    fn from_value(value: i64) -> Option<Self>;
+    /// Returns the name of the variant.
+    fn name(&self) -> &'static str;
+    /// Returns the variant of the given name, ignoring the case.
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+/// Defines an enum and implements `DotNetEnum` for it, with the names and values of its variants.
+///
+/// # Examples
+///
+/// ```ignore
+/// signalr_client::dotnet_enum! {
+///     #[derive(Debug, Clone, Copy, PartialEq)]
+///     pub enum OrderState {
+///         Open = 0,
+///         Shipped = 1,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! dotnet_enum {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($variant:ident = $value:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant = $value),+
+        }
+
+        impl $crate::interop::DotNetEnum for $name {
+            fn to_value(&self) -> i64 {
+                match self {
+                    $($name::$variant => $value),+
+                }
+            }
+
+            fn from_value(value: i64) -> Option<Self> {
+                match value {
+                    $($value => Some($name::$variant),)+
+                    _ => None,
+                }
+            }
+
+            fn name(&self) -> &'static str {
+                match self {
+                    $($name::$variant => stringify!($variant)),+
+                }
+            }
+
+            fn from_name(name: &str) -> Option<Self> {
+                $(if name.eq_ignore_ascii_case(stringify!($variant)) {
+                    return Some($name::$variant);
+                })+
+
+                None
+            }
+        }
+    };
+}
+
+struct EnumVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: DotNetEnum> Visitor<'de> for EnumVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an enum value or name")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        T::from_value(v).ok_or_else(|| E::custom(format!("Unknown enum value {}", v)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        i64::try_from(v).ok()
+            .and_then(T::from_value)
+            .ok_or_else(|| E::custom(format!("Unknown enum value {}", v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        T::from_name(v)
+            .or_else(|| v.parse().ok().and_then(T::from_value))
+            .ok_or_else(|| E::custom(format!("Unknown enum name '{}'", v)))
+    }
+}
+
+fn deserialize_any<'de, T: DotNetEnum, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    deserializer.deserialize_any(EnumVisitor(std::marker::PhantomData))
+}
+
+/// Serializes the enum as its value, like .NET does by default.
+pub mod as_int {
+    use serde::{Deserializer, Serializer};
+
+    use super::DotNetEnum;
+
+    pub fn serialize<T: DotNetEnum, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(value.to_value())
+    }
+
+    pub fn deserialize<'de, T: DotNetEnum, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        super::deserialize_any(deserializer)
+    }
+}
+
+/// Serializes the enum as its name, like a `JsonStringEnumConverter` does.
+pub mod as_string {
+    use serde::{Deserializer, Serializer};
+
+    use super::DotNetEnum;
+
+    pub fn serialize<T: DotNetEnum, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(value.name())
+    }
+
+    pub fn deserialize<'de, T: DotNetEnum, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        super::deserialize_any(deserializer)
+    }
+}
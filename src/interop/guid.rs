@@ -0,0 +1,115 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de::{self, Visitor}, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A .NET `Guid`.
+///
+/// Both protocols get the string form, like `"0f8fad5b-d9cb-469f-a165-70867728950e"`.
+/// Strings with braces, upper case or without hyphens are accepted, and so are the 16 bytes
+/// a `NativeGuidResolver` of `MessagePack-CSharp` writes.
+///
+/// # Examples
+///
+/// ```ignore
+/// let id: Guid = "0f8fad5b-d9cb-469f-a165-70867728950e".parse()?;
+///
+/// assert_eq!(id.to_string(), "0f8fad5b-d9cb-469f-a165-70867728950e");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Guid([u8; 16]);
+
+impl Guid {
+    /// The empty `Guid`, with all zeros.
+    pub const NIL: Guid = Guid([0; 16]);
+
+    /// Creates a `Guid` of the bytes in the order of the string form.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Guid(bytes)
+    }
+
+    /// Returns the bytes in the order of the string form.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Creates a `Guid` of the bytes .NET `Guid.ToByteArray` returns, whose first three groups are little endian.
+    pub fn from_dotnet_bytes(bytes: [u8; 16]) -> Self {
+        let mut ordered = bytes;
+        ordered[..4].reverse();
+        ordered[4..6].reverse();
+        ordered[6..8].reverse();
+        Guid(ordered)
+    }
+
+    /// Returns `true` if the `Guid` is all zeros.
+    pub fn is_nil(&self) -> bool {
+        *self == Guid::NIL
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if matches!(index, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Guid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let inner = trimmed.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')).unwrap_or(trimmed);
+        let digits = inner.bytes().filter(|b| *b != b'-').collect::<Vec<_>>();
+
+        if digits.len() != 32 || !matches!(inner.len(), 32 | 36) {
+            return Err(format!("Invalid Guid '{}'", s));
+        }
+
+        let mut bytes = [0u8; 16];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            let hex = std::str::from_utf8(pair).map_err(|_| format!("Invalid Guid '{}'", s))?;
+            *byte = u8::from_str_radix(hex, 16).map_err(|_| format!("Invalid Guid '{}'", s))?;
+        }
+
+        Ok(Guid(bytes))
+    }
+}
+
+impl Serialize for Guid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Guid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(GuidVisitor)
+    }
+}
+
+struct GuidVisitor;
+
+impl<'de> Visitor<'de> for GuidVisitor {
+    type Value = Guid;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Guid string or 16 bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Guid, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Guid, E> {
+        let bytes = <[u8; 16]>::try_from(v).map_err(|_| E::invalid_length(v.len(), &self))?;
+
+        Ok(Guid::from_dotnet_bytes(bytes))
+    }
+}
@@ -0,0 +1,151 @@
+//! Serde adapters and types for the .NET types hubs send and receive.
+//!
+//! The adapters are used with the `with` attribute of serde, and work with both hub protocols:
+//! JSON gets the text forms of `System.Text.Json`, MessagePack gets the forms of `MessagePack-CSharp`.
+//!
+//! | .NET type | Rust type | JSON | MessagePack |
+//! |---|---|---|---|
+//! | `DateTime` | `SystemTime` with `datetime` | `"2024-01-02T03:04:05.123Z"` | timestamp extension (type -1) |
+//! | `DateTimeOffset` | `DateTimeOffset` | `"2024-01-02T05:04:05.123+02:00"` | `[timestamp, offset minutes]` |
+//! | `TimeSpan` | `Duration` with `timespan` | `"1.02:03:04.5000000"` | ticks |
+//! | `Guid` | `Guid` | `"0f8fad5b-d9cb-469f-a165-70867728950e"` | the same string |
+//! | `decimal` | `f64` with `decimal` | number | string |
+//! | enums | a `DotNetEnum` with `enums::as_int` or `enums::as_string` | number or name | number or name |
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use std::time::{Duration, SystemTime};
+//! use signalr_client::interop::{self, DateTimeOffset, Guid};
+//!
+//! signalr_client::dotnet_enum! {
+//!     #[derive(Debug, Clone, Copy, PartialEq)]
+//!     pub enum OrderState {
+//!         Open = 0,
+//!         Shipped = 1,
+//!     }
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Order {
+//!     id: Guid,
+//!     #[serde(with = "interop::datetime")]
+//!     created: SystemTime,
+//!     updated: DateTimeOffset,
+//!     #[serde(with = "interop::timespan")]
+//!     timeout: Duration,
+//!     #[serde(with = "interop::decimal")]
+//!     price: f64,
+//!     #[serde(with = "interop::enums::as_int")]
+//!     state: OrderState,
+//! }
+//! ```
+
+mod time;
+mod offset;
+mod guid;
+pub mod datetime;
+pub mod timespan;
+pub mod decimal;
+pub mod enums;
+
+pub use offset::DateTimeOffset;
+pub use guid::Guid;
+pub use enums::DotNetEnum;
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    crate::dotnet_enum! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum OrderState {
+            Open = 0,
+            Shipped = 2,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Order {
+        id: Guid,
+        #[serde(with = "datetime")]
+        created: SystemTime,
+        updated: DateTimeOffset,
+        #[serde(with = "timespan")]
+        timeout: Duration,
+        #[serde(with = "decimal")]
+        price: f64,
+        #[serde(with = "enums::as_int")]
+        state: OrderState,
+        #[serde(with = "enums::as_string")]
+        previous: OrderState,
+    }
+
+    fn sample() -> Order {
+        Order {
+            id: "0f8fad5b-d9cb-469f-a165-70867728950e".parse().unwrap(),
+            created: UNIX_EPOCH + Duration::new(1_700_000_000, 250_000_000),
+            updated: DateTimeOffset::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000), 120),
+            timeout: Duration::from_secs(300),
+            price: 12.5,
+            state: OrderState::Shipped,
+            previous: OrderState::Open,
+        }
+    }
+
+    #[test]
+    fn test_json_forms() {
+        let json = serde_json::to_value(sample()).unwrap();
+
+        assert_eq!(json, serde_json::json!({
+            "id": "0f8fad5b-d9cb-469f-a165-70867728950e",
+            "created": "2023-11-14T22:13:20.25Z",
+            "updated": "2023-11-15T00:13:20+02:00",
+            "timeout": "00:05:00",
+            "price": 12.5,
+            "state": 2,
+            "previous": "Open",
+        }));
+
+        assert_eq!(serde_json::from_value::<Order>(json).unwrap(), sample());
+
+        // .NET writes unspecified dates without an offset, and enums by the other convention
+        let lenient = serde_json::from_value::<Order>(serde_json::json!({
+            "id": "{0F8FAD5B-D9CB-469F-A165-70867728950E}",
+            "created": "2023-11-14T22:13:20.2500000",
+            "updated": "2023-11-14T22:13:20Z",
+            "timeout": "0.00:05:00",
+            "price": "12.5",
+            "state": "shipped",
+            "previous": 0,
+        })).unwrap();
+
+        assert_eq!(lenient.id, sample().id);
+        assert_eq!(lenient.created, sample().created);
+        assert_eq!(lenient.updated, DateTimeOffset::new(sample().updated.time, 0));
+        assert_eq!(lenient.state, OrderState::Shipped);
+        assert_eq!(lenient.previous, OrderState::Open);
+    }
+
+    #[cfg(feature = "messagepack")]
+    #[test]
+    fn test_msgpack_forms() {
+        let bytes = rmp_serde::to_vec_named(&sample()).unwrap();
+        let value = rmpv::decode::read_value(&mut std::io::Cursor::new(&bytes)).unwrap();
+        let field = |name: &str| value.as_map().unwrap().iter().find(|(k, _)| k.as_str() == Some(name)).unwrap().1.clone();
+
+        assert_eq!(field("id"), rmpv::Value::from("0f8fad5b-d9cb-469f-a165-70867728950e"));
+        assert!(matches!(field("created"), rmpv::Value::Ext(-1, _)));
+        assert!(matches!(field("updated"), rmpv::Value::Array(ref items) if items.len() == 2 && items[1] == rmpv::Value::from(120)));
+        assert_eq!(field("timeout"), rmpv::Value::from(300 * 10_000_000i64));
+        assert_eq!(field("price"), rmpv::Value::from("12.5"));
+        assert_eq!(field("state"), rmpv::Value::from(2));
+
+        assert_eq!(rmp_serde::from_slice::<Order>(&bytes).unwrap(), sample());
+    }
+}
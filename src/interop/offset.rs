@@ -0,0 +1,92 @@
+use std::{fmt, time::SystemTime};
+
+use serde::{de::{self, SeqAccess, Visitor}, ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::time::{format_iso, parse_iso, UnixTime};
+
+/// A .NET `DateTimeOffset`: a point in time, and the offset from UTC it was recorded with.
+///
+/// JSON gets an ISO 8601 string with the offset, like `"2024-01-02T05:04:05.123+02:00"`.
+/// MessagePack gets the array `MessagePack-CSharp` writes: the clock time as a timestamp, and the offset in minutes.
+///
+/// # Examples
+///
+/// ```ignore
+/// let now = DateTimeOffset::new(SystemTime::now(), 120);
+///
+/// assert_eq!(now.offset_minutes, 120);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTimeOffset {
+    /// The point in time.
+    pub time: SystemTime,
+    /// The offset of the clock time from UTC, in minutes.
+    pub offset_minutes: i16,
+}
+
+impl DateTimeOffset {
+    /// Creates a `DateTimeOffset` of the given point in time and offset.
+    pub fn new(time: SystemTime, offset_minutes: i16) -> Self {
+        DateTimeOffset { time, offset_minutes }
+    }
+
+    /// Creates a `DateTimeOffset` of the given point in time in UTC.
+    pub fn utc(time: SystemTime) -> Self {
+        DateTimeOffset::new(time, 0)
+    }
+
+    fn from_clock(clock: UnixTime, offset_minutes: i16) -> Result<Self, String> {
+        let time = clock.add_minutes(-i64::from(offset_minutes)).to_system_time()?;
+
+        Ok(DateTimeOffset::new(time, offset_minutes))
+    }
+}
+
+impl fmt::Display for DateTimeOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_iso(UnixTime::from(self.time), Some(self.offset_minutes)))
+    }
+}
+
+impl Serialize for DateTimeOffset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string())
+        } else {
+            let clock = UnixTime::from(self.time).add_minutes(self.offset_minutes.into());
+            let mut tuple = serializer.serialize_tuple(2)?;
+            tuple.serialize_element(&clock)?;
+            tuple.serialize_element(&self.offset_minutes)?;
+            tuple.end()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DateTimeOffset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DateTimeOffsetVisitor)
+    }
+}
+
+struct DateTimeOffsetVisitor;
+
+impl<'de> Visitor<'de> for DateTimeOffsetVisitor {
+    type Value = DateTimeOffset;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an ISO 8601 date with an offset, or a timestamp and an offset")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<DateTimeOffset, E> {
+        let (clock, offset) = parse_iso(v).map_err(E::custom)?;
+
+        DateTimeOffset::from_clock(clock, offset.unwrap_or(0)).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<DateTimeOffset, A::Error> {
+        let clock = seq.next_element::<UnixTime>()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let offset = seq.next_element::<i16>()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        DateTimeOffset::from_clock(clock, offset).map_err(de::Error::custom)
+    }
+}
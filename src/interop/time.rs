@@ -0,0 +1,374 @@
+use std::{fmt, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use serde::{de::{self, Visitor}, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::protocol::value::{ByteBuf, Bytes, EXT_STRUCT_NAME};
+
+/// The MessagePack extension type of timestamps, which .NET uses for `DateTime`.
+pub(crate) const TIMESTAMP_EXT: i8 = -1;
+
+pub(crate) const NANOS_PER_TICK: i64 = 100;
+pub(crate) const TICKS_PER_SECOND: i64 = 10_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// A point in time as seconds and nanoseconds since the Unix epoch, before or after it.
+///
+/// Serialized as an ISO 8601 string for JSON, and as a MessagePack timestamp otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct UnixTime {
+    pub(crate) secs: i64,
+    pub(crate) nanos: u32,
+}
+
+impl UnixTime {
+    pub(crate) fn add_minutes(self, minutes: i64) -> Self {
+        UnixTime { secs: self.secs + minutes * 60, nanos: self.nanos }
+    }
+
+    /// Returns the time as a `SystemTime`, which cannot represent every .NET date on every platform.
+    pub(crate) fn to_system_time(self) -> Result<SystemTime, String> {
+        let time = if self.secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(self.secs as u64, self.nanos))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs(self.secs.unsigned_abs()))
+                .and_then(|time| time.checked_add(Duration::from_nanos(self.nanos.into())))
+        };
+
+        time.ok_or_else(|| format!("The date {} cannot be represented on this platform", format_iso(self, None)))
+    }
+}
+
+impl From<SystemTime> for UnixTime {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => UnixTime { secs: after.as_secs() as i64, nanos: after.subsec_nanos() },
+            Err(e) => {
+                let before = e.duration();
+                if before.subsec_nanos() == 0 {
+                    UnixTime { secs: -(before.as_secs() as i64), nanos: 0 }
+                } else {
+                    UnixTime { secs: -(before.as_secs() as i64) - 1, nanos: 1_000_000_000 - before.subsec_nanos() }
+                }
+            },
+        }
+    }
+}
+
+impl Serialize for UnixTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&format_iso(*self, None))
+        } else {
+            serializer.serialize_newtype_struct(EXT_STRUCT_NAME, &(TIMESTAMP_EXT, Bytes(&encode_timestamp(*self))))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for UnixTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(UnixTimeVisitor)
+    }
+}
+
+struct UnixTimeVisitor;
+
+impl<'de> Visitor<'de> for UnixTimeVisitor {
+    type Value = UnixTime;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an ISO 8601 date or a MessagePack timestamp")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<UnixTime, E> {
+        let (time, offset) = parse_iso(v).map_err(E::custom)?;
+
+        Ok(time.add_minutes(-i64::from(offset.unwrap_or(0))))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<UnixTime, D::Error> {
+        let (tag, ByteBuf(data)) = <(i8, ByteBuf)>::deserialize(deserializer)?;
+
+        if tag != TIMESTAMP_EXT {
+            return Err(de::Error::custom(format!("The extension type {} is not a timestamp", tag)));
+        }
+
+        decode_timestamp(&data).map_err(de::Error::custom)
+    }
+}
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((i64::from(month) + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date of the proleptic Gregorian calendar of the given days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = (if days >= 0 { days } else { days - 146_096 }) / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = (if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 }) as u32;
+
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Formats the fraction of a second with at most 7 digits, the precision of .NET ticks, without trailing zeros.
+fn format_fraction(nanos: u32) -> String {
+    let ticks = nanos / NANOS_PER_TICK as u32;
+
+    if ticks == 0 {
+        String::new()
+    } else {
+        format!(".{:07}", ticks).trim_end_matches('0').to_string()
+    }
+}
+
+/// Formats the time like .NET: `2024-01-02T03:04:05.123Z` in UTC, or `2024-01-02T05:04:05.123+02:00` with an offset.
+pub(crate) fn format_iso(time: UnixTime, offset_minutes: Option<i16>) -> String {
+    let local = time.add_minutes(offset_minutes.unwrap_or(0).into());
+    let (year, month, day) = civil_from_days(local.secs.div_euclid(SECONDS_PER_DAY));
+    let seconds = local.secs.rem_euclid(SECONDS_PER_DAY);
+
+    let zone = match offset_minutes {
+        None => "Z".to_string(),
+        Some(offset) => format!("{}{:02}:{:02}", if offset < 0 { '-' } else { '+' }, offset.unsigned_abs() / 60, offset.unsigned_abs() % 60),
+    };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{}", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60, format_fraction(local.nanos), zone)
+}
+
+/// Reads the digits of a fixed width number at the start of the text.
+fn take_number(text: &mut &str, width: usize) -> Result<u32, String> {
+    let digits = text.get(..width).filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+        .ok_or_else(|| format!("Expected {} digits at '{}'", width, text))?;
+    *text = &text[width..];
+
+    digits.parse().map_err(|e| format!("Invalid number {}: {}", digits, e))
+}
+
+fn take_char(text: &mut &str, expected: char) -> bool {
+    match text.strip_prefix(expected) {
+        Some(rest) => {
+            *text = rest;
+            true
+        },
+        None => false,
+    }
+}
+
+/// Parses an ISO 8601 date as .NET writes it, and returns the clock time with the offset, if the text has one.
+///
+/// A time without an offset, like an unspecified .NET `DateTime`, is taken as UTC.
+pub(crate) fn parse_iso(text: &str) -> Result<(UnixTime, Option<i16>), String> {
+    let mut rest = text.trim();
+    let year = take_number(&mut rest, 4)?;
+    let month = if take_char(&mut rest, '-') { take_number(&mut rest, 2)? } else { return Err(format!("Invalid date '{}'", text)) };
+    let day = if take_char(&mut rest, '-') { take_number(&mut rest, 2)? } else { return Err(format!("Invalid date '{}'", text)) };
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(format!("Invalid date '{}'", text));
+    }
+
+    let (mut hour, mut minute, mut second, mut nanos) = (0, 0, 0, 0);
+
+    if take_char(&mut rest, 'T') || take_char(&mut rest, ' ') {
+        hour = take_number(&mut rest, 2)?;
+        minute = if take_char(&mut rest, ':') { take_number(&mut rest, 2)? } else { return Err(format!("Invalid time '{}'", text)) };
+
+        if take_char(&mut rest, ':') {
+            second = take_number(&mut rest, 2)?;
+
+            if take_char(&mut rest, '.') || take_char(&mut rest, ',') {
+                let length = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+                let digits = &rest[..length];
+                rest = &rest[length..];
+
+                nanos = format!("{:0<9}", &digits[..length.min(9)]).parse().map_err(|e| format!("Invalid fraction '{}': {}", digits, e))?;
+            }
+        }
+
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(format!("Invalid time '{}'", text));
+        }
+    }
+
+    let offset = if rest.is_empty() {
+        None
+    } else if take_char(&mut rest, 'Z') || take_char(&mut rest, 'z') {
+        Some(0)
+    } else {
+        let sign = if take_char(&mut rest, '+') { 1 } else if take_char(&mut rest, '-') { -1 } else { return Err(format!("Invalid offset in '{}'", text)) };
+        let hours = take_number(&mut rest, 2)?;
+        take_char(&mut rest, ':');
+        let minutes = take_number(&mut rest, 2)?;
+
+        Some(sign * (hours * 60 + minutes) as i16)
+    };
+
+    if !rest.is_empty() {
+        return Err(format!("Unexpected '{}' in '{}'", rest, text));
+    }
+
+    let secs = days_from_civil(year.into(), month, day) * SECONDS_PER_DAY + i64::from(hour * 3600 + minute * 60 + second);
+
+    Ok((UnixTime { secs, nanos }, offset))
+}
+
+/// Encodes the data of a MessagePack timestamp in the smallest of the 32, 64 and 96 bit formats.
+pub(crate) fn encode_timestamp(time: UnixTime) -> Vec<u8> {
+    if time.secs >> 34 == 0 {
+        let value = (u64::from(time.nanos) << 34) | time.secs as u64;
+
+        if value >> 32 == 0 {
+            (value as u32).to_be_bytes().to_vec()
+        } else {
+            value.to_be_bytes().to_vec()
+        }
+    } else {
+        let mut data = time.nanos.to_be_bytes().to_vec();
+        data.extend_from_slice(&time.secs.to_be_bytes());
+        data
+    }
+}
+
+/// Decodes the data of a MessagePack timestamp in any of the 32, 64 and 96 bit formats.
+pub(crate) fn decode_timestamp(data: &[u8]) -> Result<UnixTime, String> {
+    match data.len() {
+        4 => Ok(UnixTime { secs: u32::from_be_bytes(data.try_into().unwrap()).into(), nanos: 0 }),
+        8 => {
+            let value = u64::from_be_bytes(data.try_into().unwrap());
+
+            Ok(UnixTime { secs: (value & 0x3_ffff_ffff) as i64, nanos: (value >> 34) as u32 })
+        },
+        12 => Ok(UnixTime {
+            secs: i64::from_be_bytes(data[4..].try_into().unwrap()),
+            nanos: u32::from_be_bytes(data[..4].try_into().unwrap()),
+        }),
+        length => Err(format!("A timestamp cannot be {} bytes long", length)),
+    }
+}
+
+/// Formats .NET ticks like the constant format of `TimeSpan`: `[-][d.]hh:mm:ss[.fffffff]`.
+pub(crate) fn format_timespan(ticks: i64) -> String {
+    let sign = if ticks < 0 { "-" } else { "" };
+    let ticks = ticks.unsigned_abs();
+    let seconds = ticks / TICKS_PER_SECOND as u64;
+    let fraction = ticks % TICKS_PER_SECOND as u64;
+    let days = seconds / SECONDS_PER_DAY as u64;
+
+    let mut text = sign.to_string();
+    if days > 0 {
+        text.push_str(&format!("{}.", days));
+    }
+    text.push_str(&format!("{:02}:{:02}:{:02}", seconds / 3600 % 24, seconds / 60 % 60, seconds % 60));
+    if fraction > 0 {
+        text.push_str(&format!(".{:07}", fraction));
+    }
+
+    text
+}
+
+/// Parses a `TimeSpan` in the constant format to .NET ticks. The seconds and the fraction are optional.
+pub(crate) fn parse_timespan(text: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid TimeSpan '{}'", text);
+    let trimmed = text.trim();
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed),
+    };
+
+    // the days are separated by a dot before the first colon, the fraction by a dot after the last one
+    let first_colon = unsigned.find(':').ok_or_else(invalid)?;
+    let (days, clock) = match unsigned[..first_colon].find('.') {
+        Some(dot) => (&unsigned[..dot], &unsigned[dot + 1..]),
+        None => ("0", unsigned),
+    };
+    let last_colon = clock.rfind(':').ok_or_else(invalid)?;
+    let (clock, fraction) = match clock[last_colon..].find('.') {
+        Some(dot) => (&clock[..last_colon + dot], &clock[last_colon + dot + 1..]),
+        None => (clock, ""),
+    };
+
+    let parts = clock.split(':')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (hours, minutes, seconds) = match parts[..] {
+        [hours, minutes] => (hours, minutes, 0),
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        _ => return Err(invalid()),
+    };
+
+    if fraction.len() > 7 || !fraction.bytes().all(|b| b.is_ascii_digit()) || minutes > 59 || seconds > 59 {
+        return Err(invalid());
+    }
+
+    let fraction = if fraction.is_empty() { 0 } else { format!("{:0<7}", fraction).parse::<i64>().map_err(|_| invalid())? };
+    let days = days.parse::<i64>().map_err(|_| invalid())?;
+    let ticks = ((days * SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds) * TICKS_PER_SECOND) + fraction;
+
+    Ok(if negative { -ticks } else { ticks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iso_round_trip() {
+        let (time, offset) = parse_iso("2024-02-29T13:45:10.1234567Z").unwrap();
+
+        assert_eq!(offset, Some(0));
+        assert_eq!(time, UnixTime { secs: 1_709_214_310, nanos: 123_456_700 });
+        assert_eq!(format_iso(time, None), "2024-02-29T13:45:10.1234567Z");
+        assert_eq!(format_iso(time, Some(-90)), "2024-02-29T12:15:10.1234567-01:30");
+
+        let (local, offset) = parse_iso("2024-02-29T12:15:10.1234567-01:30").unwrap();
+        assert_eq!(local.add_minutes(-i64::from(offset.unwrap())), time);
+
+        let (old, _) = parse_iso("0001-01-01T00:00:00").unwrap();
+        assert_eq!(format_iso(old, None), "0001-01-01T00:00:00Z");
+        assert_eq!(UnixTime::from(old.to_system_time().unwrap()), old);
+
+        assert!(parse_iso("2024-13-01").is_err());
+        assert!(parse_iso("2024-01-01T10:00:00Q").is_err());
+    }
+
+    #[test]
+    fn test_timestamp_formats() {
+        for time in [
+            UnixTime { secs: 1_700_000_000, nanos: 0 },
+            UnixTime { secs: 1_700_000_000, nanos: 5 },
+            UnixTime { secs: -62_135_596_800, nanos: 100 },
+        ] {
+            assert_eq!(decode_timestamp(&encode_timestamp(time)).unwrap(), time);
+        }
+
+        assert_eq!(encode_timestamp(UnixTime { secs: 1, nanos: 0 }).len(), 4);
+        assert_eq!(encode_timestamp(UnixTime { secs: 1, nanos: 1 }).len(), 8);
+        assert_eq!(encode_timestamp(UnixTime { secs: -1, nanos: 0 }).len(), 12);
+    }
+
+    #[test]
+    fn test_timespan_round_trip() {
+        for (text, ticks) in [
+            ("00:05:00", 5 * 60 * TICKS_PER_SECOND),
+            ("1.02:03:04.5000000", ((86_400 + 2 * 3600 + 3 * 60 + 4) * TICKS_PER_SECOND) + 5_000_000),
+            ("-00:00:00.0000001", -1),
+        ] {
+            assert_eq!(parse_timespan(text).unwrap(), ticks);
+            assert_eq!(format_timespan(ticks), text);
+        }
+
+        assert_eq!(parse_timespan("01:30").unwrap(), 90 * 60 * TICKS_PER_SECOND);
+        assert!(parse_timespan("5 minutes").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Serializes a `Duration` as a .NET `TimeSpan`.
+//!
+//! JSON gets the constant format of `TimeSpan`, like `"00:05:00"` or `"1.02:03:04.5000000"`,
+//! MessagePack gets the number of ticks of 100 nanoseconds. Both forms are accepted with either protocol.
+//! A negative `TimeSpan` cannot be deserialized, as a `Duration` cannot be negative.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Entity {
+//!     #[serde(with = "signalr_client::interop::timespan")]
+//!     timeout: Duration,
+//! }
+//! ```
+
+use std::{fmt, time::Duration};
+
+use serde::{de::{self, Visitor}, Deserializer, Serializer};
+
+use super::time::{format_timespan, parse_timespan, NANOS_PER_TICK};
+
+pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    let ticks = i64::try_from(value.as_nanos() / NANOS_PER_TICK as u128)
+        .map_err(|_| serde::ser::Error::custom("The duration is too long for a TimeSpan"))?;
+
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&format_timespan(ticks))
+    } else {
+        serializer.serialize_i64(ticks)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let ticks = deserializer.deserialize_any(TicksVisitor)?;
+    let ticks = u64::try_from(ticks).map_err(|_| de::Error::custom("A negative TimeSpan cannot be a Duration"))?;
+
+    Ok(Duration::from_nanos(ticks).saturating_mul(NANOS_PER_TICK as u32))
+}
+
+struct TicksVisitor;
+
+impl<'de> Visitor<'de> for TicksVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a TimeSpan string or a number of ticks")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+        Ok(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<i64, E> {
+        i64::try_from(v).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<i64, E> {
+        parse_timespan(v).map_err(E::custom)
+    }
+}
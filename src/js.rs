@@ -0,0 +1,422 @@
+use std::{cell::{Cell, RefCell}, collections::HashMap, pin::Pin, rc::Rc, time::Duration};
+
+use futures::{Stream, StreamExt};
+use js_sys::{Array, Function, Object, Promise, Reflect};
+use log::error;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::{DelayListPolicy, HubConnectionState, InvocationContext, SignalRClient};
+
+#[wasm_bindgen(typescript_custom_section)]
+const HUB_CONNECTION_OPTIONS: &'static str = r#"
+/** The options of a hub connection. Every option is optional. */
+export interface HubConnectionOptions {
+    /** Connects over `wss`/`https` when `true` (the default), and over `ws`/`http` otherwise. */
+    secure?: boolean;
+    /** The port of the server. */
+    port?: number;
+    /** The bearer token sent when negotiating and connecting. */
+    accessToken?: string;
+    /** The hub protocol, `"json"` by default. `"messagepack"` requires the `messagepack` feature. */
+    protocol?: "json" | "messagepack";
+    /** The delays in milliseconds between the reconnection attempts. The client does not reconnect when omitted. */
+    reconnectDelays?: number[];
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "HubConnectionOptions")]
+    pub type HubConnectionOptions;
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct ConnectionOptions {
+    secure: Option<bool>,
+    port: Option<i32>,
+    access_token: Option<String>,
+    protocol: Option<String>,
+    reconnect_delays: Option<Vec<u64>>,
+}
+
+type JsHandlers = Rc<RefCell<HashMap<String, Vec<Function>>>>;
+type JsStream = Pin<Box<dyn Stream<Item = Result<serde_json::Value, String>>>>;
+
+/// A connection to a SignalR hub, exported to JavaScript.
+///
+/// Mirrors the `HubConnection` of the official JavaScript client: the handlers registered with `on` survive `stop` and `start`,
+/// and the hub methods are called with an array of arguments converted from and to JSON compatible values.
+///
+/// # Examples
+///
+/// ```ignore
+/// const connection = new HubConnection("localhost", "test", { port: 5220, secure: false });
+/// connection.on("callback1", entity => console.log(entity.text));
+/// await connection.start();
+///
+/// const entity = await connection.invoke("SingleEntity");
+/// for await (const item of connection.stream("HundredEntities")) {
+///     console.log(item.number);
+/// }
+///
+/// await connection.stop();
+/// ```
+#[wasm_bindgen]
+pub struct HubConnection {
+    _domain: String,
+    _hub: String,
+    _options: Rc<ConnectionOptions>,
+    _client: Rc<RefCell<Option<SignalRClient>>>,
+    _starting: Rc<Cell<bool>>,
+    _handlers: JsHandlers,
+}
+
+#[wasm_bindgen]
+impl HubConnection {
+    /// Creates a connection to the given hub. The connection is opened by `start`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(domain: String, hub: String, options: Option<HubConnectionOptions>) -> Result<HubConnection, JsError> {
+        let options = match options {
+            Some(options) => serde_wasm_bindgen::from_value::<ConnectionOptions>(options.into())
+                .map_err(|e| JsError::new(&format!("The connection options are invalid: {}", e)))?,
+            None => ConnectionOptions::default(),
+        };
+
+        match options.protocol.as_deref() {
+            None | Some("json") => {},
+            #[cfg(feature = "messagepack")]
+            Some("messagepack") => {},
+            Some(protocol) => return Err(JsError::new(&format!("The hub protocol '{}' is not supported", protocol))),
+        }
+
+        Ok(HubConnection {
+            _domain: domain,
+            _hub: hub,
+            _options: Rc::new(options),
+            _client: Rc::new(RefCell::new(None)),
+            _starting: Rc::new(Cell::new(false)),
+            _handlers: Rc::new(RefCell::new(HashMap::new())),
+        })
+    }
+
+    /// Opens the connection and registers the handlers added with `on`.
+    ///
+    /// Rejects when the connection is already started, or while another `start` is connecting.
+    #[wasm_bindgen(unchecked_return_type = "Promise<void>")]
+    pub fn start(&self) -> Promise {
+        // checked before the first await, so concurrent starts cannot both connect
+        if self._client.borrow().is_some() || self._starting.get() {
+            return Promise::reject(&JsError::new("The connection is already started").into());
+        }
+
+        self._starting.set(true);
+
+        let domain = self._domain.clone();
+        let hub = self._hub.clone();
+        let options = self._options.clone();
+        let slot = self._client.clone();
+        let starting = self._starting.clone();
+        let handlers = self._handlers.clone();
+
+        future_to_promise(async move {
+            let client = SignalRClient::connect_with(&domain, &hub, |c| {
+                if options.secure == Some(false) {
+                    c.unsecure();
+                }
+                if let Some(port) = options.port {
+                    c.with_port(port);
+                }
+                if let Some(token) = &options.access_token {
+                    c.authenticate_bearer(token.clone());
+                }
+                #[cfg(feature = "messagepack")]
+                if options.protocol.as_deref() == Some("messagepack") {
+                    c.with_messagepack_protocol();
+                }
+                if let Some(delays) = &options.reconnect_delays {
+                    c.with_automatic_reconnect(DelayListPolicy::new(delays.iter().map(|ms| Duration::from_millis(*ms)).collect()));
+                }
+            }).await;
+
+            starting.set(false);
+            let mut client = client.map_err(|e| JsError::new(&e))?;
+
+            let targets: Vec<String> = handlers.borrow().keys().cloned().collect();
+            for target in targets {
+                register_target(&mut client, &handlers, target);
+            }
+
+            *slot.borrow_mut() = Some(client);
+
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Invokes a hub method and resolves to its result.
+    #[wasm_bindgen(unchecked_return_type = "Promise<any>")]
+    pub fn invoke(&self, method: String, #[wasm_bindgen(unchecked_param_type = "any[]")] args: Option<Array>) -> Promise {
+        let client = self.client();
+
+        future_to_promise(async move {
+            let mut client = client?;
+            let args = from_js_args(args)?;
+
+            let result = client.invoke_with_args::<serde_json::Value, _>(method, |c| {
+                for arg in &args {
+                    c.argument(arg.clone());
+                }
+            }).await.map_err(|e| JsError::new(&e))?;
+
+            to_js(&result)
+        })
+    }
+
+    /// Sends a hub method invocation without waiting for a result.
+    #[wasm_bindgen(unchecked_return_type = "Promise<void>")]
+    pub fn send(&self, method: String, #[wasm_bindgen(unchecked_param_type = "any[]")] args: Option<Array>) -> Promise {
+        let client = self.client();
+
+        future_to_promise(async move {
+            let mut client = client?;
+            let args = from_js_args(args)?;
+
+            client.send_with_args(method, |c| {
+                for arg in &args {
+                    c.argument(arg.clone());
+                }
+            }).await.map_err(|e| JsError::new(&e))?;
+
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Adds a handler for the given client method.
+    ///
+    /// The value returned by the handler, or the value its promise resolves to, is sent back when the hub awaits a result.
+    pub fn on(&self, method: String, #[wasm_bindgen(unchecked_param_type = "(...args: any[]) => any")] callback: Function) {
+        let first = {
+            let mut handlers = self._handlers.borrow_mut();
+            let functions = handlers.entry(method.clone()).or_default();
+            functions.push(callback);
+            functions.len() == 1
+        };
+
+        if first {
+            if let Some(client) = self._client.borrow_mut().as_mut() {
+                register_target(client, &self._handlers, method);
+            }
+        }
+    }
+
+    /// Removes the given handler of a client method, or all of its handlers when no handler is given.
+    pub fn off(&self, method: String, #[wasm_bindgen(unchecked_param_type = "(...args: any[]) => any")] callback: Option<Function>) {
+        let mut handlers = self._handlers.borrow_mut();
+
+        if let Some(callback) = callback {
+            if let Some(functions) = handlers.get_mut(&method) {
+                functions.retain(|f| *f != callback);
+            }
+        } else {
+            handlers.remove(&method);
+        }
+    }
+
+    /// Invokes a streaming hub method, and returns an async iterator of its items.
+    ///
+    /// The invocation is sent when the first item is requested. An item that cannot be converted,
+    /// a failed hub method or a lost connection rejects the promise of the item.
+    pub fn stream(&self, method: String, #[wasm_bindgen(unchecked_param_type = "any[]")] args: Option<Array>) -> Result<HubStream, JsValue> {
+        let mut client = self.client()?;
+        let args = from_js_args(args)?;
+
+        let stream = futures::stream::once(async move {
+            let stream = client.try_enumerate_with_args::<serde_json::Value, _>(method, move |c| {
+                for arg in &args {
+                    c.argument(arg.clone());
+                }
+            }).await;
+
+            match stream {
+                Ok(stream) => stream.left_stream(),
+                Err(e) => futures::stream::once(futures::future::ready(Err(e))).right_stream(),
+            }
+        }).flatten();
+
+        Ok(HubStream {
+            _stream: Rc::new(RefCell::new(Some(Box::pin(stream)))),
+        })
+    }
+
+    /// Closes the connection. The handlers are kept for the next `start`.
+    #[wasm_bindgen(unchecked_return_type = "Promise<void>")]
+    pub fn stop(&self) -> Promise {
+        let slot = self._client.clone();
+
+        future_to_promise(async move {
+            let client = slot.borrow_mut().take();
+
+            if let Some(client) = client {
+                client.stop().await;
+            }
+
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// The state of the connection: `Connecting`, `Connected`, `Reconnecting` or `Disconnected`.
+    #[wasm_bindgen(getter, unchecked_return_type = "\"Connecting\" | \"Connected\" | \"Reconnecting\" | \"Disconnected\"")]
+    pub fn state(&self) -> String {
+        if self._starting.get() {
+            return "Connecting".to_string();
+        }
+
+        let state = self._client.borrow().as_ref().map(|client| client.state());
+
+        match state {
+            Some(HubConnectionState::Connecting) => "Connecting",
+            Some(HubConnectionState::Connected) => "Connected",
+            Some(HubConnectionState::Reconnecting) => "Reconnecting",
+            Some(HubConnectionState::Disconnected(_)) | None => "Disconnected",
+        }.to_string()
+    }
+
+    fn client(&self) -> Result<SignalRClient, JsValue> {
+        self._client.borrow().clone().ok_or_else(|| JsError::new("The connection is not started").into())
+    }
+}
+
+/// The items of a streaming hub method, iterated with `for await`.
+#[wasm_bindgen]
+pub struct HubStream {
+    _stream: Rc<RefCell<Option<JsStream>>>,
+}
+
+#[wasm_bindgen]
+impl HubStream {
+    /// Resolves to the next item of the stream.
+    #[wasm_bindgen(unchecked_return_type = "Promise<IteratorResult<any>>")]
+    pub fn next(&self) -> Promise {
+        let slot = self._stream.clone();
+
+        future_to_promise(async move {
+            let stream = slot.borrow_mut().take();
+
+            let item = match stream {
+                Some(mut stream) => {
+                    let item = stream.next().await;
+                    if item.is_some() {
+                        *slot.borrow_mut() = Some(stream);
+                    }
+                    item
+                },
+                None => None,
+            };
+
+            match item {
+                Some(Ok(value)) => iterator_result(to_js(&value)?, false),
+                Some(Err(e)) => Err(JsError::new(&e).into()),
+                None => iterator_result(JsValue::UNDEFINED, true),
+            }
+        })
+    }
+
+    /// Stops the iteration and drops the stream.
+    #[wasm_bindgen(js_name = "return", unchecked_return_type = "Promise<IteratorResult<any>>")]
+    pub fn finish(&self) -> Promise {
+        self._stream.borrow_mut().take();
+
+        match iterator_result(JsValue::UNDEFINED, true) {
+            Ok(result) => Promise::resolve(&result),
+            Err(e) => Promise::reject(&e),
+        }
+    }
+
+    #[wasm_bindgen(js_name = "[Symbol.asyncIterator]")]
+    pub fn iterator(&self) -> HubStream {
+        HubStream {
+            _stream: self._stream.clone(),
+        }
+    }
+}
+
+fn register_target(client: &mut SignalRClient, handlers: &JsHandlers, target: String) {
+    let handlers = handlers.clone();
+    let name = target.clone();
+
+    client.register(target, move |ctx| {
+        let functions = handlers.borrow().get(&name).cloned().unwrap_or_default();
+        let result = call_handlers(&ctx, &functions);
+
+        if ctx.expects_result() {
+            let name = name.clone();
+
+            InvocationContext::spawn(async move {
+                complete(ctx, &name, functions.is_empty(), result).await;
+            });
+        }
+    });
+}
+
+fn call_handlers(ctx: &InvocationContext, functions: &[Function]) -> Result<JsValue, JsValue> {
+    let args = Array::new();
+    for value in ctx.arguments_raw() {
+        args.push(&to_js(&value)?);
+    }
+
+    let mut result = Ok(JsValue::UNDEFINED);
+    for function in functions {
+        result = function.apply(&JsValue::NULL, &args);
+        if let Err(e) = &result {
+            error!("The handler threw an error: {:?}", e);
+        }
+    }
+
+    result
+}
+
+async fn complete(mut ctx: InvocationContext, target: &str, missing: bool, result: Result<JsValue, JsValue>) {
+    let result = match result {
+        _ if missing => Err(format!("Client didn't provide a result for the method '{}'", target)),
+        Ok(value) if value.is_instance_of::<Promise>() => JsFuture::from(Promise::from(value)).await.map_err(error_message),
+        Ok(value) => Ok(value),
+        Err(e) => Err(error_message(e)),
+    };
+
+    let sent = match result.and_then(|value| serde_wasm_bindgen::from_value::<serde_json::Value>(value).map_err(|e| e.to_string())) {
+        Ok(value) => ctx.complete(value).await,
+        Err(e) => ctx.complete_error(e).await,
+    };
+
+    if let Err(e) = sent {
+        error!("Cannot send the result of {}: {}", target, e);
+    }
+}
+
+fn error_message(error: JsValue) -> String {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.message()),
+        None => error.as_string().unwrap_or_else(|| format!("{:?}", error)),
+    }
+}
+
+fn from_js_args(args: Option<Array>) -> Result<Vec<serde_json::Value>, JsValue> {
+    match args {
+        Some(args) => args.iter().map(|arg| serde_wasm_bindgen::from_value(arg).map_err(JsValue::from)).collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    value.serialize(&serde_wasm_bindgen::Serializer::json_compatible()).map_err(JsValue::from)
+}
+
+fn iterator_result(value: JsValue, done: bool) -> Result<JsValue, JsValue> {
+    let result = Object::new();
+    Reflect::set(&result, &JsValue::from_str("value"), &value)?;
+    Reflect::set(&result, &JsValue::from_str("done"), &JsValue::from_bool(done))?;
+
+    Ok(result.into())
+}
@@ -1,3 +1,5 @@
+extern crate self as signalr_client;
+
 mod completer;
 mod tests;
 mod execution;
@@ -5,12 +7,61 @@ mod protocol;
 mod client;
 mod communication;
 pub(crate) mod platform;
+pub mod interop;
+#[cfg(target_arch = "wasm32")]
+mod js;
 
 pub use client::{InvocationContext, SignalRClient, DisconnectionHandler, ReconnectionHandler};
-pub use execution::{ArgumentConfiguration, CallbackHandler};
+pub use execution::{ArgumentConfiguration, CallbackHandler, CallbackRegistrations, DispatchMode, StreamBuffer, DEFAULT_STREAM_CAPACITY};
 pub use completer::{CompletedFuture, ManualFuture, ManualStream};
-pub use protocol::hub_protocol::HubProtocolKind;
+pub use protocol::hub_protocol::{CompletionResult, HubMessage, HubPayload, HubProtocol, HubProtocolKind, PayloadFormat, TransferFormat};
+pub use protocol::value::HubValue;
+pub use protocol::naming::KeyNaming;
+pub use protocol::json::{JsonHubProtocol, JsonProtocolOptions, NullHandling};
+#[cfg(feature = "messagepack")]
+pub use protocol::msgpack::{MessagePackDecoding, MessagePackHubProtocol, MessagePackOptions, StructEncoding};
+pub use communication::{ConnectedHookError, HubConnectionState};
 pub use communication::reconnection::{
-    ReconnectionConfig, ReconnectionPolicy,
-    NoReconnectPolicy, ConstantDelayPolicy, LinearBackoffPolicy, ExponentialBackoffPolicy
-};
\ No newline at end of file
+    ReconnectionConfig, ReconnectionPolicy, RetryPolicy, RetryContext, ConnectionError, DisconnectCause,
+    NoReconnectPolicy, ConstantDelayPolicy, LinearBackoffPolicy, ExponentialBackoffPolicy,
+    FullJitterBackoffPolicy, DecorrelatedJitterBackoffPolicy, DelayListPolicy, TimeLimitedPolicy
+};
+
+#[cfg(target_arch = "wasm32")]
+pub use js::{HubConnection, HubStream};
+
+#[cfg(feature = "macros")]
+pub use signalr_client_macros::{hub_client, hub_proxy};
+
+#[doc(hidden)]
+pub mod __private {
+    use log::error;
+    use serde::Serialize;
+
+    use crate::InvocationContext;
+
+    pub use futures;
+    pub use crate::platform::MaybeSend;
+
+    /// Sends the result of a handler generated by `#[hub_client]` back to the hub, if the hub awaits one.
+    pub async fn complete_invocation<T: Serialize>(mut ctx: InvocationContext, result: T) {
+        if ctx.expects_result() {
+            if let Err(e) = ctx.complete(result).await {
+                error!("Cannot send the result of {}: {}", ctx.target(), e);
+            }
+        }
+    }
+
+    /// Reports an argument binding error of a handler generated by `#[hub_client]`.
+    pub fn reject_invocation(mut ctx: InvocationContext, error: String) {
+        error!("Cannot bind the arguments of {}: {}", ctx.target(), error);
+
+        if ctx.expects_result() {
+            InvocationContext::spawn(async move {
+                if let Err(e) = ctx.complete_error(error).await {
+                    error!("Cannot send the error of {}: {}", ctx.target(), e);
+                }
+            });
+        }
+    }
+}
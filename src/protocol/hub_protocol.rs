@@ -1,13 +1,9 @@
-/// Represents the raw bytes of a serialized SignalR message,
-/// whether it originated from JSON text or MessagePack binary.
-#[derive(Debug, Clone)]
-pub enum MessagePayload {
-    /// JSON text payload
-    Text(String),
-    /// MessagePack binary payload
-    #[cfg(feature = "messagepack")]
-    Binary(Vec<u8>),
-}
+use std::{borrow::Cow, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::value::RawValue;
+
+use super::{negotiate::MessageType, value::HubValue};
 
 /// Protocol selection for the SignalR hub connection.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -30,9 +26,18 @@ impl HubProtocolKind {
     /// Returns the transfer format expected in negotiation.
     pub fn transfer_format(&self) -> &'static str {
         match self {
-            HubProtocolKind::Json => "Text",
+            HubProtocolKind::Json => TransferFormat::Text.name(),
+            #[cfg(feature = "messagepack")]
+            HubProtocolKind::MessagePack => TransferFormat::Binary.name(),
+        }
+    }
+
+    /// Creates the built-in implementation of the protocol.
+    pub(crate) fn create(&self) -> Arc<dyn HubProtocol> {
+        match self {
+            HubProtocolKind::Json => Arc::new(crate::protocol::json::JsonHubProtocol),
             #[cfg(feature = "messagepack")]
-            HubProtocolKind::MessagePack => "Binary",
+            HubProtocolKind::MessagePack => Arc::new(crate::protocol::msgpack::MessagePackHubProtocol),
         }
     }
 }
@@ -42,3 +47,286 @@ impl Default for HubProtocolKind {
         HubProtocolKind::Json
     }
 }
+
+/// The kind of web socket frames a hub protocol is sent in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFormat {
+    Text,
+    Binary,
+}
+
+impl TransferFormat {
+    /// Returns the name of the format in the negotiation response.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransferFormat::Text => "Text",
+            TransferFormat::Binary => "Binary",
+        }
+    }
+}
+
+/// The encoding of the arguments, results and stream items of a hub protocol.
+///
+/// The values sent to the hub are serialized in this format before the protocol writes the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PayloadFormat {
+    #[default]
+    Json,
+    #[cfg(feature = "messagepack")]
+    MessagePack,
+}
+
+/// An argument, result or stream item of a hub message.
+///
+/// The value is kept in the encoding it was received in, as the slice of the message it was read from,
+/// and is deserialized only when the client asks for it, straight to the requested type.
+#[derive(Debug, Clone)]
+pub enum HubPayload {
+    /// The text of a single JSON value.
+    Json(Box<RawValue>),
+    /// The bytes of a single MessagePack value.
+    #[cfg(feature = "messagepack")]
+    MessagePack(Vec<u8>),
+}
+
+impl PartialEq for HubPayload {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (HubPayload::Json(a), HubPayload::Json(b)) => a.get() == b.get(),
+            #[cfg(feature = "messagepack")]
+            (HubPayload::MessagePack(a), HubPayload::MessagePack(b)) => a == b,
+            #[cfg(feature = "messagepack")]
+            _ => false,
+        }
+    }
+}
+
+impl From<serde_json::Value> for HubPayload {
+    fn from(value: serde_json::Value) -> Self {
+        HubPayload::Json(serde_json::value::to_raw_value(&value).expect("A JSON value is always serializable"))
+    }
+}
+
+impl HubPayload {
+    /// Deserializes the value to a Rust type, with the default options of its encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// for message in JsonHubProtocol.parse_messages(frame) {
+    ///     if let Ok(HubMessage::StreamItem { item, .. }) = message {
+    ///         let entity: TestEntity = item.to_type()?;
+    ///     }
+    /// }
+    /// ```
+    pub fn to_type<T: DeserializeOwned>(&self) -> Result<T, String> {
+        self.decode(&ProtocolOptions::default())
+    }
+
+    /// Returns the value as JSON, converting MessagePack if necessary.
+    pub fn to_json(&self) -> Result<serde_json::Value, String> {
+        match self {
+            HubPayload::Json(value) => serde_json::from_str(value.get()).map_err(|e| format!("Failed to parse JSON: {}", e)),
+            #[cfg(feature = "messagepack")]
+            HubPayload::MessagePack(bytes) => {
+                let value = rmpv::decode::read_value(&mut bytes.as_slice())
+                    .map_err(|e| format!("Failed to parse MessagePack: {}", e))?;
+
+                Ok(crate::protocol::msgpack::msgpack_value_to_json(&value))
+            },
+        }
+    }
+
+    /// Returns the bytes of the value as MessagePack, converting JSON if necessary.
+    #[cfg(feature = "messagepack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, String> {
+        match self {
+            HubPayload::Json(_) => {
+                let mut buf = Vec::new();
+                rmpv::encode::write_value(&mut buf, &crate::protocol::msgpack::json_value_to_msgpack(&self.to_json()?))
+                    .map_err(|e| format!("Failed to encode value: {}", e))?;
+
+                Ok(buf)
+            },
+            HubPayload::MessagePack(bytes) => Ok(bytes.clone()),
+        }
+    }
+
+    /// Returns the text of the value as JSON, borrowed if it is JSON already.
+    pub(crate) fn to_raw_json(&self) -> Result<Cow<'_, RawValue>, String> {
+        match self {
+            HubPayload::Json(value) => Ok(Cow::Borrowed(value)),
+            #[cfg(feature = "messagepack")]
+            HubPayload::MessagePack(_) => serde_json::value::to_raw_value(&self.to_json()?)
+                .map(Cow::Owned)
+                .map_err(|e| format!("Failed to encode value: {}", e)),
+        }
+    }
+
+    /// Serializes a value sent to the hub, in the payload format and with the options of the protocol.
+    pub(crate) fn encode<T: ?Sized + Serialize>(data: &T, options: &ProtocolOptions) -> Result<Self, String> {
+        match options.format {
+            PayloadFormat::Json => crate::protocol::json::to_raw_value(data, &options.json).map(HubPayload::Json),
+            #[cfg(feature = "messagepack")]
+            PayloadFormat::MessagePack => crate::protocol::msgpack::encode_argument(data, &options.messagepack).map(HubPayload::MessagePack),
+        }
+    }
+
+    /// Deserializes the value received from the hub, with the options of its encoding.
+    pub(crate) fn decode<T: DeserializeOwned>(&self, options: &ProtocolOptions) -> Result<T, String> {
+        match self {
+            HubPayload::Json(value) => crate::protocol::json::raw_to_type(value, &options.json),
+            #[cfg(feature = "messagepack")]
+            HubPayload::MessagePack(bytes) => crate::protocol::msgpack::slice_to_type(bytes, &options.messagepack),
+        }
+    }
+
+    /// Returns the value without deserializing it to a Rust type. A value that cannot be read is `HubValue::Null`.
+    pub(crate) fn to_hub_value(&self) -> HubValue {
+        match self {
+            HubPayload::Json(value) => serde_json::from_str::<HubValue>(value.get()).unwrap_or_default(),
+            #[cfg(feature = "messagepack")]
+            HubPayload::MessagePack(bytes) => rmp_serde::from_slice::<HubValue>(bytes).unwrap_or_default(),
+        }
+    }
+}
+
+/// The outcome of an invocation, carried by a completion message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompletionResult {
+    /// The hub method returned nothing.
+    Void,
+    Result(HubPayload),
+    Error(String),
+}
+
+/// A message of the SignalR hub protocol, independent of its encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HubMessage {
+    /// Invokes a method, and awaits its result if it has an invocation id.
+    Invocation {
+        invocation_id: Option<String>,
+        target: String,
+        arguments: Vec<HubPayload>,
+        stream_ids: Vec<String>,
+    },
+    /// Invokes a streaming method, whose items arrive in `StreamItem` messages.
+    StreamInvocation {
+        invocation_id: String,
+        target: String,
+        arguments: Vec<HubPayload>,
+        stream_ids: Vec<String>,
+    },
+    StreamItem {
+        invocation_id: String,
+        item: HubPayload,
+    },
+    /// Ends an invocation or a stream.
+    Completion {
+        invocation_id: String,
+        result: CompletionResult,
+    },
+    CancelInvocation {
+        invocation_id: String,
+    },
+    Ping,
+    Close {
+        error: Option<String>,
+        allow_reconnect: bool,
+    },
+    /// Acknowledges the messages received up to the sequence id, with stateful reconnect.
+    Ack {
+        sequence_id: u64,
+    },
+    /// Sets the sequence id of the next message, with stateful reconnect.
+    Sequence {
+        sequence_id: u64,
+    },
+}
+
+impl HubMessage {
+    pub(crate) fn message_type(&self) -> MessageType {
+        match self {
+            HubMessage::Invocation { .. } => MessageType::Invocation,
+            HubMessage::StreamInvocation { .. } => MessageType::StreamInvocation,
+            HubMessage::StreamItem { .. } => MessageType::StreamItem,
+            HubMessage::Completion { .. } => MessageType::Completion,
+            HubMessage::CancelInvocation { .. } => MessageType::CancelInvocation,
+            HubMessage::Ping => MessageType::Ping,
+            HubMessage::Close { .. } => MessageType::Close,
+            HubMessage::Ack { .. } => MessageType::Ack,
+            HubMessage::Sequence { .. } => MessageType::Sequence,
+        }
+    }
+}
+
+/// A hub protocol, which turns the frames of the web socket into `HubMessage`s and back.
+///
+/// `JsonHubProtocol` and `MessagePackHubProtocol` are the built-in implementations. A custom protocol is
+/// set with `ConnectionConfiguration::with_hub_protocol`, and can wrap a built-in one, e.g. to compress its frames.
+/// The handshake is always sent as JSON, with the name and the version of the protocol.
+///
+/// # Examples
+///
+/// ```ignore
+/// struct CompressedJson;
+///
+/// impl HubProtocol for CompressedJson {
+///     fn name(&self) -> &str { "compressedjson" }
+///     fn transfer_format(&self) -> TransferFormat { TransferFormat::Binary }
+///     fn payload_format(&self) -> PayloadFormat { PayloadFormat::Json }
+///
+///     fn parse_messages(&self, input: &[u8]) -> Vec<Result<HubMessage, String>> {
+///         match decompress(input) {
+///             Ok(text) => JsonHubProtocol.parse_messages(&text),
+///             Err(e) => vec![Err(e)],
+///         }
+///     }
+///
+///     fn write_message(&self, message: &HubMessage) -> Result<Vec<u8>, String> {
+///         JsonHubProtocol.write_message(message).and_then(|text| compress(&text))
+///     }
+/// }
+///
+/// let client = SignalRClient::connect_with("localhost", "test", |c| {
+///     c.with_hub_protocol(CompressedJson);
+/// }).await.unwrap();
+/// ```
+pub trait HubProtocol: Send + Sync {
+    /// The name of the protocol in the handshake.
+    fn name(&self) -> &str;
+
+    /// The version of the protocol in the handshake.
+    fn version(&self) -> u8 {
+        1
+    }
+
+    fn transfer_format(&self) -> TransferFormat;
+
+    fn payload_format(&self) -> PayloadFormat;
+
+    /// Parses the messages of a received frame. A message that cannot be parsed is an error, and the others are still processed.
+    fn parse_messages(&self, input: &[u8]) -> Vec<Result<HubMessage, String>>;
+
+    /// Writes a message to send, with its framing.
+    fn write_message(&self, message: &HubMessage) -> Result<Vec<u8>, String>;
+}
+
+impl std::fmt::Debug for dyn HubProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HubProtocol")
+            .field("name", &self.name())
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+/// The options of the hub protocols, shared by the client and the actions that parse the messages of the hub.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProtocolOptions {
+    /// The encoding of the values sent to the hub, given by the protocol of the connection.
+    pub(crate) format: PayloadFormat,
+    pub(crate) json: crate::protocol::json::JsonProtocolOptions,
+    #[cfg(feature = "messagepack")]
+    pub(crate) messagepack: crate::protocol::msgpack::MessagePackOptions,
+}
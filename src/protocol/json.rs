@@ -0,0 +1,707 @@
+use std::borrow::Cow;
+
+use serde::{de::{self, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor}, forward_to_deserialize_any, Deserialize, Deserializer, Serialize};
+use serde_json::{value::RawValue, Map, Value};
+
+use super::{hub_protocol::{CompletionResult, HubMessage, HubPayload, HubProtocol, PayloadFormat, TransferFormat}, messages::{MessageParser, RECORD_SEPARATOR}, naming::{to_snake_case, KeyNaming}, negotiate::MessageType};
+
+/// How `null` values of objects are handled by the JSON protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullHandling {
+    /// Null fields are sent, and the null fields of the hub are deserialized as they are.
+    #[default]
+    Keep,
+    /// Null fields are left out of the objects sent to the hub, like `JsonIgnoreCondition.WhenWritingNull` of .NET,
+    /// and the null fields of the hub are taken as absent, so `#[serde(default)]` applies to them.
+    Omit,
+}
+
+/// The options of the JSON protocol, set with `ConnectionConfiguration::with_json_options`.
+///
+/// The defaults send and receive the objects exactly as serde names them.
+/// The naming policy renames the keys of the arguments and results sent to the hub, and the keys of the values
+/// received from the hub are matched to the renamed field names, so the Rust types need no `rename_all` attribute.
+/// The variant keys of externally tagged enums are matched the same way. The fields of a struct with `#[serde(flatten)]`
+/// fields are not known while it is read, so its keys are converted back to snake_case instead.
+///
+/// # Examples
+///
+/// ```ignore
+/// let client = SignalRClient::connect_with("localhost", "test", |c| {
+///     c.with_json_options(JsonProtocolOptions {
+///         key_naming: KeyNaming::CamelCase,
+///         case_insensitive: true,
+///         null_handling: NullHandling::Omit,
+///     });
+/// }).await.unwrap();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct JsonProtocolOptions {
+    /// Applies to every key of the objects sent to the hub, also to the keys of maps that are not structs.
+    pub key_naming: KeyNaming,
+    /// Matches the keys of the hub to the fields of the Rust types ignoring the case, like `PropertyNameCaseInsensitive` of .NET.
+    pub case_insensitive: bool,
+    pub null_handling: NullHandling,
+}
+
+impl Default for JsonProtocolOptions {
+    fn default() -> Self {
+        JsonProtocolOptions {
+            key_naming: KeyNaming::Preserve,
+            case_insensitive: false,
+            null_handling: NullHandling::Keep,
+        }
+    }
+}
+
+impl JsonProtocolOptions {
+    /// Returns `true` if the values pass through serde untouched, so the conversions can be skipped.
+    fn is_verbatim(&self) -> bool {
+        matches!(self.key_naming, KeyNaming::Preserve) && !self.case_insensitive && self.null_handling == NullHandling::Keep
+    }
+
+    /// Renames the keys of an object received from the hub to the fields of the struct they belong to.
+    fn match_fields(&self, entries: Map<String, Value>, fields: &'static [&'static str]) -> Vec<(String, Value)> {
+        entries.into_iter()
+            .filter(|(_, value)| !(value.is_null() && self.null_handling == NullHandling::Omit))
+            .map(|(key, value)| (self.match_name(key, fields), value))
+            .collect()
+    }
+
+    /// Returns the name of a field or variant that was sent as the given key, or the key itself if none matches.
+    fn match_name(&self, key: String, names: &'static [&'static str]) -> String {
+        let name = names.iter().find(|name| **name == key)
+            .or_else(|| names.iter().find(|name| self.key_naming.apply(name) == key))
+            .or_else(|| names.iter().find(|name| self.case_insensitive
+                && (name.eq_ignore_ascii_case(&key) || self.key_naming.apply(name).eq_ignore_ascii_case(&key))));
+
+        name.map(|name| name.to_string()).unwrap_or(key)
+    }
+
+    /// Returns `true` if the keys of the hub may differ from the names of the fields.
+    fn renames_keys(&self) -> bool {
+        !matches!(self.key_naming, KeyNaming::Preserve) || self.case_insensitive
+    }
+}
+
+/// Serializes a value sent to the hub, renaming its keys and leaving out its null fields as the options say.
+pub(crate) fn to_value<T: ?Sized + Serialize>(data: &T, options: &JsonProtocolOptions) -> Result<Value, String> {
+    let value = MessageParser::to_json_value(data).map_err(|e| format!("Serialization error: {}", e))?;
+
+    if options.is_verbatim() {
+        Ok(value)
+    } else {
+        Ok(transform_outgoing(value, options))
+    }
+}
+
+fn transform_outgoing(value: Value, options: &JsonProtocolOptions) -> Value {
+    match value {
+        Value::Object(entries) => Value::Object(entries.into_iter()
+            .filter(|(_, value)| !(value.is_null() && options.null_handling == NullHandling::Omit))
+            .map(|(key, value)| (options.key_naming.apply(&key), transform_outgoing(value, options)))
+            .collect()),
+        Value::Array(items) => Value::Array(items.into_iter().map(|item| transform_outgoing(item, options)).collect()),
+        other => other,
+    }
+}
+
+/// Deserializes a value received from the hub, matching its keys to the fields as the options say.
+pub(crate) fn value_to_type<T: DeserializeOwned>(value: &Value, options: &JsonProtocolOptions) -> Result<T, String> {
+    let result = if options.is_verbatim() {
+        T::deserialize(value)
+    } else {
+        T::deserialize(OptionsDeserializer { value: value.clone(), options })
+    };
+
+    result.map_err(|e| e.to_string())
+}
+
+/// Serializes a value sent to the hub straight to JSON text, unless the options have to transform it first.
+pub(crate) fn to_raw_value<T: ?Sized + Serialize>(data: &T, options: &JsonProtocolOptions) -> Result<Box<RawValue>, String> {
+    let raw = if options.is_verbatim() {
+        serde_json::value::to_raw_value(data)
+    } else {
+        serde_json::value::to_raw_value(&to_value(data, options)?)
+    };
+
+    raw.map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Deserializes the text of a value received from the hub. The text is read into a `Value` first only if the options match the keys.
+pub(crate) fn raw_to_type<T: DeserializeOwned>(raw: &RawValue, options: &JsonProtocolOptions) -> Result<T, String> {
+    if options.is_verbatim() {
+        serde_json::from_str(raw.get()).map_err(|e| e.to_string())
+    } else {
+        value_to_type(&serde_json::from_str(raw.get()).map_err(|e| e.to_string())?, options)
+    }
+}
+
+/// The JSON hub protocol, the default of SignalR.
+///
+/// The messages are JSON objects in text frames, each ended by the record separator `0x1E`.
+/// The values are renamed and matched as the `JsonProtocolOptions` of the connection say.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonHubProtocol;
+
+impl HubProtocol for JsonHubProtocol {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn transfer_format(&self) -> TransferFormat {
+        TransferFormat::Text
+    }
+
+    fn payload_format(&self) -> PayloadFormat {
+        PayloadFormat::Json
+    }
+
+    fn parse_messages(&self, input: &[u8]) -> Vec<Result<HubMessage, String>> {
+        match std::str::from_utf8(input) {
+            Ok(text) => text.split(RECORD_SEPARATOR)
+                .filter(|message| !message.trim().is_empty())
+                .map(|message| serde_json::from_str::<JsonMessage>(message)
+                    .map_err(|e| format!("Cannot parse message {:?}: {}", message, e))
+                    .and_then(JsonMessage::into_message))
+                .collect(),
+            Err(e) => vec![Err(format!("The frame is not UTF-8 text: {}", e))],
+        }
+    }
+
+    fn write_message(&self, message: &HubMessage) -> Result<Vec<u8>, String> {
+        let text = MessageParser::to_json(&OutgoingMessage::create(message)?)
+            .map_err(|e| format!("Serialization error: {}", e))?;
+
+        Ok(text.into_bytes())
+    }
+}
+
+/// The fields of every kind of hub message, read in a single pass.
+///
+/// The arguments, items and results are kept as the text they were received in, and are deserialized by the actions.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonMessage {
+    r#type: MessageType,
+    invocation_id: Option<String>,
+    target: Option<String>,
+    #[serde(default)]
+    arguments: Vec<Box<RawValue>>,
+    #[serde(default)]
+    stream_ids: Vec<String>,
+    #[serde(default, deserialize_with = "present")]
+    item: Option<Box<RawValue>>,
+    /// `Some` of `null` if the result is null, and `None` if there is no result.
+    #[serde(default, deserialize_with = "present")]
+    result: Option<Box<RawValue>>,
+    error: Option<String>,
+    #[serde(default)]
+    allow_reconnect: bool,
+    sequence_id: Option<u64>,
+}
+
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Box<RawValue>>, D::Error> {
+    Box::<RawValue>::deserialize(deserializer).map(Some)
+}
+
+impl JsonMessage {
+    fn into_message(self) -> Result<HubMessage, String> {
+        let invocation_id = self.invocation_id.ok_or("The message has no invocation id");
+        let sequence_id = self.sequence_id.ok_or("The message has no sequence id");
+        let arguments = self.arguments.into_iter().map(HubPayload::Json).collect();
+
+        Ok(match self.r#type {
+            MessageType::Invocation => HubMessage::Invocation {
+                invocation_id: invocation_id.ok(),
+                target: self.target.ok_or("The invocation has no target")?,
+                arguments,
+                stream_ids: self.stream_ids,
+            },
+            MessageType::StreamInvocation => HubMessage::StreamInvocation {
+                invocation_id: invocation_id?,
+                target: self.target.ok_or("The invocation has no target")?,
+                arguments,
+                stream_ids: self.stream_ids,
+            },
+            MessageType::StreamItem => HubMessage::StreamItem {
+                invocation_id: invocation_id?,
+                item: HubPayload::Json(self.item.unwrap_or_else(|| RawValue::NULL.to_owned())),
+            },
+            MessageType::Completion => HubMessage::Completion {
+                invocation_id: invocation_id?,
+                result: match (self.error, self.result) {
+                    (Some(error), _) => CompletionResult::Error(error),
+                    (None, Some(result)) => CompletionResult::Result(HubPayload::Json(result)),
+                    (None, None) => CompletionResult::Void,
+                },
+            },
+            MessageType::CancelInvocation => HubMessage::CancelInvocation { invocation_id: invocation_id? },
+            MessageType::Ping => HubMessage::Ping,
+            MessageType::Close => HubMessage::Close { error: self.error, allow_reconnect: self.allow_reconnect },
+            MessageType::Ack => HubMessage::Ack { sequence_id: sequence_id? },
+            MessageType::Sequence => HubMessage::Sequence { sequence_id: sequence_id? },
+        })
+    }
+}
+
+/// The fields of a hub message to send. The payloads are written as the text they were serialized to.
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct OutgoingMessage<'a> {
+    r#type: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    invocation_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Vec<Cow<'a, RawValue>>>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stream_ids: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item: Option<Cow<'a, RawValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Cow<'a, RawValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allow_reconnect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sequence_id: Option<u64>,
+}
+
+impl<'a> OutgoingMessage<'a> {
+    fn create(message: &'a HubMessage) -> Result<Self, String> {
+        let mut outgoing = OutgoingMessage { r#type: message.message_type() as u8, ..Default::default() };
+
+        match message {
+            HubMessage::Invocation { invocation_id, target, arguments, stream_ids } => {
+                outgoing.invocation_id = invocation_id.as_deref();
+                outgoing.write_invocation(target, arguments, stream_ids)?;
+            },
+            HubMessage::StreamInvocation { invocation_id, target, arguments, stream_ids } => {
+                outgoing.invocation_id = Some(invocation_id);
+                outgoing.write_invocation(target, arguments, stream_ids)?;
+            },
+            HubMessage::StreamItem { invocation_id, item } => {
+                outgoing.invocation_id = Some(invocation_id);
+                outgoing.item = Some(item.to_raw_json()?);
+            },
+            HubMessage::Completion { invocation_id, result } => {
+                outgoing.invocation_id = Some(invocation_id);
+                match result {
+                    CompletionResult::Void => {},
+                    CompletionResult::Result(payload) => outgoing.result = Some(payload.to_raw_json()?),
+                    CompletionResult::Error(error) => outgoing.error = Some(error),
+                }
+            },
+            HubMessage::CancelInvocation { invocation_id } => outgoing.invocation_id = Some(invocation_id),
+            HubMessage::Ping => {},
+            HubMessage::Close { error, allow_reconnect } => {
+                outgoing.error = error.as_deref();
+                outgoing.allow_reconnect = Some(*allow_reconnect);
+            },
+            HubMessage::Ack { sequence_id } | HubMessage::Sequence { sequence_id } => outgoing.sequence_id = Some(*sequence_id),
+        }
+
+        Ok(outgoing)
+    }
+
+    fn write_invocation(&mut self, target: &'a str, arguments: &'a [HubPayload], stream_ids: &'a [String]) -> Result<(), String> {
+        self.target = Some(target);
+        self.arguments = Some(arguments.iter().map(HubPayload::to_raw_json).collect::<Result<_, _>>()?);
+        self.stream_ids = stream_ids;
+
+        Ok(())
+    }
+}
+
+/// Deserializes a JSON value, and renames the keys of the objects to the fields of the structs on the way.
+struct OptionsDeserializer<'a> {
+    value: Value,
+    options: &'a JsonProtocolOptions,
+}
+
+impl<'de> Deserializer<'de> for OptionsDeserializer<'_> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Array(items) => visitor.visit_seq(OptionsSeqAccess { items: items.into_iter(), options: self.options }),
+            // the fields are not known, e.g. for a struct with flattened fields, so the keys read as field names are converted to snake_case
+            Value::Object(entries) => visitor.visit_map(OptionsMapAccess {
+                entries: entries.into_iter().collect::<Vec<_>>().into_iter(),
+                value: None,
+                options: self.options,
+                field_names: self.options.renames_keys(),
+            }),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Object(entries) => visitor.visit_map(OptionsMapAccess {
+                entries: self.options.match_fields(entries, fields).into_iter(),
+                value: None,
+                options: self.options,
+                field_names: false,
+            }),
+            other => OptionsDeserializer { value: other, options: self.options }.deserialize_any(visitor),
+        }
+    }
+
+    /// Reads an externally tagged enum. The key of a variant with content is matched like the key of a field,
+    /// as the keys of the enums sent to the hub are renamed too.
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Object(entries) if entries.len() == 1 => {
+                let Some((variant, value)) = entries.into_iter().next() else {
+                    return Err(de::Error::custom("The variant of the enum is missing"));
+                };
+
+                visitor.visit_enum(OptionsEnumAccess {
+                    variant: self.options.match_name(variant, variants),
+                    value,
+                    options: self.options,
+                })
+            },
+            other => other.deserialize_enum(name, variants, visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+struct OptionsEnumAccess<'a> {
+    variant: String,
+    value: Value,
+    options: &'a JsonProtocolOptions,
+}
+
+impl<'de, 'a> EnumAccess<'de> for OptionsEnumAccess<'a> {
+    type Error = serde_json::Error;
+    type Variant = OptionsDeserializer<'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(KeyDeserializer { key: self.variant, field_name: false })?;
+
+        Ok((variant, OptionsDeserializer { value: self.value, options: self.options }))
+    }
+}
+
+/// Deserializes the content of an enum variant, so its structs are matched to their fields too.
+impl<'de> VariantAccess<'de> for OptionsDeserializer<'_> {
+    type Error = serde_json::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        <()>::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_struct("", fields, visitor)
+    }
+}
+
+struct OptionsSeqAccess<'a> {
+    items: std::vec::IntoIter<Value>,
+    options: &'a JsonProtocolOptions,
+}
+
+impl<'de> SeqAccess<'de> for OptionsSeqAccess<'_> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        match self.items.next() {
+            Some(value) => seed.deserialize(OptionsDeserializer { value, options: self.options }).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct OptionsMapAccess<'a> {
+    entries: std::vec::IntoIter<(String, Value)>,
+    value: Option<Value>,
+    options: &'a JsonProtocolOptions,
+    /// Converts the keys read as field names to snake_case, when the fields could not be matched by name.
+    field_names: bool,
+}
+
+impl<'de> MapAccess<'de> for OptionsMapAccess<'_> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(KeyDeserializer { key, field_name: self.field_names }).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let value = self.value.take().ok_or_else(|| de::Error::custom("The value of the key is missing"))?;
+
+        seed.deserialize(OptionsDeserializer { value, options: self.options })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// Deserializes the key of an object, which can be a number for the maps with number keys.
+///
+/// With `field_name`, a key read as the name of a field is converted to snake_case, the naming of the Rust fields.
+/// The keys of the maps are read as strings, so they are kept as they are.
+struct KeyDeserializer {
+    key: String,
+    field_name: bool,
+}
+
+macro_rules! deserialize_number_key {
+    ($($method:ident => $visit:ident: $ty:ty),*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            match self.key.parse::<$ty>() {
+                Ok(number) => visitor.$visit(number),
+                Err(_) => visitor.visit_string(self.key),
+            }
+        })*
+    };
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.key)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.field_name {
+            visitor.visit_string(to_snake_case(&self.key))
+        } else {
+            visitor.visit_string(self.key)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        Value::String(self.key).deserialize_enum(name, variants, visitor)
+    }
+
+    deserialize_number_key! {
+        deserialize_i8 => visit_i8: i8, deserialize_i16 => visit_i16: i16, deserialize_i32 => visit_i32: i32, deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8, deserialize_u16 => visit_u16: u16, deserialize_u32 => visit_u32: u32, deserialize_u64 => visit_u64: u64
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Inner {
+        user_name: String,
+        #[serde(default)]
+        score: i32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Outer {
+        inner_items: Vec<Inner>,
+        by_id: HashMap<u32, Option<Inner>>,
+        note: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        NamedUser { user_name: String, #[serde(default)] score: i32 },
+        Wrapped(Inner),
+        Pair(Inner, u8),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Flattened {
+        request_id: u32,
+        #[serde(flatten)]
+        inner: Inner,
+        labels: HashMap<String, u8>,
+    }
+
+    fn sample() -> Outer {
+        Outer {
+            inner_items: vec![Inner { user_name: "a".to_string(), score: 1 }],
+            by_id: HashMap::from([(7, None)]),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_default_options_keep_the_values() {
+        let options = JsonProtocolOptions::default();
+        let value = to_value(&sample(), &options).unwrap();
+
+        assert_eq!(value, serde_json::to_value(sample()).unwrap());
+        assert_eq!(value_to_type::<Outer>(&value, &options).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_naming_and_nulls() {
+        let options = JsonProtocolOptions {
+            key_naming: KeyNaming::CamelCase,
+            case_insensitive: false,
+            null_handling: NullHandling::Omit,
+        };
+
+        let value = to_value(&sample(), &options).unwrap();
+        assert_eq!(value, serde_json::json!({
+            "innerItems": [{ "userName": "a", "score": 1 }],
+            "byId": {},
+        }));
+
+        let received = serde_json::json!({
+            "innerItems": [{ "userName": "b", "score": null }],
+            "byId": { "7": { "userName": "c" } },
+            "note": null,
+        });
+        assert_eq!(value_to_type::<Outer>(&received, &options).unwrap(), Outer {
+            inner_items: vec![Inner { user_name: "b".to_string(), score: 0 }],
+            by_id: HashMap::from([(7, Some(Inner { user_name: "c".to_string(), score: 0 }))]),
+            note: None,
+        });
+    }
+
+    #[test]
+    fn test_enum_variants() {
+        let options = JsonProtocolOptions { key_naming: KeyNaming::CamelCase, ..Default::default() };
+        let shapes = vec![
+            Shape::Empty,
+            Shape::NamedUser { user_name: "a".to_string(), score: 1 },
+            Shape::Wrapped(Inner { user_name: "b".to_string(), score: 2 }),
+            Shape::Pair(Inner { user_name: "c".to_string(), score: 3 }, 4),
+        ];
+
+        let value = to_value(&shapes, &options).unwrap();
+        assert_eq!(value[1], serde_json::json!({ "namedUser": { "userName": "a", "score": 1 } }));
+        assert_eq!(value_to_type::<Vec<Shape>>(&value, &options).unwrap(), shapes);
+
+        let options = JsonProtocolOptions { key_naming: KeyNaming::PascalCase, case_insensitive: true, ..Default::default() };
+        let received = serde_json::json!({ "NAMEDUSER": { "UserName": "d" } });
+        assert_eq!(value_to_type::<Shape>(&received, &options).unwrap(), Shape::NamedUser { user_name: "d".to_string(), score: 0 });
+    }
+
+    #[test]
+    fn test_flattened_fields() {
+        let options = JsonProtocolOptions { key_naming: KeyNaming::CamelCase, ..Default::default() };
+        let flattened = Flattened {
+            request_id: 1,
+            inner: Inner { user_name: "a".to_string(), score: 2 },
+            labels: HashMap::from([("firstLabel".to_string(), 3)]),
+        };
+
+        let value = to_value(&flattened, &options).unwrap();
+        assert_eq!(value, serde_json::json!({ "requestId": 1, "userName": "a", "score": 2, "labels": { "firstLabel": 3 } }));
+        assert_eq!(value_to_type::<Flattened>(&value, &options).unwrap(), flattened);
+    }
+
+    #[test]
+    fn test_case_insensitive_matching() {
+        let received = r#"{ "USER_NAME": "x", "Score": 3 }"#;
+
+        let parse = |text: &str, options: &JsonProtocolOptions| value_to_type::<Inner>(&serde_json::from_str(text).unwrap(), options);
+
+        assert!(parse(received, &JsonProtocolOptions::default()).is_err());
+
+        let options = JsonProtocolOptions { case_insensitive: true, ..Default::default() };
+        assert_eq!(parse(received, &options).unwrap(), Inner { user_name: "x".to_string(), score: 3 });
+
+        let options = JsonProtocolOptions { key_naming: KeyNaming::PascalCase, case_insensitive: true, ..Default::default() };
+        assert_eq!(parse(r#"{ "username": "y" }"#, &options).unwrap(), Inner { user_name: "y".to_string(), score: 0 });
+    }
+
+    #[test]
+    fn test_protocol_roundtrip() {
+        let messages = vec![
+            HubMessage::Invocation {
+                invocation_id: Some("1".to_string()),
+                target: "Target".to_string(),
+                arguments: vec![HubPayload::from(serde_json::json!({ "a": 1 })), HubPayload::from(serde_json::json!("b"))],
+                stream_ids: Vec::new(),
+            },
+            HubMessage::StreamItem { invocation_id: "2".to_string(), item: HubPayload::from(serde_json::json!(null)) },
+            HubMessage::Completion { invocation_id: "3".to_string(), result: CompletionResult::Void },
+            HubMessage::Completion { invocation_id: "4".to_string(), result: CompletionResult::Result(HubPayload::from(serde_json::json!(null))) },
+            HubMessage::Completion { invocation_id: "5".to_string(), result: CompletionResult::Error("failed".to_string()) },
+            HubMessage::Ping,
+            HubMessage::Close { error: Some("closed".to_string()), allow_reconnect: true },
+        ];
+
+        let mut frame = Vec::new();
+        for message in &messages {
+            frame.extend(JsonHubProtocol.write_message(message).unwrap());
+        }
+
+        let parsed: Vec<_> = JsonHubProtocol.parse_messages(&frame).into_iter().map(Result::unwrap).collect();
+        assert_eq!(parsed, messages);
+        assert!(JsonHubProtocol.parse_messages(b"{\"type\":42}\x1e")[0].is_err());
+    }
+
+    #[test]
+    fn test_payloads_keep_the_received_text() {
+        let frame = "{\"type\":1,\"target\":\"Target\",\"arguments\":[ { \"user_name\" : \"a\" }, 2 ]}\u{1e}";
+
+        let Ok(HubMessage::Invocation { arguments, .. }) = JsonHubProtocol.parse_messages(frame.as_bytes()).remove(0) else {
+            panic!("The message is not an invocation");
+        };
+
+        match &arguments[0] {
+            HubPayload::Json(raw) => assert_eq!(raw.get(), r#"{ "user_name" : "a" }"#),
+            #[cfg(feature = "messagepack")]
+            other => panic!("Unexpected payload {:?}", other),
+        }
+        assert_eq!(arguments[0].to_type::<Inner>().unwrap(), Inner { user_name: "a".to_string(), score: 0 });
+        assert_eq!(arguments[1].to_type::<u8>().unwrap(), 2);
+
+        let written = JsonHubProtocol.write_message(&HubMessage::StreamItem { invocation_id: "1".to_string(), item: arguments[0].clone() }).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), "{\"type\":2,\"invocationId\":\"1\",\"item\":{ \"user_name\" : \"a\" }}\u{1e}");
+    }
+}
@@ -18,6 +18,7 @@ impl MessageParser {
         Ok(serialized)
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn strip_record_separator(input: &str) -> &str {
         input.trim_end_matches(RECORD_SEPARATOR)
     }
@@ -1,8 +1,9 @@
 pub(crate) mod negotiate;
 pub(crate) mod messages;
 pub(crate) mod invoke;
-pub(crate) mod close;
-pub(crate) mod streaming;
+pub(crate) mod value;
+pub(crate) mod naming;
+pub(crate) mod json;
 pub mod hub_protocol;
 #[cfg(feature = "messagepack")]
 pub(crate) mod msgpack;
\ No newline at end of file
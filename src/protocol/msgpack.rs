@@ -1,10 +1,67 @@
-use std::collections::HashMap;
 use std::io::Cursor;
 
 use base64::Engine;
 
+use crate::protocol::hub_protocol::{CompletionResult, HubMessage, HubPayload, HubProtocol, PayloadFormat, TransferFormat};
+use crate::protocol::naming::{to_camel_case, to_snake_case, KeyNaming};
 use crate::protocol::negotiate::MessageType;
 
+// ── Options ─────────────────────────────────────────────────────────────
+
+/// How Rust structs are laid out in the MessagePack arguments sent to the hub.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StructEncoding {
+    /// A map from the field names to the values, like the `ContractlessStandardResolver` of .NET.
+    #[default]
+    Map,
+    /// An array of the values in field order, like .NET types with `[Key(n)]` attributes.
+    Array,
+}
+
+/// How the MessagePack values received from the hub are deserialized to Rust types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessagePackDecoding {
+    /// The values must match the Rust types as they are, including the map keys.
+    Strict,
+    /// When the values do not match, the map keys are renamed to camelCase and then to snake_case, and tried again.
+    #[default]
+    Lenient,
+}
+
+/// The options of the MessagePack protocol, set with `ConnectionConfiguration::with_messagepack_options`.
+///
+/// The defaults suit a .NET hub with the `ContractlessStandardResolver`:
+/// structs are sent as maps with PascalCase keys, and the keys of the hub are matched leniently.
+///
+/// # Examples
+///
+/// ```ignore
+/// let client = SignalRClient::connect_with("localhost", "test", |c| {
+///     c.with_messagepack_options(MessagePackOptions {
+///         struct_encoding: StructEncoding::Array,
+///         key_naming: KeyNaming::Preserve,
+///         decoding: MessagePackDecoding::Strict,
+///     });
+/// }).await.unwrap();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MessagePackOptions {
+    pub struct_encoding: StructEncoding,
+    /// Applies to every map key of the arguments, also to the keys of maps that are not structs.
+    pub key_naming: KeyNaming,
+    pub decoding: MessagePackDecoding,
+}
+
+impl Default for MessagePackOptions {
+    fn default() -> Self {
+        MessagePackOptions {
+            struct_encoding: StructEncoding::default(),
+            key_naming: KeyNaming::PascalCase,
+            decoding: MessagePackDecoding::default(),
+        }
+    }
+}
+
 // ── VarInt Framing ──────────────────────────────────────────────────────
 
 /// Encode a length as a VarInt (LEB128-style, MSB continuation bit).
@@ -49,7 +106,7 @@ pub fn frame_message(payload: &[u8]) -> Vec<u8> {
 }
 
 /// Extract individual messages from a binary buffer with VarInt length prefixes.
-pub fn split_framed_messages(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+pub fn split_framed_messages(data: &[u8]) -> Result<Vec<&[u8]>, String> {
     let mut messages = Vec::new();
     let mut offset = 0;
     while offset < data.len() {
@@ -58,7 +115,7 @@ pub fn split_framed_messages(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
         if offset + len > data.len() {
             return Err("Incomplete message in frame".to_string());
         }
-        messages.push(data[offset..offset + len].to_vec());
+        messages.push(&data[offset..offset + len]);
         offset += len;
     }
     Ok(messages)
@@ -82,154 +139,299 @@ pub fn read_message_type(data: &[u8]) -> Result<MessageType, String> {
         5 => Ok(MessageType::CancelInvocation),
         6 => Ok(MessageType::Ping),
         7 => Ok(MessageType::Close),
-        _ => Ok(MessageType::Other),
+        8 => Ok(MessageType::Ack),
+        9 => Ok(MessageType::Sequence),
+        _ => Err(format!("Unknown message type {}", msg_type)),
     }
 }
 
-// ── Outbound Encoding (Manual Array Writing) ────────────────────────────
+// ── Protocol ────────────────────────────────────────────────────────────
 
-/// Encode headers as a MessagePack map. Empty headers = fixmap 0 (0x80).
-fn encode_headers(buf: &mut Vec<u8>, headers: &Option<HashMap<String, String>>) -> Result<(), String> {
-    match headers {
-        Some(h) if !h.is_empty() => {
-            rmp::encode::write_map_len(buf, h.len() as u32).map_err(|e| e.to_string())?;
-            for (k, v) in h {
-                rmp::encode::write_str(buf, k).map_err(|e| e.to_string())?;
-                rmp::encode::write_str(buf, v).map_err(|e| e.to_string())?;
-            }
-        }
-        _ => {
-            rmp::encode::write_map_len(buf, 0).map_err(|e| e.to_string())?;
-        }
+/// The MessagePack hub protocol, enabled by the `messagepack` feature.
+///
+/// The messages are MessagePack arrays in binary frames, each prefixed by its length as a VarInt.
+/// The values are laid out and decoded as the `MessagePackOptions` of the connection say.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackHubProtocol;
+
+impl HubProtocol for MessagePackHubProtocol {
+    fn name(&self) -> &str {
+        "messagepack"
     }
-    Ok(())
-}
 
-/// Encode an Invocation (type 1) or StreamInvocation (type 4).
-/// Layout: [Type, Headers, InvocationId?, Target, Arguments, StreamIds]
-/// Always writes 6 elements to match the .NET SignalR MessagePack protocol.
-pub fn encode_invocation(
-    msg_type: u8,
-    headers: &Option<HashMap<String, String>>,
-    invocation_id: &Option<String>,
-    target: &str,
-    arguments: &[rmpv::Value],
-    stream_ids: &Option<Vec<String>>,
-) -> Result<Vec<u8>, String> {
-    let mut buf = Vec::new();
+    fn transfer_format(&self) -> TransferFormat {
+        TransferFormat::Binary
+    }
+
+    fn payload_format(&self) -> PayloadFormat {
+        PayloadFormat::MessagePack
+    }
 
-    // Always 6 elements - .NET SignalR MessagePack protocol expects exactly 6
-    rmp::encode::write_array_len(&mut buf, 6).map_err(|e| e.to_string())?;
-    rmp::encode::write_uint(&mut buf, msg_type as u64).map_err(|e| e.to_string())?;
-    encode_headers(&mut buf, headers)?;
+    fn parse_messages(&self, input: &[u8]) -> Vec<Result<HubMessage, String>> {
+        match split_framed_messages(input) {
+            Ok(frames) => frames.into_iter().map(parse_message).collect(),
+            Err(e) => vec![Err(format!("Failed to split binary frames: {}", e))],
+        }
+    }
 
-    // InvocationId (nil if absent)
-    match invocation_id {
-        Some(id) => rmp::encode::write_str(&mut buf, id).map_err(|e| e.to_string())?,
-        None => rmp::encode::write_nil(&mut buf).map_err(|e| e.to_string())?,
+    fn write_message(&self, message: &HubMessage) -> Result<Vec<u8>, String> {
+        write_message(message).map(|payload| frame_message(&payload))
     }
+}
 
-    rmp::encode::write_str(&mut buf, target).map_err(|e| e.to_string())?;
+// ── Outbound Encoding (Manual Array Writing) ────────────────────────────
 
-    // Arguments array
-    rmpv::encode::write_value(&mut buf, &rmpv::Value::Array(arguments.to_vec()))
-        .map_err(|e| e.to_string())?;
+/// Encode a message without its VarInt length prefix.
+/// The payloads are written as their bytes, they are MessagePack already.
+pub fn write_message(message: &HubMessage) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
 
-    // StreamIds (always present, empty array if none)
-    let ids = stream_ids.as_deref().unwrap_or(&[]);
-    rmp::encode::write_array_len(&mut buf, ids.len() as u32).map_err(|e| e.to_string())?;
-    for id in ids {
-        rmp::encode::write_str(&mut buf, id).map_err(|e| e.to_string())?;
+    match message {
+        // Layout: [Type, Headers, InvocationId?, Target, Arguments, StreamIds]
+        // Always 6 elements - .NET SignalR MessagePack protocol expects exactly 6
+        HubMessage::Invocation { invocation_id, target, arguments, stream_ids } => {
+            write_header(&mut buf, 6, message)?;
+            match invocation_id {
+                Some(id) => rmp::encode::write_str(&mut buf, id).map_err(|e| e.to_string())?,
+                None => rmp::encode::write_nil(&mut buf).map_err(|e| e.to_string())?,
+            }
+            write_invocation(&mut buf, target, arguments, stream_ids)?;
+        },
+        HubMessage::StreamInvocation { invocation_id, target, arguments, stream_ids } => {
+            write_header(&mut buf, 6, message)?;
+            rmp::encode::write_str(&mut buf, invocation_id).map_err(|e| e.to_string())?;
+            write_invocation(&mut buf, target, arguments, stream_ids)?;
+        },
+        // Layout: [2, Headers, InvocationId, Item]
+        HubMessage::StreamItem { invocation_id, item } => {
+            write_header(&mut buf, 4, message)?;
+            rmp::encode::write_str(&mut buf, invocation_id).map_err(|e| e.to_string())?;
+            buf.extend_from_slice(&item.to_msgpack()?);
+        },
+        // Layout: [3, Headers, InvocationId, ResultKind, Result?]
+        // ResultKind: 1=Error, 2=Void, 3=NonVoid
+        HubMessage::Completion { invocation_id, result } => {
+            let len = if matches!(result, CompletionResult::Void) { 4 } else { 5 };
+            write_header(&mut buf, len, message)?;
+            rmp::encode::write_str(&mut buf, invocation_id).map_err(|e| e.to_string())?;
+            match result {
+                CompletionResult::Error(error) => {
+                    rmp::encode::write_uint(&mut buf, 1).map_err(|e| e.to_string())?;
+                    rmp::encode::write_str(&mut buf, error).map_err(|e| e.to_string())?;
+                },
+                CompletionResult::Void => {
+                    rmp::encode::write_uint(&mut buf, 2).map_err(|e| e.to_string())?;
+                },
+                CompletionResult::Result(payload) => {
+                    rmp::encode::write_uint(&mut buf, 3).map_err(|e| e.to_string())?;
+                    buf.extend_from_slice(&payload.to_msgpack()?);
+                },
+            }
+        },
+        // Layout: [5, Headers, InvocationId]
+        HubMessage::CancelInvocation { invocation_id } => {
+            write_header(&mut buf, 3, message)?;
+            rmp::encode::write_str(&mut buf, invocation_id).map_err(|e| e.to_string())?;
+        },
+        // Layout: [6]
+        HubMessage::Ping => {
+            rmp::encode::write_array_len(&mut buf, 1).map_err(|e| e.to_string())?;
+            rmp::encode::write_uint(&mut buf, MessageType::Ping as u64).map_err(|e| e.to_string())?;
+        },
+        // Layout: [7, Error, AllowReconnect]
+        HubMessage::Close { error, allow_reconnect } => {
+            rmp::encode::write_array_len(&mut buf, 3).map_err(|e| e.to_string())?;
+            rmp::encode::write_uint(&mut buf, MessageType::Close as u64).map_err(|e| e.to_string())?;
+            match error {
+                Some(error) => rmp::encode::write_str(&mut buf, error).map_err(|e| e.to_string())?,
+                None => rmp::encode::write_nil(&mut buf).map_err(|e| e.to_string())?,
+            }
+            rmp::encode::write_bool(&mut buf, *allow_reconnect).map_err(|e| e.to_string())?;
+        },
+        // Layout: [8, SequenceId] and [9, SequenceId]
+        HubMessage::Ack { sequence_id } | HubMessage::Sequence { sequence_id } => {
+            rmp::encode::write_array_len(&mut buf, 2).map_err(|e| e.to_string())?;
+            rmp::encode::write_uint(&mut buf, message.message_type() as u64).map_err(|e| e.to_string())?;
+            rmp::encode::write_uint(&mut buf, *sequence_id).map_err(|e| e.to_string())?;
+        },
     }
 
     Ok(buf)
 }
 
-/// Encode Ping (type 6). Layout: [6]
-#[allow(dead_code)]
-pub fn encode_ping() -> Vec<u8> {
-    // Ping is always exactly: fixarray(1) + fixint(6) = 0x91 0x06
-    vec![0x91, 0x06]
+/// Encode the array length, the type and the empty headers (fixmap 0) of a message.
+fn write_header(buf: &mut Vec<u8>, len: u32, message: &HubMessage) -> Result<(), String> {
+    rmp::encode::write_array_len(buf, len).map_err(|e| e.to_string())?;
+    rmp::encode::write_uint(buf, message.message_type() as u64).map_err(|e| e.to_string())?;
+    rmp::encode::write_map_len(buf, 0).map_err(|e| e.to_string())?;
+
+    Ok(())
 }
 
-// ── Inbound Decoding ────────────────────────────────────────────────────
+fn write_invocation(buf: &mut Vec<u8>, target: &str, arguments: &[HubPayload], stream_ids: &[String]) -> Result<(), String> {
+    rmp::encode::write_str(buf, target).map_err(|e| e.to_string())?;
 
-/// Parse a full MessagePack message into an rmpv::Value array.
-pub fn parse_msgpack_message(data: &[u8]) -> Result<Vec<rmpv::Value>, String> {
-    let value = rmpv::decode::read_value(&mut Cursor::new(data))
-        .map_err(|e| format!("Failed to parse MessagePack: {}", e))?;
+    rmp::encode::write_array_len(buf, arguments.len() as u32).map_err(|e| e.to_string())?;
+    for argument in arguments {
+        buf.extend_from_slice(&argument.to_msgpack()?);
+    }
 
-    match value {
-        rmpv::Value::Array(items) => Ok(items),
-        _ => Err("MessagePack message is not an array".to_string()),
+    // StreamIds (always present, empty array if none)
+    rmp::encode::write_array_len(buf, stream_ids.len() as u32).map_err(|e| e.to_string())?;
+    for id in stream_ids {
+        rmp::encode::write_str(buf, id).map_err(|e| e.to_string())?;
     }
-}
 
-/// Parsed Invocation from MessagePack.
-pub struct MsgpackInvocation {
-    pub invocation_id: Option<String>,
-    pub target: String,
-    pub arguments: Vec<rmpv::Value>,
+    Ok(())
 }
 
-/// Parse Invocation or StreamInvocation.
-/// Layout: [Type, Headers, InvocationId?, Target, Arguments, StreamIds?]
-pub fn parse_invocation(items: &[rmpv::Value]) -> Result<MsgpackInvocation, String> {
-    if items.len() < 5 {
-        return Err(format!("Invocation array too short: {}", items.len()));
+/// Serialize an argument according to the options, renaming its map keys.
+pub fn encode_argument<T: ?Sized + serde::Serialize>(data: &T, options: &MessagePackOptions) -> Result<Vec<u8>, String> {
+    let bytes = match options.struct_encoding {
+        StructEncoding::Map => rmp_serde::to_vec_named(data),
+        StructEncoding::Array => rmp_serde::to_vec(data),
+    }.map_err(|e| format!("MessagePack serialization error: {}", e))?;
+
+    match options.key_naming.transform() {
+        Some(transform) => {
+            let value = rmpv::decode::read_value(&mut Cursor::new(&bytes))
+                .map_err(|e| format!("Failed to parse MessagePack: {}", e))?;
+            let mut buf = Vec::new();
+            rmpv::encode::write_value(&mut buf, &transform_keys(&value, transform))
+                .map_err(|e| format!("Failed to encode value: {}", e))?;
+            Ok(buf)
+        },
+        None => Ok(bytes),
     }
+}
 
-    let invocation_id = match &items[2] {
-        rmpv::Value::Nil => None,
-        rmpv::Value::String(s) => s.as_str().map(|s| s.to_string()),
-        _ => return Err("Invalid invocation_id type".to_string()),
-    };
-    let target = items[3].as_str().ok_or("Invalid target")?.to_string();
-    let arguments = match &items[4] {
-        rmpv::Value::Array(args) => args.clone(),
-        _ => return Err("Invalid arguments".to_string()),
+// ── Inbound Decoding ────────────────────────────────────────────────────
+
+/// Parse a message without its VarInt length prefix.
+/// The arguments, items and results keep their original bytes, and are deserialized later straight into the requested types.
+pub fn parse_message(data: &[u8]) -> Result<HubMessage, String> {
+    let message_type = read_message_type(data)?;
+    let mut reader = MessageReader { data, rest: data };
+    let len = reader.array_len()?;
+    reader.skip()?;
+
+    let message = match message_type {
+        // Layout: [Type, Headers, InvocationId?, Target, Arguments, StreamIds?]
+        MessageType::Invocation | MessageType::StreamInvocation => {
+            if len < 5 {
+                return Err(format!("Invocation array too short: {}", len));
+            }
+            reader.skip()?;
+            let invocation_id = reader.optional_string()?;
+            let target = reader.string()?;
+            let arguments = (0..reader.array_len()?).map(|_| reader.payload()).collect::<Result<Vec<_>, _>>()?;
+            let stream_ids = if len > 5 {
+                (0..reader.array_len()?).map(|_| reader.string()).collect::<Result<Vec<_>, _>>()?
+            } else {
+                Vec::new()
+            };
+
+            if message_type == MessageType::Invocation {
+                HubMessage::Invocation { invocation_id, target, arguments, stream_ids }
+            } else {
+                let invocation_id = invocation_id.ok_or("Invalid invocation_id")?;
+                HubMessage::StreamInvocation { invocation_id, target, arguments, stream_ids }
+            }
+        },
+        // Layout: [2, Headers, InvocationId, Item]
+        MessageType::StreamItem => {
+            if len < 4 {
+                return Err("StreamItem array too short".to_string());
+            }
+            reader.skip()?;
+            HubMessage::StreamItem { invocation_id: reader.string()?, item: reader.payload()? }
+        },
+        // Layout: [3, Headers, InvocationId, ResultKind, Result?]
+        MessageType::Completion => {
+            if len < 4 {
+                return Err("Completion array too short".to_string());
+            }
+            reader.skip()?;
+            let invocation_id = reader.string()?;
+            let result = match reader.integer()? {
+                1 if len > 4 => CompletionResult::Error(reader.optional_string()?.unwrap_or_else(|| "Unknown error".to_string())),
+                1 => CompletionResult::Error("Unknown error".to_string()),
+                2 => CompletionResult::Void,
+                3 if len > 4 => CompletionResult::Result(reader.payload()?),
+                3 => return Err("Completion has no result payload".to_string()),
+                kind => return Err(format!("Unknown ResultKind: {}", kind)),
+            };
+
+            HubMessage::Completion { invocation_id, result }
+        },
+        // Layout: [5, Headers, InvocationId]
+        MessageType::CancelInvocation => {
+            reader.skip()?;
+            HubMessage::CancelInvocation { invocation_id: reader.string()? }
+        },
+        MessageType::Ping => HubMessage::Ping,
+        // Layout: [7, Error, AllowReconnect?]
+        MessageType::Close => {
+            if len < 2 {
+                return Err("Close array too short".to_string());
+            }
+            let error = reader.optional_string()?;
+            let allow_reconnect = if len > 2 { reader.value()?.as_bool().unwrap_or(false) } else { false };
+
+            HubMessage::Close { error, allow_reconnect }
+        },
+        // Layout: [8, SequenceId] and [9, SequenceId]
+        MessageType::Ack => HubMessage::Ack { sequence_id: reader.integer()? },
+        MessageType::Sequence => HubMessage::Sequence { sequence_id: reader.integer()? },
     };
 
-    Ok(MsgpackInvocation { invocation_id, target, arguments })
+    Ok(message)
 }
 
-/// Parsed Completion from MessagePack.
-pub struct MsgpackCompletion {
-    pub invocation_id: String,
-    pub result_kind: u8,
-    pub payload: Option<rmpv::Value>,
+/// Reads the elements of a message one by one, without decoding the payloads.
+struct MessageReader<'a> {
+    data: &'a [u8],
+    rest: &'a [u8],
 }
 
-/// Parse Completion.
-/// Layout: [3, Headers, InvocationId, ResultKind, Result?]
-/// ResultKind: 1=Error, 2=Void, 3=NonVoid
-pub fn parse_completion(items: &[rmpv::Value]) -> Result<MsgpackCompletion, String> {
-    if items.len() < 4 {
-        return Err("Completion array too short".to_string());
+impl MessageReader<'_> {
+    fn array_len(&mut self) -> Result<u32, String> {
+        rmp::decode::read_array_len(&mut self.rest).map_err(|e| format!("Not a MessagePack array: {}", e))
     }
-    let invocation_id = items[2].as_str().ok_or("Invalid invocation_id")?.to_string();
-    let result_kind = items[3].as_u64().ok_or("Invalid ResultKind")? as u8;
-    let payload = if items.len() > 4 { Some(items[4].clone()) } else { None };
 
-    Ok(MsgpackCompletion { invocation_id, result_kind, payload })
-}
+    fn value(&mut self) -> Result<rmpv::Value, String> {
+        rmpv::decode::read_value(&mut self.rest).map_err(|e| format!("Failed to parse MessagePack: {}", e))
+    }
 
-/// Parsed StreamItem from MessagePack.
-pub struct MsgpackStreamItem {
-    pub invocation_id: String,
-    pub item: rmpv::Value,
-}
+    fn skip(&mut self) -> Result<(), String> {
+        rmpv::decode::read_value_ref(&mut self.rest)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to parse MessagePack: {}", e))
+    }
+
+    fn integer(&mut self) -> Result<u64, String> {
+        rmp::decode::read_int::<u64, _>(&mut self.rest).map_err(|e| format!("Invalid integer: {}", e))
+    }
 
-/// Parse StreamItem.
-/// Layout: [2, Headers, InvocationId, Item]
-pub fn parse_stream_item(items: &[rmpv::Value]) -> Result<MsgpackStreamItem, String> {
-    if items.len() < 4 {
-        return Err("StreamItem array too short".to_string());
+    fn string(&mut self) -> Result<String, String> {
+        self.optional_string()?.ok_or_else(|| "Invalid string".to_string())
     }
-    let invocation_id = items[2].as_str().ok_or("Invalid invocation_id")?.to_string();
-    let item = items[3].clone();
 
-    Ok(MsgpackStreamItem { invocation_id, item })
+    fn optional_string(&mut self) -> Result<Option<String>, String> {
+        match self.value()? {
+            rmpv::Value::Nil => Ok(None),
+            rmpv::Value::String(s) => s.into_str().map(Some).ok_or_else(|| "Invalid string".to_string()),
+            _ => Err("Invalid string".to_string()),
+        }
+    }
+
+    /// Returns the bytes of the next value.
+    fn payload(&mut self) -> Result<HubPayload, String> {
+        let start = self.data.len() - self.rest.len();
+        self.skip()?;
+
+        Ok(HubPayload::MessagePack(self.data[start..self.data.len() - self.rest.len()].to_vec()))
+    }
 }
 
 // ── Value Conversion ────────────────────────────────────────────────────
@@ -309,29 +511,6 @@ pub fn msgpack_value_to_json(value: &rmpv::Value) -> serde_json::Value {
     }
 }
 
-/// Convert PascalCase or any case to camelCase (lowercase first letter).
-fn to_camel_case(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars();
-    if let Some(first) = chars.next() {
-        result.push(first.to_ascii_lowercase());
-    }
-    result.extend(chars);
-    result
-}
-
-/// Convert camelCase or any case to PascalCase (uppercase first letter).
-#[allow(dead_code)]
-fn to_pascal_case(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars();
-    if let Some(first) = chars.next() {
-        result.push(first.to_ascii_uppercase());
-    }
-    result.extend(chars);
-    result
-}
-
 fn transform_keys(value: &rmpv::Value, transform: fn(&str) -> String) -> rmpv::Value {
     match value {
         rmpv::Value::Map(entries) => {
@@ -366,26 +545,35 @@ fn normalize_keys_to_camel(value: &rmpv::Value) -> rmpv::Value {
 /// Normalize map keys in an rmpv::Value tree from camelCase to PascalCase (outbound to .NET).
 #[allow(dead_code)]
 pub fn normalize_keys_to_pascal(value: &rmpv::Value) -> rmpv::Value {
-    transform_keys(value, to_pascal_case)
+    transform_keys(value, crate::protocol::naming::to_pascal_case)
 }
 
-/// Deserialize an rmpv::Value into a concrete Rust type via rmp-serde.
+/// Deserialize MessagePack bytes into a concrete Rust type via rmp-serde.
 /// Handles both array format (.NET StandardResolver) and map format (ContractlessStandardResolver).
-/// For maps, normalizes PascalCase keys to camelCase for serde compatibility.
-pub fn value_to_type<T: serde::de::DeserializeOwned>(value: &rmpv::Value) -> Result<T, String> {
+/// With lenient decoding, normalizes the map keys to camelCase and then to snake_case for serde compatibility.
+/// Binary data deserializes into `Vec<u8>` or `serde_bytes::ByteBuf`, and extension types are kept.
+pub fn slice_to_type<T: serde::de::DeserializeOwned>(data: &[u8], options: &MessagePackOptions) -> Result<T, String> {
     // First, try direct deserialization (works for arrays and primitive types)
+    let direct = rmp_serde::from_slice::<T>(data)
+        .map_err(|e| format!("Failed to deserialize from MessagePack: {}", e));
+
+    if direct.is_ok() || options.decoding == MessagePackDecoding::Strict {
+        return direct;
+    }
+
+    // If direct deserialization fails, try normalizing map keys (PascalCase → camelCase, then → snake_case)
+    let value = rmpv::decode::read_value(&mut Cursor::new(data))
+        .map_err(|e| format!("Failed to parse MessagePack: {}", e))?;
+
     let mut buf = Vec::new();
-    rmpv::encode::write_value(&mut buf, value)
+    rmpv::encode::write_value(&mut buf, &normalize_keys_to_camel(&value))
         .map_err(|e| format!("Failed to encode value: {}", e))?;
-
     if let Ok(result) = rmp_serde::from_slice::<T>(&buf) {
         return Ok(result);
     }
 
-    // If direct deserialization fails, try normalizing map keys (PascalCase → camelCase)
-    let normalized = normalize_keys_to_camel(value);
     let mut buf = Vec::new();
-    rmpv::encode::write_value(&mut buf, &normalized)
+    rmpv::encode::write_value(&mut buf, &transform_keys(&value, to_snake_case))
         .map_err(|e| format!("Failed to encode value: {}", e))?;
     rmp_serde::from_slice::<T>(&buf)
         .map_err(|e| format!("Failed to deserialize from MessagePack: {}", e))
@@ -397,6 +585,15 @@ pub fn value_to_type<T: serde::de::DeserializeOwned>(value: &rmpv::Value) -> Res
 mod tests {
     use super::*;
 
+    /// Deserializes a value built with rmpv, the way the payloads of the hub are deserialized.
+    fn value_to_type<T: serde::de::DeserializeOwned>(value: &rmpv::Value, options: &MessagePackOptions) -> Result<T, String> {
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, value)
+            .map_err(|e| format!("Failed to encode value: {}", e))?;
+
+        slice_to_type(&buf, options)
+    }
+
     #[test]
     fn test_varint_roundtrip() {
         for &value in &[0, 1, 53, 127, 128, 5248, 16384, 2_147_483_647] {
@@ -427,9 +624,24 @@ mod tests {
         assert_eq!(messages[1], msg2);
     }
 
+    fn encode(value: rmpv::Value) -> Vec<u8> {
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &value).unwrap();
+        buf
+    }
+
+    fn arguments(message: &HubMessage) -> Vec<rmpv::Value> {
+        match message {
+            HubMessage::Invocation { arguments, .. } => arguments.iter()
+                .map(|argument| rmpv::decode::read_value(&mut Cursor::new(argument.to_msgpack().unwrap())).unwrap())
+                .collect(),
+            other => panic!("Expected an invocation, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_ping_encoding() {
-        let ping = encode_ping();
+        let ping = write_message(&HubMessage::Ping).unwrap();
         assert_eq!(ping, vec![0x91, 0x06]);
 
         let msg_type = read_message_type(&ping).unwrap();
@@ -446,6 +658,15 @@ mod tests {
         assert_eq!(read_message_type(&buf).unwrap(), MessageType::Invocation);
     }
 
+    #[test]
+    fn test_parse_close() {
+        let close = encode(rmpv::Value::Array(vec![rmpv::Value::from(7), rmpv::Value::from("Server shutting down"), rmpv::Value::from(true)]));
+        assert_eq!(parse_message(&close).unwrap(), HubMessage::Close { error: Some("Server shutting down".to_string()), allow_reconnect: true });
+
+        let close = encode(rmpv::Value::Array(vec![rmpv::Value::from(7), rmpv::Value::Nil]));
+        assert_eq!(parse_message(&close).unwrap(), HubMessage::Close { error: None, allow_reconnect: false });
+    }
+
     #[test]
     fn test_json_msgpack_value_roundtrip() {
         let json = serde_json::json!({
@@ -463,29 +684,46 @@ mod tests {
 
     #[test]
     fn test_encode_decode_invocation() {
-        let args = vec![
-            rmpv::Value::String(rmpv::Utf8String::from("hello")),
-            rmpv::Value::Integer(rmpv::Integer::from(42)),
-        ];
+        let invocation = HubMessage::Invocation {
+            invocation_id: Some("inv_1".to_string()),
+            target: "TestMethod".to_string(),
+            arguments: vec![
+                HubPayload::MessagePack(encode(rmpv::Value::from("hello"))),
+                HubPayload::from(serde_json::json!(42)),
+            ],
+            stream_ids: Vec::new(),
+        };
+
+        let encoded = write_message(&invocation).unwrap();
+        assert_eq!(read_message_type(&encoded).unwrap(), MessageType::Invocation);
+
+        let parsed = parse_message(&encoded).unwrap();
+        assert_eq!(arguments(&parsed), vec![rmpv::Value::from("hello"), rmpv::Value::from(42)]);
+        assert!(matches!(parsed, HubMessage::Invocation { ref target, ref invocation_id, .. }
+            if target == "TestMethod" && invocation_id.as_deref() == Some("inv_1")));
+    }
 
-        let encoded = encode_invocation(
-            1,
-            &None,
-            &Some("inv_1".to_string()),
-            "TestMethod",
-            &args,
-            &None,
-        ).unwrap();
+    #[test]
+    fn test_protocol_roundtrip() {
+        let messages = vec![
+            HubMessage::StreamInvocation { invocation_id: "inv_2".to_string(), target: "Stream".to_string(), arguments: Vec::new(), stream_ids: vec!["s1".to_string()] },
+            HubMessage::StreamItem { invocation_id: "inv_2".to_string(), item: HubPayload::MessagePack(encode(rmpv::Value::from(1))) },
+            HubMessage::Completion { invocation_id: "inv_2".to_string(), result: CompletionResult::Void },
+            HubMessage::Completion { invocation_id: "inv_3".to_string(), result: CompletionResult::Result(HubPayload::MessagePack(encode(rmpv::Value::Nil))) },
+            HubMessage::Completion { invocation_id: "inv_4".to_string(), result: CompletionResult::Error("failed".to_string()) },
+            HubMessage::CancelInvocation { invocation_id: "inv_5".to_string() },
+            HubMessage::Ping,
+            HubMessage::Ack { sequence_id: 1 },
+            HubMessage::Sequence { sequence_id: 300 },
+        ];
 
-        let items = parse_msgpack_message(&encoded).unwrap();
-        assert_eq!(items[0].as_u64().unwrap(), 1);
+        let mut frame = Vec::new();
+        for message in &messages {
+            frame.extend(MessagePackHubProtocol.write_message(message).unwrap());
+        }
 
-        let inv = parse_invocation(&items).unwrap();
-        assert_eq!(inv.target, "TestMethod");
-        assert_eq!(inv.invocation_id, Some("inv_1".to_string()));
-        assert_eq!(inv.arguments.len(), 2);
-        assert_eq!(inv.arguments[0].as_str().unwrap(), "hello");
-        assert_eq!(inv.arguments[1].as_u64().unwrap(), 42);
+        let parsed = MessagePackHubProtocol.parse_messages(&frame).into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(parsed, messages);
     }
 
     #[test]
@@ -502,7 +740,7 @@ mod tests {
             (rmpv::Value::String("value".into()), rmpv::Value::Integer(42.into())),
         ]);
 
-        let result: TestStruct = value_to_type(&val).unwrap();
+        let result: TestStruct = value_to_type(&val, &MessagePackOptions::default()).unwrap();
         assert_eq!(result, TestStruct { name: "test".to_string(), value: 42 });
     }
 
@@ -551,25 +789,22 @@ mod tests {
         }
 
         // Step 4: Encode as invocation
-        let args = vec![norm1.clone(), norm2.clone()];
-        let payload = encode_invocation(
-            1,
-            &None,
-            &Some("PushTwoEntities_4".to_string()),
-            "PushTwoEntities",
-            &args,
-            &None,
-        ).unwrap();
+        let payload = write_message(&HubMessage::Invocation {
+            invocation_id: Some("PushTwoEntities_4".to_string()),
+            target: "PushTwoEntities".to_string(),
+            arguments: vec![HubPayload::MessagePack(encode(norm1.clone())), HubPayload::MessagePack(encode(norm2.clone()))],
+            stream_ids: Vec::new(),
+        }).unwrap();
 
         // Step 5: Parse back and verify
-        let items = parse_msgpack_message(&payload).unwrap();
-        let inv = parse_invocation(&items).unwrap();
-        assert_eq!(inv.target, "PushTwoEntities");
-        assert_eq!(inv.arguments.len(), 2);
+        let inv = parse_message(&payload).unwrap();
+        assert!(matches!(inv, HubMessage::Invocation { ref target, .. } if target == "PushTwoEntities"));
+        let args = arguments(&inv);
+        assert_eq!(args.len(), 2);
 
         // Verify argument values survive the round-trip
-        let arg1 = &inv.arguments[0];
-        let arg2 = &inv.arguments[1];
+        let arg1 = &args[0];
+        let arg2 = &args[1];
 
         if let rmpv::Value::Map(entries) = arg1 {
             for (k, v) in entries {
@@ -660,7 +895,7 @@ mod tests {
             rmpv::Value::Integer(42.into()),
             rmpv::Value::String("hello".into()),
         ]);
-        let from_array: TestEntity = value_to_type(&array_val).unwrap();
+        let from_array: TestEntity = value_to_type(&array_val, &MessagePackOptions::default()).unwrap();
         assert_eq!(from_array, TestEntity { number: 42, text: "hello".to_string() });
 
         // Map format with PascalCase keys (from .NET ContractlessStandardResolver)
@@ -668,7 +903,102 @@ mod tests {
             (rmpv::Value::String("Number".into()), rmpv::Value::Integer(42.into())),
             (rmpv::Value::String("Text".into()), rmpv::Value::String("hello".into())),
         ]);
-        let from_map: TestEntity = value_to_type(&map_val).unwrap();
+        let from_map: TestEntity = value_to_type(&map_val, &MessagePackOptions::default()).unwrap();
         assert_eq!(from_map, TestEntity { number: 42, text: "hello".to_string() });
     }
+
+    #[test]
+    fn test_parse_invocation_keeps_binary_arguments() {
+        #[derive(Debug, serde::Deserialize, PartialEq)]
+        struct TestEntity {
+            number: i32,
+            text: String,
+        }
+
+        let args = vec![
+            rmpv::Value::Binary(vec![0, 1, 255]),
+            rmpv::Value::Map(vec![
+                (rmpv::Value::String("Number".into()), rmpv::Value::Integer(7.into())),
+                (rmpv::Value::String("Text".into()), rmpv::Value::String("seven".into())),
+            ]),
+            rmpv::Value::Ext(5, vec![9, 9]),
+        ];
+
+        let encoded = encode(rmpv::Value::Array(vec![
+            rmpv::Value::from(1),
+            rmpv::Value::Map(Vec::new()),
+            rmpv::Value::from("inv_2"),
+            rmpv::Value::from("Upload"),
+            rmpv::Value::Array(args),
+        ]));
+
+        let HubMessage::Invocation { invocation_id, target, arguments, stream_ids } = parse_message(&encoded).unwrap() else {
+            panic!("Expected an invocation");
+        };
+
+        assert_eq!(target, "Upload");
+        assert_eq!(invocation_id, Some("inv_2".to_string()));
+        assert!(stream_ids.is_empty());
+        assert_eq!(arguments.len(), 3);
+
+        let raw = |index: usize| match &arguments[index] {
+            HubPayload::MessagePack(bytes) => bytes.clone(),
+            other => panic!("Expected MessagePack, got {:?}", other),
+        };
+
+        let bytes: Vec<u8> = slice_to_type(&raw(0), &MessagePackOptions::default()).unwrap();
+        assert_eq!(bytes, vec![0, 1, 255]);
+
+        let entity: TestEntity = slice_to_type(&raw(1), &MessagePackOptions::default()).unwrap();
+        assert_eq!(entity, TestEntity { number: 7, text: "seven".to_string() });
+
+        let ext = rmpv::decode::read_value(&mut Cursor::new(raw(2))).unwrap();
+        assert_eq!(ext, rmpv::Value::Ext(5, vec![9, 9]));
+    }
+
+    #[test]
+    fn test_encode_argument_options() {
+        #[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+        struct TestEntity {
+            user_name: String,
+            number: i32,
+        }
+
+        let entity = TestEntity { user_name: "name".to_string(), number: 5 };
+        let decode = |bytes: Vec<u8>| rmpv::decode::read_value(&mut Cursor::new(bytes)).unwrap();
+
+        let array = MessagePackOptions { struct_encoding: StructEncoding::Array, ..Default::default() };
+        assert_eq!(decode(encode_argument(&entity, &array).unwrap()), rmpv::Value::Array(vec!["name".into(), 5.into()]));
+
+        let keys = |naming: KeyNaming| {
+            let options = MessagePackOptions { key_naming: naming, ..Default::default() };
+            match decode(encode_argument(&entity, &options).unwrap()) {
+                rmpv::Value::Map(entries) => entries.iter().map(|(k, _)| k.as_str().unwrap().to_string()).collect::<Vec<_>>(),
+                other => panic!("Expected a map, got {:?}", other),
+            }
+        };
+        assert_eq!(keys(KeyNaming::PascalCase), vec!["UserName", "Number"]);
+        assert_eq!(keys(KeyNaming::CamelCase), vec!["userName", "number"]);
+        assert_eq!(keys(KeyNaming::Preserve), vec!["user_name", "number"]);
+        assert_eq!(keys(KeyNaming::Custom(|k| k.to_uppercase())), vec!["USER_NAME", "NUMBER"]);
+    }
+
+    #[test]
+    fn test_strict_and_lenient_decoding() {
+        #[derive(Debug, serde::Deserialize, PartialEq)]
+        struct TestEntity {
+            user_name: String,
+        }
+
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, &rmpv::Value::Map(vec![
+            (rmpv::Value::String("UserName".into()), rmpv::Value::String("name".into())),
+        ])).unwrap();
+
+        let lenient: TestEntity = slice_to_type(&bytes, &MessagePackOptions::default()).unwrap();
+        assert_eq!(lenient, TestEntity { user_name: "name".to_string() });
+
+        let strict = MessagePackOptions { decoding: MessagePackDecoding::Strict, ..Default::default() };
+        assert!(slice_to_type::<TestEntity>(&bytes, &strict).is_err());
+    }
 }
@@ -0,0 +1,98 @@
+/// How the keys of the objects sent to the hub are renamed.
+///
+/// Set with `MessagePackOptions::key_naming` and `JsonProtocolOptions::key_naming`.
+#[derive(Debug, Clone, Copy)]
+pub enum KeyNaming {
+    /// The keys are sent as serde names them.
+    Preserve,
+    /// `userName` is sent as `UserName`, to match the .NET property names.
+    PascalCase,
+    /// `UserName` is sent as `userName`.
+    CamelCase,
+    /// `userName` is sent as `user_name`.
+    SnakeCase,
+    /// The keys are renamed with the given function.
+    Custom(fn(&str) -> String),
+}
+
+impl KeyNaming {
+    pub(crate) fn transform(&self) -> Option<fn(&str) -> String> {
+        match self {
+            KeyNaming::Preserve => None,
+            KeyNaming::PascalCase => Some(to_pascal_case),
+            KeyNaming::CamelCase => Some(to_camel_case),
+            KeyNaming::SnakeCase => Some(to_snake_case),
+            KeyNaming::Custom(transform) => Some(*transform),
+        }
+    }
+
+    /// Returns the key as it is sent to the hub.
+    pub(crate) fn apply(&self, key: &str) -> String {
+        match self.transform() {
+            Some(transform) => transform(key),
+            None => key.to_string(),
+        }
+    }
+}
+
+/// Convert PascalCase or snake_case to camelCase (lowercase first letter, underscores removed).
+pub(crate) fn to_camel_case(s: &str) -> String {
+    join_words(s, false)
+}
+
+/// Convert camelCase or snake_case to PascalCase (uppercase first letter, underscores removed).
+pub(crate) fn to_pascal_case(s: &str) -> String {
+    join_words(s, true)
+}
+
+/// Uppercase the letters after underscores and drop the underscores, keeping the leading ones.
+fn join_words(s: &str, upper_first: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut started = false;
+    let mut upper = false;
+    for c in s.chars() {
+        if !started {
+            if c != '_' {
+                started = true;
+                result.push(if upper_first { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() });
+            } else {
+                result.push(c);
+            }
+        } else if c == '_' {
+            upper = true;
+        } else {
+            result.push(if upper { c.to_ascii_uppercase() } else { c });
+            upper = false;
+        }
+    }
+    result
+}
+
+/// Convert camelCase or PascalCase to snake_case (an underscore before each word but the first).
+pub(crate) fn to_snake_case(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 4);
+    let mut previous_lower = false;
+    for c in s.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            result.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        result.push(c.to_ascii_lowercase());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_conversions() {
+        assert_eq!(to_snake_case("UserName"), "user_name");
+        assert_eq!(to_snake_case("userName2Id"), "user_name2_id");
+        assert_eq!(to_camel_case("UserName"), "userName");
+        assert_eq!(to_camel_case("user_name"), "userName");
+        assert_eq!(to_pascal_case("userName"), "UserName");
+        assert_eq!(to_pascal_case("_user_name"), "_UserName");
+    }
+}
@@ -11,7 +11,8 @@ pub enum MessageType {
     CancelInvocation = 5,
     Ping = 6,
     Close = 7,
-    Other = 8,
+    Ack = 8,
+    Sequence = 9,
 }
 
 /// Version 0: only `connection_id`, used directly as `?id=` on the transport URL.
@@ -101,10 +102,10 @@ pub struct HandshakeRequest {
 }
 
 impl HandshakeRequest {
-    pub fn new(protocol: impl ToString) -> Self {
+    pub fn new(protocol: impl ToString, version: u8) -> Self {
         HandshakeRequest {
             protocol: protocol.to_string(),
-            version: 1,
+            version,
         }
     }
 }
@@ -117,38 +118,8 @@ pub struct HandshakeResponse {
     error: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-/// Sent by either party to check if the connection is active.
-pub struct Ping {
-    r#type: MessageType,
-}
-
-impl Ping {
-    pub fn new() -> Self {
-        Ping {
-            r#type: MessageType::Ping,
-        }
-    }
-
-    pub fn message_type(&self) -> MessageType {
-        self.r#type
-    }
-}
-
-impl Default for Ping {
-    fn default() -> Self {
-        Ping::new()
+impl HandshakeResponse {
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
     }
 }
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-/// Sent by the server when a connection is closed. Contains an error if the connection was closed because of an error.
-pub struct Close {
-    r#type: MessageType,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    allow_reconnect: Option<bool>,
-}
\ No newline at end of file
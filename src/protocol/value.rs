@@ -0,0 +1,356 @@
+use std::fmt;
+
+use serde::{de::{self, MapAccess, SeqAccess, Visitor}, ser::{SerializeMap, SerializeSeq}, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The name `rmp-serde` reserves for MessagePack extension types.
+pub(crate) const EXT_STRUCT_NAME: &str = "_ExtStruct";
+
+/// A hub value of any type, for calling hub methods without Rust types.
+///
+/// Unlike `serde_json::Value`, it keeps the distinctions of the MessagePack protocol:
+/// binary data, extension types, map keys of any type, and integers apart from floats.
+/// With the JSON protocol binary data is sent as an array of bytes, and an extension type as a `[type, bytes]` pair.
+///
+/// # Examples
+///
+/// ```ignore
+/// let result = client.invoke_dynamic("PushEntity".to_string(), vec![
+///     HubValue::Map(vec![
+///         (HubValue::from("text"), HubValue::from("push1")),
+///         (HubValue::from("number"), HubValue::from(100)),
+///     ]),
+/// ]).await?;
+///
+/// assert_eq!(result, HubValue::Bool(true));
+/// ```
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum HubValue {
+    /// `null` in JSON, `nil` in MessagePack.
+    #[default]
+    Nil,
+    Bool(bool),
+    /// A signed integer, or an unsigned integer that fits into an `i64`.
+    Int(i64),
+    /// An unsigned integer greater than `i64::MAX`.
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<HubValue>),
+    /// The entries of a map in their original order.
+    Map(Vec<(HubValue, HubValue)>),
+    /// A MessagePack extension type with its type tag and data.
+    Ext(i8, Vec<u8>),
+}
+
+impl HubValue {
+    /// Returns `true` if the value is `Nil`.
+    pub fn is_nil(&self) -> bool {
+        matches!(self, HubValue::Nil)
+    }
+
+    /// Returns the string, if the value is a `String`.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            HubValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer, if the value is an integer that fits into an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            HubValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the number as a float, if the value is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            HubValue::Int(value) => Some(*value as f64),
+            HubValue::UInt(value) => Some(*value as f64),
+            HubValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the given string key, if the value is a `Map`.
+    pub fn get(&self, key: &str) -> Option<&HubValue> {
+        match self {
+            HubValue::Map(entries) => entries.iter().find(|(k, _)| k.as_str() == Some(key)).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for HubValue {
+    fn from(value: bool) -> Self {
+        HubValue::Bool(value)
+    }
+}
+
+impl From<i32> for HubValue {
+    fn from(value: i32) -> Self {
+        HubValue::Int(value.into())
+    }
+}
+
+impl From<i64> for HubValue {
+    fn from(value: i64) -> Self {
+        HubValue::Int(value)
+    }
+}
+
+impl From<u64> for HubValue {
+    fn from(value: u64) -> Self {
+        i64::try_from(value).map(HubValue::Int).unwrap_or(HubValue::UInt(value))
+    }
+}
+
+impl From<f64> for HubValue {
+    fn from(value: f64) -> Self {
+        HubValue::Float(value)
+    }
+}
+
+impl From<&str> for HubValue {
+    fn from(value: &str) -> Self {
+        HubValue::String(value.to_string())
+    }
+}
+
+impl From<String> for HubValue {
+    fn from(value: String) -> Self {
+        HubValue::String(value)
+    }
+}
+
+impl From<Vec<u8>> for HubValue {
+    fn from(value: Vec<u8>) -> Self {
+        HubValue::Binary(value)
+    }
+}
+
+impl From<serde_json::Value> for HubValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => HubValue::Nil,
+            serde_json::Value::Bool(value) => HubValue::Bool(value),
+            serde_json::Value::Number(number) => {
+                if let Some(value) = number.as_i64() {
+                    HubValue::Int(value)
+                } else if let Some(value) = number.as_u64() {
+                    HubValue::UInt(value)
+                } else {
+                    HubValue::Float(number.as_f64().unwrap_or(f64::NAN))
+                }
+            },
+            serde_json::Value::String(value) => HubValue::String(value),
+            serde_json::Value::Array(items) => HubValue::Array(items.into_iter().map(HubValue::from).collect()),
+            serde_json::Value::Object(entries) => {
+                HubValue::Map(entries.into_iter().map(|(k, v)| (HubValue::String(k), HubValue::from(v))).collect())
+            },
+        }
+    }
+}
+
+/// Serializes the data of `Binary` and `Ext` as bytes.
+pub(crate) struct Bytes<'a>(pub(crate) &'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Deserializes bytes, which arrive as an array of numbers with formats that have no binary type.
+pub(crate) struct ByteBuf(pub(crate) Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteBufVisitor;
+
+        impl<'de> Visitor<'de> for ByteBufVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+                let mut bytes = Vec::new();
+                while let Some(byte) = seq.next_element::<u8>()? {
+                    bytes.push(byte);
+                }
+                Ok(ByteBuf(bytes))
+            }
+        }
+
+        deserializer.deserialize_bytes(ByteBufVisitor)
+    }
+}
+
+impl Serialize for HubValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            HubValue::Nil => serializer.serialize_unit(),
+            HubValue::Bool(value) => serializer.serialize_bool(*value),
+            HubValue::Int(value) => serializer.serialize_i64(*value),
+            HubValue::UInt(value) => serializer.serialize_u64(*value),
+            HubValue::Float(value) => serializer.serialize_f64(*value),
+            HubValue::String(value) => serializer.serialize_str(value),
+            HubValue::Binary(value) => serializer.serialize_bytes(value),
+            HubValue::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            },
+            HubValue::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            },
+            HubValue::Ext(tag, data) => serializer.serialize_newtype_struct(EXT_STRUCT_NAME, &(tag, Bytes(data))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HubValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(HubValueVisitor)
+    }
+}
+
+struct HubValueVisitor;
+
+impl<'de> Visitor<'de> for HubValueVisitor {
+    type Value = HubValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any hub value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<HubValue, E> {
+        Ok(HubValue::Nil)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<HubValue, E> {
+        Ok(HubValue::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<HubValue, D::Error> {
+        HubValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<HubValue, E> {
+        Ok(HubValue::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<HubValue, E> {
+        Ok(HubValue::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<HubValue, E> {
+        Ok(HubValue::from(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<HubValue, E> {
+        Ok(HubValue::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<HubValue, E> {
+        Ok(HubValue::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<HubValue, E> {
+        Ok(HubValue::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<HubValue, E> {
+        Ok(HubValue::Binary(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<HubValue, E> {
+        Ok(HubValue::Binary(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<HubValue, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(HubValue::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<HubValue, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(HubValue::Map(entries))
+    }
+
+    /// MessagePack extension types arrive as a newtype struct of the type tag and the data.
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<HubValue, D::Error> {
+        let (tag, data) = <(i8, ByteBuf)>::deserialize(deserializer)?;
+        Ok(HubValue::Ext(tag, data.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> HubValue {
+        HubValue::Map(vec![
+            (HubValue::from("text"), HubValue::from("test")),
+            (HubValue::from("number"), HubValue::from(-5)),
+            (HubValue::from("big"), HubValue::from(u64::MAX)),
+            (HubValue::from("ratio"), HubValue::Float(2.0)),
+            (HubValue::from("items"), HubValue::Array(vec![HubValue::Nil, HubValue::Bool(true)])),
+        ])
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let json = serde_json::to_string(&sample()).unwrap();
+        let value: HubValue = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value, sample());
+        assert_eq!(HubValue::from(serde_json::json!({ "items": [null, 1.5] })), HubValue::Map(vec![
+            (HubValue::from("items"), HubValue::Array(vec![HubValue::Nil, HubValue::Float(1.5)])),
+        ]));
+        assert_eq!(value.get("number").and_then(HubValue::as_i64), Some(-5));
+    }
+
+    #[cfg(feature = "messagepack")]
+    #[test]
+    fn test_msgpack_round_trip() {
+        let value = HubValue::Array(vec![
+            sample(),
+            HubValue::Binary(vec![1, 2, 3]),
+            HubValue::Ext(-1, vec![0, 0, 0, 1]),
+            HubValue::Map(vec![(HubValue::from(1), HubValue::from("integer key"))]),
+        ]);
+
+        let bytes = rmp_serde::to_vec(&value).unwrap();
+        let decoded = rmpv::decode::read_value(&mut bytes.as_slice()).unwrap();
+
+        assert!(matches!(&decoded[1], rmpv::Value::Binary(_)));
+        assert!(matches!(&decoded[2], rmpv::Value::Ext(-1, _)));
+        assert_eq!(rmp_serde::from_slice::<HubValue>(&bytes).unwrap(), value);
+    }
+}
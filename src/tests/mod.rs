@@ -13,4 +13,13 @@ pub mod tests_tokio;
 #[cfg(test)]
 #[cfg(not(target_arch="wasm32"))]
 #[cfg(feature = "messagepack")]
-pub mod tests_msgpack;
\ No newline at end of file
+pub mod tests_msgpack;
+
+#[cfg(test)]
+#[cfg(not(target_arch="wasm32"))]
+#[cfg(feature = "macros")]
+pub mod tests_macros;
+
+#[cfg(test)]
+#[cfg(target_arch="wasm32")]
+pub mod tests_browser;
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use js_sys::{Function, Object, Reflect, Symbol};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+use crate::{ConstantDelayPolicy, HubConnection, HubConnectionState, SignalRClient, tests::TestEntity};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+async fn test_reconnect_queue() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+        c.with_automatic_reconnect(ConstantDelayPolicy::new(Duration::from_millis(500), Some(5)));
+        c.with_reconnect_queue(10);
+    }).await.unwrap();
+
+    let mut states = client.state_changes();
+
+    // the hub aborts the connection, so the client reconnects
+    client.send("Abort".to_string()).await.unwrap();
+
+    while let Some(state) = states.next().await {
+        if state == HubConnectionState::Reconnecting {
+            break;
+        }
+    }
+
+    // the invocation is queued while reconnecting, and sent right after the handshake
+    let entity = client.invoke::<TestEntity>("SingleEntity".to_string()).await.unwrap();
+
+    assert_eq!(entity.text, "test".to_string());
+    assert_eq!(client.state(), HubConnectionState::Connected);
+
+    client.disconnect();
+}
+
+async fn test_reconnect(mut client: SignalRClient) {
+    let first_id = client.invoke::<String>("GetConnectionId".to_string()).await.unwrap();
+    let mut states = client.state_changes();
+    assert_eq!(states.next().await, Some(HubConnectionState::Connected));
+
+    // the hub aborts the connection, so the client handshakes again on a new connection
+    client.send("Abort".to_string()).await.unwrap();
+
+    let mut transitions = Vec::new();
+    while let Some(state) = states.next().await {
+        transitions.push(state.clone());
+
+        if state == HubConnectionState::Connected {
+            break;
+        }
+    }
+
+    assert_eq!(transitions, vec![HubConnectionState::Reconnecting, HubConnectionState::Connected]);
+
+    let new_id = client.invoke::<String>("GetConnectionId".to_string()).await.unwrap();
+    assert_ne!(new_id, first_id);
+
+    let entity = client.invoke::<TestEntity>("SingleEntity".to_string()).await.unwrap();
+    assert_eq!(entity.text, "test".to_string());
+
+    client.disconnect();
+}
+
+#[wasm_bindgen_test]
+async fn test_reconnect_handshake() {
+    let client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+        c.with_automatic_reconnect(ConstantDelayPolicy::new(Duration::from_millis(500), Some(5)));
+    }).await.unwrap();
+
+    test_reconnect(client).await;
+}
+
+#[cfg(feature = "messagepack")]
+#[wasm_bindgen_test]
+async fn test_reconnect_messagepack_handshake() {
+    // the hub may send the handshake response in a binary frame, followed by MessagePack messages
+    let client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+        c.with_messagepack_protocol();
+        c.with_automatic_reconnect(ConstantDelayPolicy::new(Duration::from_millis(500), Some(5)));
+    }).await.unwrap();
+
+    test_reconnect(client).await;
+}
+
+fn js_connection() -> HubConnection {
+    let options = Object::new();
+    Reflect::set(&options, &JsValue::from_str("port"), &JsValue::from(5220)).unwrap();
+    Reflect::set(&options, &JsValue::from_str("secure"), &JsValue::FALSE).unwrap();
+
+    HubConnection::new("localhost".to_string(), "test".to_string(), Some(options.unchecked_into())).unwrap()
+}
+
+async fn next_item(iterator: &JsValue) -> Option<JsValue> {
+    let next: Function = Reflect::get(iterator, &JsValue::from_str("next")).unwrap().unchecked_into();
+    let result = JsFuture::from(js_sys::Promise::from(next.call0(iterator).unwrap())).await.unwrap();
+
+    match Reflect::get(&result, &JsValue::from_str("done")).unwrap().as_bool() {
+        Some(true) => None,
+        _ => Some(Reflect::get(&result, &JsValue::from_str("value")).unwrap()),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn test_concurrent_starts() {
+    let connection = js_connection();
+
+    let first = JsFuture::from(connection.start());
+    assert_eq!(connection.state(), "Connecting".to_string());
+
+    // the second start is rejected while the first is connecting
+    assert!(JsFuture::from(connection.start()).await.is_err());
+
+    first.await.unwrap();
+    assert_eq!(connection.state(), "Connected".to_string());
+    assert!(JsFuture::from(connection.start()).await.is_err());
+
+    JsFuture::from(connection.stop()).await.unwrap();
+    assert_eq!(connection.state(), "Disconnected".to_string());
+}
+
+#[wasm_bindgen_test]
+async fn test_stream_async_iterator() {
+    let connection = js_connection();
+    JsFuture::from(connection.start()).await.unwrap();
+
+    // `for await` looks up the iterator with `Symbol.asyncIterator`
+    let stream = JsValue::from(connection.stream("HundredEntities".to_string(), None).unwrap());
+    let iterator_of: Function = Reflect::get(&stream, &Symbol::async_iterator()).unwrap().dyn_into().unwrap();
+    let iterator = iterator_of.call0(&stream).unwrap();
+
+    let mut count = 0;
+    while let Some(item) = next_item(&iterator).await {
+        assert!(Reflect::get(&item, &JsValue::from_str("number")).unwrap().as_f64().is_some());
+        count += 1;
+    }
+
+    assert_eq!(count, 100);
+
+    // `break` calls `return`, which ends the iteration
+    let stream = JsValue::from(connection.stream("HundredEntities".to_string(), None).unwrap());
+    let iterator = iterator_of.call0(&stream).unwrap();
+    assert!(next_item(&iterator).await.is_some());
+
+    let finish: Function = Reflect::get(&iterator, &JsValue::from_str("return")).unwrap().unchecked_into();
+    JsFuture::from(js_sys::Promise::from(finish.call0(&iterator).unwrap())).await.unwrap();
+    assert!(next_item(&iterator).await.is_none());
+
+    JsFuture::from(connection.stop()).await.unwrap();
+}
@@ -0,0 +1,104 @@
+use std::{sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::Duration};
+
+use futures::{Stream, StreamExt};
+use log::info;
+use tokio::time::Instant;
+
+use crate::{hub_client, hub_proxy, CallbackHandler, SignalRClient, tests::TestEntity};
+
+#[hub_proxy]
+trait TestHub {
+    async fn single_entity(&self) -> TestEntity;
+    async fn push_entity(&self, entity: TestEntity) -> bool;
+    async fn push_two_entities(&self, entity1: TestEntity, entity2: TestEntity) -> TestEntity;
+    #[hub_method(name = "TriggerEntityCallback")]
+    async fn trigger_callback(&self, callback: String);
+    fn hundred_entities(&self) -> impl Stream<Item = TestEntity>;
+    async fn trigger_entity_response(&self, callback: String) -> bool;
+}
+
+#[derive(Default)]
+struct TestHandlers {
+    received: Arc<AtomicUsize>,
+}
+
+#[hub_client(rename_all = "none")]
+impl TestHandlers {
+    fn callback1(&self, entity: TestEntity) {
+        info!("Callback1 results entity: {}, {}", entity.text, entity.number);
+        self.received.fetch_add(1, Ordering::SeqCst);
+    }
+
+    async fn callback2(&self, entity: TestEntity) -> TestEntity {
+        info!("Callback2 results entity: {}, {}", entity.text, entity.number);
+        entity
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_hub_proxy() {
+    let client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    let entity = client.single_entity().await.unwrap();
+    assert_eq!(entity.text, "test".to_string());
+
+    let pushed = client.push_entity(TestEntity {
+        text: "push1".to_string(),
+        number: 100,
+    }).await;
+    assert!(pushed.unwrap());
+
+    let merged = client.push_two_entities(TestEntity {
+        text: "entity1".to_string(),
+        number: 200,
+    }, TestEntity {
+        text: "entity2".to_string(),
+        number: 300,
+    }).await.unwrap();
+    assert_eq!(merged.number, 500);
+    info!("Merged Entity {}, {}", merged.text, merged.number);
+
+    assert!(client.trigger_callback("callback1".to_string()).await.is_ok());
+
+    let mut count = 0;
+    {
+        let mut stream = client.hundred_entities();
+        while let Some(item) = stream.next().await {
+            info!("Entity {}, {}", item.text, item.number);
+            count += 1;
+        }
+    }
+    assert_eq!(count, 100);
+
+    client.disconnect();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_hub_client() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    let handlers = TestHandlers::default();
+    let received = handlers.received.clone();
+    let registrations = handlers.register_handlers(&mut client);
+    assert_eq!(registrations.len(), 2);
+
+    assert!(client.trigger_callback("callback1".to_string()).await.is_ok());
+
+    // the handler runs on the dispatcher, so it may finish after the hub method
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while received.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+    assert!(client.trigger_entity_response("callback2".to_string()).await.unwrap());
+
+    registrations.unregister();
+
+    client.disconnect();
+}
@@ -1,8 +1,10 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
 use futures::StreamExt;
 use log::info;
 use tokio::{spawn, time::Instant};
 
-use crate::{DisconnectionHandler, ReconnectionHandler, SignalRClient, execution::CallbackHandler, tests::TestEntity};
+use crate::{ConnectedHookError, ConstantDelayPolicy, DisconnectionHandler, HubConnectionState, ReconnectionHandler, SignalRClient, StreamBuffer, execution::CallbackHandler, tests::TestEntity};
 
 struct MyDisconnectionHandler {
 
@@ -26,6 +28,8 @@ async fn test_service() {
         c.with_disconnection_handler(MyDisconnectionHandler {});
     }).await.unwrap();
 
+    assert_eq!(client.state(), HubConnectionState::Connected);
+
     let re = client.invoke::<TestEntity>("SingleEntity".to_string()).await;
 
     assert!(re.is_ok());
@@ -122,4 +126,310 @@ async fn test_service() {
     c2.unregister();
 
     client.disconnect();
-}
\ No newline at end of file
+}
+
+#[test_log::test(tokio::test)]
+async fn test_panicking_callback() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let failures = errors.clone();
+    client.on_callback_error(move |target, error| failures.lock().unwrap().push((target, error)));
+
+    let c1 = client.register("callback1".to_string(), |_| panic!("callback1 failed"));
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let c2 = client.register("callback2".to_string(), move |ctx| {
+        let _ = sender.send(ctx.argument::<TestEntity>(0));
+    });
+
+    // the hub awaits the result of callback1, so it gets the error completion of the panic
+    let response = client.invoke_with_args::<bool, _>("TriggerEntityResponse".to_string(), |c| {
+        c.argument("callback1".to_string());
+    }).await;
+
+    assert!(response.is_err());
+    assert_eq!(errors.lock().unwrap().len(), 1);
+    assert_eq!(errors.lock().unwrap()[0].0, "callback1".to_string());
+
+    // the client keeps processing the invocations after the panic
+    client.send_with_args("TriggerEntityCallback".to_string(), |c| {
+        c.argument("callback2".to_string());
+    }).await.unwrap();
+
+    let entity = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(entity.text, "callback".to_string());
+
+    c1.unregister();
+    c2.unregister();
+
+    client.disconnect();
+}
+
+async fn wait_for_stream_cancelled(client: &mut SignalRClient) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while Instant::now() < deadline {
+        if client.invoke::<bool>("IsStreamCancelled".to_string()).await == Ok(true) {
+            return true;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    false
+}
+
+#[test_log::test(tokio::test)]
+async fn test_stream_cancellation() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    // dropping the stream before the hub completes it stops the hub method
+    let mut stream = client.enumerate::<TestEntity>("EndlessEntities".to_string()).await;
+
+    for _ in 0..5 {
+        assert!(stream.next().await.is_some());
+    }
+
+    drop(stream);
+
+    assert!(wait_for_stream_cancelled(&mut client).await);
+
+    // so does the overflow of a stream buffer in cancel mode, while the stream is not read
+    let stream = client.enumerate_with_args::<TestEntity, _>("EndlessEntities".to_string(), |c| {
+        c.stream_buffer(StreamBuffer::Cancel { capacity: 10 });
+    }).await;
+
+    assert!(wait_for_stream_cancelled(&mut client).await);
+
+    drop(stream);
+
+    client.disconnect();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invoke_while_reading_stream() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    // with the default buffer the stream is cancelled when it fills up, the results of the invocations still arrive
+    let mut stream = Box::pin(client.try_enumerate::<TestEntity>("MillionEntities".to_string()).await.unwrap());
+
+    for _ in 0..20 {
+        let entity = match stream.next().await {
+            Some(Ok(entity)) => entity,
+            _ => break,
+        };
+
+        let pushed = tokio::time::timeout(Duration::from_secs(5), client.invoke_with_args::<bool, _>("PushEntity".to_string(), |c| {
+            c.argument(entity.clone());
+        })).await;
+
+        assert_eq!(pushed, Ok(Ok(true)));
+    }
+
+    drop(stream);
+
+    let entity = tokio::time::timeout(Duration::from_secs(5), client.invoke::<TestEntity>("SingleEntity".to_string())).await;
+    assert_eq!(entity.unwrap().unwrap().text, "test".to_string());
+
+    // a Backpressure buffer would fill up while the invocations are awaited, and hold back their results
+    let mut stream = client.enumerate_with_args::<TestEntity, _>("MillionEntities".to_string(), |c| {
+        c.stream_buffer(StreamBuffer::Unbounded);
+    }).await;
+
+    for _ in 0..20 {
+        let entity = stream.next().await.unwrap();
+        let pushed = tokio::time::timeout(Duration::from_secs(5), client.invoke_with_args::<bool, _>("PushEntity".to_string(), |c| {
+            c.argument(entity.clone());
+        })).await;
+
+        assert_eq!(pushed, Ok(Ok(true)));
+    }
+
+    drop(stream);
+
+    client.disconnect();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_automatic_reconnect_closes() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+        c.with_automatic_reconnect(ConstantDelayPolicy::new(Duration::from_secs(1), Some(3)));
+    }).await.unwrap();
+
+    assert_eq!(client.state(), HubConnectionState::Connected);
+
+    let first_id = client.invoke::<String>("GetConnectionId".to_string()).await.unwrap();
+    let transitions = Arc::new(Mutex::new(Vec::new()));
+    let reconnecting = transitions.clone();
+    client.on_reconnecting(move |_| reconnecting.lock().unwrap().push(HubConnectionState::Reconnecting));
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let reconnected = transitions.clone();
+    client.on_reconnected(move |connection_id| {
+        reconnected.lock().unwrap().push(HubConnectionState::Connected);
+        let _ = sender.send(connection_id);
+    });
+
+    // the hub aborts the connection, so the transport is dropped and the client reconnects
+    client.send("Abort".to_string()).await.unwrap();
+
+    let new_id = tokio::time::timeout(Duration::from_secs(10), receiver.recv()).await.unwrap().unwrap().unwrap();
+
+    assert_eq!(*transitions.lock().unwrap(), vec![HubConnectionState::Reconnecting, HubConnectionState::Connected]);
+    assert_eq!(client.state(), HubConnectionState::Connected);
+    assert_ne!(new_id, first_id);
+    assert_eq!(client.invoke::<String>("GetConnectionId".to_string()).await, Ok(new_id));
+
+    let closed = Arc::new(Mutex::new(Vec::new()));
+    let errors = closed.clone();
+    client.on_closed(move |error| errors.lock().unwrap().push(error));
+
+    let state = client.clone();
+    client.disconnect();
+
+    // the clone keeps the connection open
+    assert_eq!(state.state(), HubConnectionState::Connected);
+    state.disconnect();
+
+    assert_eq!(*closed.lock().unwrap(), vec![None]);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_drop_closes_with_callbacks() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    // the callback and the stream keep clients of their own, which do not keep the connection open
+    let _callback = client.register("callback1".to_string(), |_| {});
+    let _stream = client.enumerate::<TestEntity>("EndlessEntities".to_string()).await;
+    let connection_id = client.invoke::<String>("GetConnectionId".to_string()).await.unwrap();
+
+    drop(client);
+
+    let mut observer = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut disconnected = false;
+
+    while !disconnected && Instant::now() < deadline {
+        disconnected = observer.invoke_with_args::<bool, _>("IsDisconnected".to_string(), |c| {
+            c.argument(connection_id.clone());
+        }).await == Ok(true);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    assert!(disconnected);
+
+    observer.disconnect();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_connected_hooks() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let entities = calls.clone();
+
+    let client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+
+        let entities = entities.clone();
+        c.on_connected(move |mut client| {
+            let entities = entities.clone();
+
+            async move {
+                // the hook sets up the connection before the client is marked connected
+                assert_eq!(client.state(), HubConnectionState::Connecting);
+
+                let entity = client.invoke::<TestEntity>("SingleEntity".to_string()).await?;
+                entities.lock().unwrap().push(entity.text);
+
+                Ok(())
+            }
+        });
+    }).await.unwrap();
+
+    assert_eq!(*calls.lock().unwrap(), vec!["test".to_string()]);
+    assert_eq!(client.state(), HubConnectionState::Connected);
+
+    client.disconnect();
+
+    // a hook failing with Retry on the initial connection gets another connection from the policy
+    let attempts = Arc::new(Mutex::new(Vec::new()));
+    let ids = attempts.clone();
+
+    let retried = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+        c.with_automatic_reconnect(ConstantDelayPolicy::new(Duration::from_millis(100), Some(3)));
+
+        let ids = ids.clone();
+        c.on_connected(move |mut client| {
+            let ids = ids.clone();
+
+            async move {
+                let id = client.invoke::<String>("GetConnectionId".to_string()).await?;
+                let first = ids.lock().unwrap().is_empty();
+                ids.lock().unwrap().push(id);
+
+                if first {
+                    return Err(ConnectedHookError::Retry("not ready yet".to_string()));
+                }
+
+                Ok(())
+            }
+        });
+    }).await.unwrap();
+
+    let ids = attempts.lock().unwrap().clone();
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+    assert_eq!(retried.state(), HubConnectionState::Connected);
+
+    retried.disconnect();
+
+    let aborted = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+        c.on_connected(|_| async { Err(ConnectedHookError::Abort("not allowed".to_string())) });
+    }).await;
+
+    assert!(aborted.err().unwrap().contains("not allowed"));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_stop() {
+    let mut client = SignalRClient::connect_with("localhost", "test", |c| {
+        c.with_port(5220);
+        c.unsecure();
+    }).await.unwrap();
+
+    let other = client.clone();
+    let entity = client.invoke::<TestEntity>("SingleEntity".to_string()).await.unwrap();
+    assert_eq!(entity.text, "test".to_string());
+
+    // stopping closes the connection for every clone
+    other.stop_with_drain(Duration::from_secs(1)).await;
+
+    assert_eq!(client.state(), HubConnectionState::Disconnected(None));
+    assert!(client.invoke::<TestEntity>("SingleEntity".to_string()).await.is_err());
+
+    // stopping again does nothing
+    client.stop().await;
+}